@@ -7,9 +7,54 @@ configs.etymora = {
     cmd = { "etymora" },
     -- filetypes = { "markdown" },
     root_dir = util.root_pattern(".git", "Cargo.toml"),
+    -- The same keys are read from etymora.toml in the project and in ~/.config/etymora/. See etymora.toml
     init_options = {
       dict_config = "example",
+      -- dict_config = { stardict = { path = "/path/to/dictionary.ifo" } },
+      -- dict_config = { dictd = { host = "localhost", port = 2628, databases = { "wn" }, headwords_strategy = "re" } },
+      -- dict_config = { wiktionary = { path = "/path/to/kaikki.jsonl", lang_code = "en" } },
+      -- dict_config = { wordnet = { path = "/path/to/WordNet-3.0/dict" } },
+      -- dict_config = { jmdict = { path = "/path/to/JMdict_e.gz", lang = "eng" } },
+      -- Adapters in any language over JSON-RPC on stdio. See crates/adapter-external for the protocol
+      -- dict_config = { external = { command = "my-adapter", args = { "--stdio" }, options = { lang = "en" } } },
+      -- WASM components implementing crates/adapter-wasm/wit/dictionary.wit. Only `data_dir` is readable from them
+      -- dict_config = { wasm = { path = "/path/to/adapter.wasm", data_dir = "/path/to/data", options = {}, timeout = 5000, max_memory = 256 } },
+      -- dict_config = {
+      --   sqlite = {
+      --     path = "/path/to/glossary.sqlite",
+      --     lookup_query = "SELECT * FROM terms WHERE term = :word COLLATE NOCASE",
+      --     prefix_query = "SELECT term, definition FROM terms_fts WHERE terms_fts MATCH :query ORDER BY term LIMIT :limit",
+      --     fuzzy_query = "SELECT terms.* FROM terms_fts JOIN terms ON terms.id = terms_fts.rowid WHERE terms_fts MATCH :query ORDER BY rank LIMIT :limit",
+      --     headwords_query = "SELECT term FROM terms",
+      --     headword = "term",
+      --     fields = { { column = "definition" }, { column = "note", label = "Note" } },
+      --   },
+      -- },
+      -- Dictionaries in priority order. `lookup_mode = "merge"` shows all of them which have the word
+      -- dict_config = {
+      --   { dict = { jmdict = { path = "/path/to/JMdict_e.gz" } }, name = "JMdict", languages = { "ja" } },
+      --   { dict = { wordnet = { path = "/path/to/WordNet-3.0/dict" } }, name = "WordNet" },
+      --   { dict = { dictd = { host = "localhost" } }, enabled = false },
+      -- },
+      -- lookup_mode = "first",
+      -- The projects whose etymora.toml can use the `external` and `wasm` dictionaries, which run code
+      -- trusted_projects = { "/path/to/project" },
+      -- segment = { apostrophe = "join", hyphen = "split", phrase_words = 4 },
+      -- segment = { cjk = "auto", japanese_dictionary = "/path/to/mecab-ipadic-utf8" },
+      -- Look up only comments and strings in Rust and Python. Source code is prose by default
+      -- segment = { syntax = true },
+      -- Report the words which no dictionary knows. URLs, code spans and identifiers are skipped
+      -- spell_check = { enabled = true, severity = "information", ignore = { "etymora" }, debounce = 500 },
+      -- The first route matching the languageId and the path is used. A marker like `lang: de` in the document overrides `language`
+      -- routes = {
+      --   { globs = { "docs/de/**" }, language = "de" },
+      --   { language_ids = { "markdown", "plaintext" }, dictionaries = { "WordNet" } },
+      -- },
+      -- Guess the language of the hovered sentence. Low-confidence results fall back to the language of the route
+      -- detect_language = { enabled = true, min_confidence = 0.5, languages = { "en", "de", "fr" } },
     },
+    -- Applied without restarting the server. Each of them replaces the one in init_options
+    -- settings = { etymora = { lookup_mode = "merge", spell_check = { enabled = true } } },
   },
   docs = {
     description = [[
@@ -21,3 +66,19 @@ configs.etymora = {
 }
 
 lspconfig.etymora.setup({})
+
+-- `etymora/lookup` looks up an arbitrary word. `dictionary` and `language` are optional
+vim.api.nvim_create_user_command("EtymoraLookup", function(opts)
+  local client = vim.lsp.get_clients({ name = "etymora" })[1]
+  if client == nil then
+    return
+  end
+
+  client:request("etymora/lookup", { word = opts.args }, function(err, result)
+    if err ~= nil or result == nil or result.markdown == vim.NIL then
+      vim.notify("No entry for " .. opts.args)
+      return
+    end
+    vim.lsp.util.open_floating_preview(vim.split(result.markdown, "\n"), "markdown")
+  end)
+end, { nargs = 1 })
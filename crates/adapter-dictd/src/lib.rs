@@ -0,0 +1,703 @@
+//! DICT protocol(RFC 2229) adapter
+//! Keeps a connection to a `dictd` server, and uses `DEFINE` and `MATCH`.
+//! The connection is made on the first lookup, and made again after errors.
+
+use std::{collections::BTreeSet, future::Future, pin::Pin, time::Duration};
+
+use etymora_traits::{fold_case, Completion, Dictionary, Word};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::Mutex,
+};
+
+const DEFAULT_PORT: u16 = 2628;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DictdConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// The databases to search in the order. `!` searches all until a match, `*` all
+    #[serde(default = "default_databases")]
+    pub databases: Vec<String>,
+    /// The strategy of `MATCH` used to check the existence of words
+    #[serde(default = "default_strategy")]
+    pub strategy: String,
+    /// The regular expression strategy of `MATCH`(like `re`) to list all the headwords with `.` for spelling suggestions.
+    /// The databases `!` and `*` are expanded with `SHOW DB`. No words are suggested if it's not set
+    #[serde(default)]
+    pub headwords_strategy: Option<String>,
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+fn default_databases() -> Vec<String> {
+    vec!["!".into()]
+}
+
+fn default_strategy() -> String {
+    "exact".into()
+}
+
+#[derive(Debug, Error)]
+pub enum DictdError {
+    #[error("Failed to communicate with the DICT server: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("Timed out communicating with the DICT server")]
+    Timeout,
+    #[error("The DICT server closed the connection")]
+    Closed,
+    #[error("Unexpected response from the DICT server: {0}")]
+    UnexpectedResponse(String),
+}
+
+#[derive(Debug)]
+pub struct DictdDictionary {
+    config: DictdConfig,
+    /// `None` until the first lookup, and after errors
+    connection: Mutex<Option<Connection>>,
+}
+
+/// The commands run on a connection by `DictdDictionary::with_connection`
+type Commands<'c, T> = Pin<Box<dyn Future<Output = Result<T, DictdError>> + Send + 'c>>;
+
+/// A definition from `DEFINE`
+#[derive(Debug, PartialEq, Eq)]
+struct Definition {
+    database_description: String,
+    text: String,
+}
+
+/// A connection to the DICT server
+#[derive(Debug)]
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+/// Quote the word for commands
+fn quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The status code and the rest of a status line
+fn parse_status(line: &str) -> Result<(u16, &str), DictdError> {
+    line.get(..3)
+        .and_then(|code| code.parse().ok())
+        .map(|code| (code, line[3..].trim()))
+        .ok_or_else(|| DictdError::UnexpectedResponse(line.into()))
+}
+
+/// Split the first quoted(or not quoted) word from the text
+fn split_quoted(text: &str) -> Option<(String, &str)> {
+    let text = text.trim_start();
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut word = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => word.extend(chars.next().map(|(_, c)| c)),
+                '"' => return Some((word, &rest[i + 1..])),
+                c => word.push(c),
+            }
+        }
+        None
+    } else {
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        (end > 0).then(|| (text[..end].to_string(), &text[end..]))
+    }
+}
+
+impl Connection {
+    async fn connect(config: &DictdConfig) -> Result<Self, DictdError> {
+        let stream = tokio::time::timeout(
+            TIMEOUT,
+            TcpStream::connect((config.host.as_str(), config.port)),
+        )
+        .await
+        .map_err(|_| DictdError::Timeout)?
+        .map_err(DictdError::Io)?;
+
+        let (reader, writer) = stream.into_split();
+        let mut connection = Connection {
+            reader: BufReader::new(reader),
+            writer,
+        };
+
+        // The banner
+        let (code, line) = connection.status().await?;
+        if code != 220 {
+            return Err(DictdError::UnexpectedResponse(line));
+        }
+
+        connection.command("CLIENT etymora").await?;
+        connection.status().await?;
+
+        Ok(connection)
+    }
+
+    async fn read_line(&mut self) -> Result<String, DictdError> {
+        let mut line = String::new();
+        let read = tokio::time::timeout(TIMEOUT, self.reader.read_line(&mut line))
+            .await
+            .map_err(|_| DictdError::Timeout)?
+            .map_err(DictdError::Io)?;
+        if read == 0 {
+            return Err(DictdError::Closed);
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    async fn status(&mut self) -> Result<(u16, String), DictdError> {
+        let line = self.read_line().await?;
+        let (code, _) = parse_status(&line)?;
+        Ok((code, line))
+    }
+
+    /// Read a text response terminated by a line with a single dot
+    async fn text(&mut self) -> Result<Vec<String>, DictdError> {
+        let mut lines = vec![];
+        loop {
+            let line = self.read_line().await?;
+            if line == "." {
+                return Ok(lines);
+            }
+            // Lines starting with a dot are doubled
+            lines.push(line.strip_prefix('.').unwrap_or(&line).to_string());
+        }
+    }
+
+    async fn command(&mut self, command: &str) -> Result<(), DictdError> {
+        self.writer
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await
+            .map_err(DictdError::Io)
+    }
+
+    /// `DEFINE database word`
+    async fn define(&mut self, database: &str, word: &str) -> Result<Vec<Definition>, DictdError> {
+        self.command(&format!("DEFINE {} {}", quote(database), quote(word)))
+            .await?;
+
+        let (code, line) = self.status().await?;
+        match code {
+            // No match
+            552 => return Ok(vec![]),
+            150 => (),
+            _ => return Err(DictdError::UnexpectedResponse(line)),
+        }
+
+        let mut definitions = vec![];
+        loop {
+            let (code, line) = self.status().await?;
+            match code {
+                151 => {
+                    // 151 "word" database "description"
+                    let (_, rest) = parse_status(&line)?;
+                    let description = split_quoted(rest)
+                        .and_then(|(_, rest)| split_quoted(rest))
+                        .and_then(|(database, rest)| {
+                            split_quoted(rest)
+                                .map(|(description, _)| description)
+                                .or(Some(database))
+                        })
+                        .unwrap_or_default();
+
+                    definitions.push(Definition {
+                        database_description: description,
+                        text: self.text().await?.join("\n"),
+                    });
+                }
+                250 => return Ok(definitions),
+                _ => return Err(DictdError::UnexpectedResponse(line)),
+            }
+        }
+    }
+
+    /// `MATCH database strategy word`, returning the matched words
+    async fn match_words(
+        &mut self,
+        database: &str,
+        strategy: &str,
+        word: &str,
+    ) -> Result<Vec<String>, DictdError> {
+        self.command(&format!(
+            "MATCH {} {} {}",
+            quote(database),
+            quote(strategy),
+            quote(word)
+        ))
+        .await?;
+
+        let (code, line) = self.status().await?;
+        match code {
+            552 => return Ok(vec![]),
+            152 => (),
+            _ => return Err(DictdError::UnexpectedResponse(line)),
+        }
+
+        // database "word"
+        let words = self
+            .text()
+            .await?
+            .iter()
+            .filter_map(|line| split_quoted(line).and_then(|(_, rest)| split_quoted(rest)))
+            .map(|(word, _)| word)
+            .collect();
+
+        let (code, line) = self.status().await?;
+        if code != 250 {
+            return Err(DictdError::UnexpectedResponse(line));
+        }
+
+        Ok(words)
+    }
+
+    /// `SHOW DB`, returning the names of the databases
+    async fn show_databases(&mut self) -> Result<Vec<String>, DictdError> {
+        self.command("SHOW DB").await?;
+
+        let (code, line) = self.status().await?;
+        match code {
+            // No databases present
+            554 => return Ok(vec![]),
+            110 => (),
+            _ => return Err(DictdError::UnexpectedResponse(line)),
+        }
+
+        // database "description"
+        let databases = self
+            .text()
+            .await?
+            .iter()
+            .filter_map(|line| split_quoted(line))
+            .map(|(database, _)| database)
+            .collect();
+
+        let (code, line) = self.status().await?;
+        if code != 250 {
+            return Err(DictdError::UnexpectedResponse(line));
+        }
+
+        Ok(databases)
+    }
+
+    /// Whether any of the databases has the word
+    async fn exists(&mut self, config: &DictdConfig, word: &str) -> Result<bool, DictdError> {
+        for database in &config.databases {
+            if !self
+                .match_words(database, &config.strategy, word)
+                .await?
+                .is_empty()
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The definitions of all the databases
+    async fn define_all(
+        &mut self,
+        databases: &[String],
+        word: &str,
+    ) -> Result<Vec<Definition>, DictdError> {
+        let mut definitions = vec![];
+        for database in databases {
+            definitions.extend(self.define(database, word).await?);
+        }
+        Ok(definitions)
+    }
+
+    /// The words matched in all the databases, without duplicates
+    async fn match_all(
+        &mut self,
+        databases: &[String],
+        strategy: &str,
+        word: &str,
+    ) -> Result<Vec<String>, DictdError> {
+        let mut words: Vec<String> = vec![];
+        for database in databases {
+            for word in self.match_words(database, strategy, word).await? {
+                if !words.contains(&word) {
+                    words.push(word);
+                }
+            }
+        }
+        Ok(words)
+    }
+
+    /// The databases with `!` and `*` expanded
+    async fn expand_databases(&mut self, databases: &[String]) -> Result<Vec<String>, DictdError> {
+        let mut expanded = vec![];
+        for database in databases {
+            match database.as_str() {
+                "!" | "*" => expanded.extend(self.show_databases().await?),
+                _ => expanded.push(database.clone()),
+            }
+        }
+        Ok(expanded)
+    }
+}
+
+impl DictdDictionary {
+    /// Run the commands on the connection, connecting if it's not connected.
+    /// A broken connection is dropped, and the commands are retried once on a new connection
+    /// if the reused one is broken, since the server closes idle connections
+    async fn with_connection<T>(
+        &self,
+        commands: impl for<'c> Fn(&'c mut Connection) -> Commands<'c, T>,
+    ) -> Result<T, DictdError> {
+        let mut connection = self.connection.lock().await;
+        loop {
+            let reused = connection.is_some();
+            let current = match connection.as_mut() {
+                Some(current) => current,
+                None => connection.insert(Connection::connect(&self.config).await?),
+            };
+
+            match commands(current).await {
+                Ok(result) => return Ok(result),
+                Err(e) => {
+                    *connection = None;
+                    if !(reused && matches!(e, DictdError::Io(_) | DictdError::Closed)) {
+                        return Err(e);
+                    }
+                    tracing::warn!("Reconnecting to the DICT server: {e}");
+                }
+            }
+        }
+    }
+}
+
+impl Dictionary for DictdDictionary {
+    type Error = DictdError;
+    type InitInput = DictdConfig;
+
+    async fn init(input: &Self::InitInput) -> Result<Self, Self::Error> {
+        // The server is connected on lookups, so that it can be started later
+        tracing::info!("Using the DICT server at {}:{}", input.host, input.port);
+
+        Ok(DictdDictionary {
+            config: input.clone(),
+            connection: Mutex::new(None),
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        let word = word.as_str().to_string();
+        self.with_connection(|connection| {
+            let (config, word) = (self.config.clone(), word.clone());
+            Box::pin(async move { connection.exists(&config, &word).await })
+        })
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn lookup_ditail(&self, word: &Word) -> Result<Option<String>, Self::Error> {
+        let definitions = self
+            .with_connection(|connection| {
+                let (databases, word) = (self.config.databases.clone(), word.as_str().to_string());
+                Box::pin(async move { connection.define_all(&databases, &word).await })
+            })
+            .await?;
+
+        if definitions.is_empty() {
+            return Ok(None);
+        }
+
+        let sections = definitions
+            .iter()
+            .map(|definition| {
+                format!(
+                    "## {}\n\n```text\n{}\n```\n",
+                    definition.database_description,
+                    definition.text.trim_end()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(Some(format!("# {word}\n\n{sections}")))
+    }
+
+    /// `MATCH` with the `prefix` strategy. The glosses are not available without `DEFINE`
+    #[tracing::instrument(skip(self))]
+    async fn prefix_search(
+        &self,
+        prefix: &Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        let words = self
+            .with_connection(|connection| {
+                let (databases, prefix) =
+                    (self.config.databases.clone(), prefix.as_str().to_string());
+                Box::pin(async move { connection.match_all(&databases, "prefix", &prefix).await })
+            })
+            .await?;
+
+        Ok(words
+            .into_iter()
+            .take(limit)
+            .map(|word| Completion::new(word, None))
+            .collect())
+    }
+
+    /// `MATCH` every word with `headwords_strategy`
+    #[tracing::instrument(skip(self))]
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        let Some(strategy) = &self.config.headwords_strategy else {
+            return Ok(vec![]);
+        };
+
+        let words = self
+            .with_connection(|connection| {
+                let (databases, strategy) = (self.config.databases.clone(), strategy.clone());
+                Box::pin(async move {
+                    let databases = connection.expand_databases(&databases).await?;
+                    connection.match_all(&databases, &strategy, ".").await
+                })
+            })
+            .await?;
+
+        let headwords: BTreeSet<String> = words.iter().map(|word| fold_case(word)).collect();
+        Ok(headwords.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::net::TcpListener;
+
+    /// Start a mock DICT server which knows "apple" in the database "fruits".
+    /// It closes the connection on `MATCH` of "close". The number of the connections is counted
+    async fn mock_server() -> (u16, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+
+                    writer
+                        .write_all(b"220 mock dictd <auth.mime> <1@mock>\r\n")
+                        .await
+                        .unwrap();
+
+                    while let Some(line) = lines.next_line().await.unwrap() {
+                        let response: &[u8] = match line.as_str() {
+                            "CLIENT etymora" => b"250 ok\r\n",
+                            r#"DEFINE "fruits" "apple""# | r#"DEFINE "!" "apple""# => {
+                                b"150 1 definitions retrieved\r\n\
+                                  151 \"apple\" fruits \"Fruits Dictionary\"\r\n\
+                                  apple\r\n\
+                                  \x20  A round fruit.\r\n\
+                                  ..dotted\r\n\
+                                  .\r\n\
+                                  250 ok\r\n"
+                            }
+                            r#"MATCH "fruits" "exact" "apple""#
+                            | r#"MATCH "!" "exact" "apple""# => {
+                                b"152 1 matches found\r\n\
+                                  fruits \"apple\"\r\n\
+                                  .\r\n\
+                                  250 ok\r\n"
+                            }
+                            r#"MATCH "fruits" "prefix" "ap""# => {
+                                b"152 2 matches found\r\n\
+                                  fruits \"apple\"\r\n\
+                                  fruits \"apricot\"\r\n\
+                                  .\r\n\
+                                  250 ok\r\n"
+                            }
+                            "SHOW DB" => {
+                                b"110 2 databases present\r\n\
+                                  fruits \"Fruits Dictionary\"\r\n\
+                                  vegetables \"Vegetables Dictionary\"\r\n\
+                                  .\r\n\
+                                  250 ok\r\n"
+                            }
+                            r#"MATCH "fruits" "re" ".""# => {
+                                b"152 2 matches found\r\n\
+                                  fruits \"Apple\"\r\n\
+                                  fruits \"apricot\"\r\n\
+                                  .\r\n\
+                                  250 ok\r\n"
+                            }
+                            r#"MATCH "fruits" "exact" "close""# => break,
+                            "QUIT" => {
+                                writer.write_all(b"221 bye\r\n").await.unwrap();
+                                break;
+                            }
+                            l if l.starts_with("DEFINE \"unknown\"") => b"550 invalid database\r\n",
+                            l if l.starts_with("DEFINE") || l.starts_with("MATCH") => {
+                                b"552 no match\r\n"
+                            }
+                            _ => b"500 unknown command\r\n",
+                        };
+                        writer.write_all(response).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        (port, connections)
+    }
+
+    async fn dictionary(port: u16, databases: &[&str]) -> DictdDictionary {
+        DictdDictionary::init(&DictdConfig {
+            host: "127.0.0.1".into(),
+            port,
+            databases: databases.iter().map(|db| db.to_string()).collect(),
+            strategy: default_strategy(),
+            headwords_strategy: Some("re".into()),
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dictd_dictionary() {
+        let (port, _) = mock_server().await;
+
+        for databases in [&["fruits"][..], &["!"], &["vegetables", "fruits"]] {
+            let dict = dictionary(port, databases).await;
+
+            assert!(dict.exits(&"apple".into()).await.unwrap());
+            assert!(!dict.exits(&"cherry".into()).await.unwrap());
+
+            assert_eq!(
+                dict.lookup_ditail(&"apple".into()).await.unwrap().unwrap(),
+                "# apple\n\n## Fruits Dictionary\n\n```text\napple\n   A round fruit.\n.dotted\n```\n"
+            );
+            assert_eq!(dict.lookup_ditail(&"cherry".into()).await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dictd_prefix_search() {
+        let (port, _) = mock_server().await;
+        let dict = dictionary(port, &["vegetables", "fruits"]).await;
+
+        assert_eq!(
+            dict.prefix_search(&"ap".into(), 10).await.unwrap(),
+            vec![
+                Completion::new("apple", None),
+                Completion::new("apricot", None)
+            ]
+        );
+        assert_eq!(dict.prefix_search(&"ap".into(), 1).await.unwrap().len(), 1);
+        assert!(dict
+            .prefix_search(&"ch".into(), 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dictd_headwords() {
+        let (port, _) = mock_server().await;
+
+        for databases in [&["fruits"][..], &["!"], &["*", "fruits"]] {
+            assert_eq!(
+                dictionary(port, databases).await.headwords().await.unwrap(),
+                vec!["apple", "apricot"]
+            );
+        }
+
+        let dict = DictdDictionary::init(&DictdConfig {
+            headwords_strategy: None,
+            ..dictionary(port, &["fruits"]).await.config
+        })
+        .await
+        .unwrap();
+        assert!(dict.headwords().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dictd_connection() {
+        let (port, connections) = mock_server().await;
+        let dict = dictionary(port, &["fruits"]).await;
+
+        // Connected on the first lookup, and reused
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+        assert!(dict.exits(&"apple".into()).await.unwrap());
+        assert!(dict.lookup_ditail(&"apple".into()).await.unwrap().is_some());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // Retried once on a new connection
+        assert!(matches!(
+            dict.exits(&"close".into()).await,
+            Err(DictdError::Closed)
+        ));
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        // Reconnected
+        assert!(dict.exits(&"apple".into()).await.unwrap());
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_dictd_errors() {
+        let (port, _) = mock_server().await;
+
+        assert!(matches!(
+            dictionary(port, &["unknown"])
+                .await
+                .lookup_ditail(&"apple".into())
+                .await,
+            Err(DictdError::UnexpectedResponse(_))
+        ));
+
+        // Nothing listens on the port
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        assert!(dictionary(closed_port, &["!"])
+            .await
+            .exits(&"apple".into())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_split_quoted() {
+        assert_eq!(
+            split_quoted(r#""a \"b\"" c"#),
+            Some((r#"a "b""#.to_string(), " c"))
+        );
+        assert_eq!(
+            split_quoted("fruits \"apple\""),
+            Some(("fruits".to_string(), " \"apple\""))
+        );
+        assert_eq!(split_quoted("  "), None);
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+    }
+}
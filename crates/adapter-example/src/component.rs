@@ -0,0 +1,75 @@
+//! `ExampleDictionary` as a WASM component, which is a sample of the guest of `adapter-wasm`.
+//! Build with `cargo build -p adapter-example --target wasm32-wasip2 --release`
+
+use std::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use etymora_traits::Dictionary;
+
+use crate::ExampleDictionary;
+
+wit_bindgen::generate!({
+    path: "../adapter-wasm/wit",
+    world: "adapter",
+});
+
+struct Component;
+
+use exports::etymora::dictionary::dictionary::{Completion, Guest};
+
+impl Guest for Component {
+    fn init(_options: String) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn exists(word: String) -> Result<bool, String> {
+        block_on(ExampleDictionary.exits(&word.into())).map_err(|e| e.to_string())
+    }
+
+    fn lookup_detail(word: String) -> Result<Option<String>, String> {
+        block_on(ExampleDictionary.lookup_ditail(&word.into())).map_err(|e| e.to_string())
+    }
+
+    fn prefix_search(prefix: String, limit: u32) -> Result<Vec<Completion>, String> {
+        let completions = block_on(ExampleDictionary.prefix_search(&prefix.into(), limit as usize))
+            .map_err(|e| e.to_string())?;
+
+        Ok(completions
+            .into_iter()
+            .map(|completion| Completion {
+                word: completion.word,
+                gloss: completion.gloss,
+            })
+            .collect())
+    }
+
+    fn headwords() -> Result<Vec<String>, String> {
+        block_on(ExampleDictionary.headwords()).map_err(|e| e.to_string())
+    }
+}
+
+export!(Component);
+
+/// Components are called synchronously, and the futures of `ExampleDictionary` never wait
+fn block_on<F: Future>(future: F) -> F::Output {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(std::ptr::null(), &VTABLE),
+        |_| (),
+        |_| (),
+        |_| (),
+    );
+
+    // SAFETY: The waker does nothing
+    let waker = unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) };
+    let mut context = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(target_arch = "wasm32")]
+mod component;
+
 use etymora_traits::{Dictionary, Word};
 use thiserror::Error;
 
@@ -21,9 +24,10 @@ impl Dictionary for ExampleDictionary {
         Ok(ExampleDictionary)
     }
 
+    /// Every single word exists, but phrases don't
     #[tracing::instrument]
-    async fn exits(&self, _word: &Word) -> Result<bool, Self::Error> {
-        Ok(true)
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        Ok(!word.as_str().contains(char::is_whitespace))
     }
 
     #[tracing::instrument]
@@ -49,6 +53,7 @@ mod tests {
     async fn test_example_dictionary() {
         let dict = ExampleDictionary;
         assert!(dict.exits(&"lang".into()).await.unwrap());
+        assert!(!dict.exits(&"give up".into()).await.unwrap());
     }
 
     #[tokio::test]
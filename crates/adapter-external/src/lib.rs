@@ -0,0 +1,501 @@
+//! External adapter
+//! Spawns a command, and looks up words with it over JSON-RPC 2.0 on its stdio.
+//! Adapters can be written in any language with this protocol.
+//!
+//! # Protocol
+//!
+//! Each message is a JSON-RPC 2.0 object on a line(UTF-8, terminated by `\n`).
+//! Etymora sends requests to the stdin of the adapter, and the adapter writes the responses to its stdout.
+//! Lines without `id` from the adapter(like notifications) are ignored, and stderr is inherited.
+//!
+//! | Method          | Params                            | Result                                    |
+//! | --------------- | --------------------------------- | ----------------------------------------- |
+//! | `initialize`    | `{ "options": <options> }`        | Anything. Sent once after the spawn       |
+//! | `exists`        | `{ "word": "give up" }`           | `true` if the word is in the dictionary   |
+//! | `lookup`        | `{ "word": "give up" }`           | The markdown of the word, or `null`       |
+//! | `prefix_search` | `{ "prefix": "gi", "limit": 50 }` | `[{ "word": "give up", "gloss": "..." }]` |
+//! | `headwords`     | `{}`                              | `["give up", ...]`, all the words         |
+//!
+//! Errors are responded as JSON-RPC errors like `{ "code": -32000, "message": "..." }`.
+//! `prefix_search` and `headwords` are optional, and the adapters without them respond "Method not found"(`-32601`).
+//! `headwords` is called once, on the first spelling suggestion.
+//! The adapter is restarted if it has exited, and killed if it doesn't respond in time.
+//! It is killed when the server exits.
+
+use std::{collections::BTreeMap, process::Stdio, time::Duration};
+
+use etymora_traits::{fold_case, Completion, Dictionary, Word};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::Mutex,
+};
+
+/// The JSON-RPC error code of the methods which the adapter doesn't have
+const METHOD_NOT_FOUND: i64 = -32601;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExternalConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Passed to the adapter with `initialize`
+    #[serde(default)]
+    pub options: Value,
+    /// The timeout of each request in milliseconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// How many times in a row the adapter is restarted after it has exited.
+    /// The count is reset by a successful request
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: usize,
+}
+
+fn default_timeout() -> u64 {
+    5000
+}
+
+fn default_max_restarts() -> usize {
+    3
+}
+
+#[derive(Debug, Error)]
+pub enum ExternalError {
+    #[error("Failed to spawn {command}: {source}")]
+    Spawn {
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to communicate with {command}: {source}")]
+    Io {
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{0} has exited")]
+    Exited(String),
+    #[error("{0} has exited too many times")]
+    TooManyRestarts(String),
+    #[error("{0} timed out")]
+    Timeout(String),
+    #[error("{command} responded an error({code}): {message}")]
+    Remote {
+        command: String,
+        code: i64,
+        message: String,
+    },
+    #[error("Invalid response from {command}: {response}")]
+    InvalidResponse { command: String, response: String },
+}
+
+/// A running adapter
+#[derive(Debug)]
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+#[derive(Debug)]
+struct State {
+    process: Option<Process>,
+    restarts: usize,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+pub struct ExternalDictionary {
+    config: ExternalConfig,
+    state: Mutex<State>,
+}
+
+impl ExternalDictionary {
+    fn io(&self, source: std::io::Error) -> ExternalError {
+        ExternalError::Io {
+            command: self.config.command.clone(),
+            source,
+        }
+    }
+
+    fn invalid(&self, response: impl Into<String>) -> ExternalError {
+        ExternalError::InvalidResponse {
+            command: self.config.command.clone(),
+            response: response.into(),
+        }
+    }
+
+    /// Spawn the adapter, and initialize it
+    async fn spawn(&self, state: &mut State) -> Result<(), ExternalError> {
+        let mut child = Command::new(&self.config.command)
+            .args(&self.config.args)
+            .envs(&self.config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|source| ExternalError::Spawn {
+                command: self.config.command.clone(),
+                source,
+            })?;
+
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            unreachable!("stdin and stdout are piped");
+        };
+
+        state.process = Some(Process {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        });
+
+        self.send(
+            state,
+            "initialize",
+            json!({ "options": self.config.options }),
+        )
+        .await?;
+
+        tracing::info!("Spawned {}", self.config.command);
+        Ok(())
+    }
+
+    /// Restart the adapter if it has exited
+    async fn ensure_running(&self, state: &mut State) -> Result<(), ExternalError> {
+        let exited = match state.process.as_mut() {
+            Some(process) => process.child.try_wait().map_err(|e| self.io(e))?.is_some(),
+            None => true,
+        };
+
+        if exited {
+            if state.restarts >= self.config.max_restarts {
+                return Err(ExternalError::TooManyRestarts(self.config.command.clone()));
+            }
+            state.restarts += 1;
+            tracing::warn!("Restarting {}", self.config.command);
+            self.spawn(state).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a request, and wait for the response.
+    /// The adapter is dropped(killed) if it fails, and restarted at the next request
+    async fn send(
+        &self,
+        state: &mut State,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, ExternalError> {
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let Some(process) = state.process.as_mut() else {
+            return Err(ExternalError::Exited(self.config.command.clone()));
+        };
+
+        let result = tokio::time::timeout(
+            Duration::from_millis(self.config.timeout),
+            self.request(process, id, method, params),
+        )
+        .await
+        .unwrap_or_else(|_| Err(ExternalError::Timeout(self.config.command.clone())));
+
+        if matches!(
+            result,
+            Err(ExternalError::Io { .. }
+                | ExternalError::Exited(_)
+                | ExternalError::Timeout(_)
+                | ExternalError::InvalidResponse { .. })
+        ) {
+            state.process = None;
+        }
+
+        result
+    }
+
+    async fn request(
+        &self,
+        process: &mut Process,
+        id: u64,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, ExternalError> {
+        let mut request =
+            json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string();
+        request.push('\n');
+
+        process
+            .stdin
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| self.io(e))?;
+        process.stdin.flush().await.map_err(|e| self.io(e))?;
+
+        loop {
+            let mut line = String::new();
+            if process
+                .stdout
+                .read_line(&mut line)
+                .await
+                .map_err(|e| self.io(e))?
+                == 0
+            {
+                return Err(ExternalError::Exited(self.config.command.clone()));
+            }
+
+            let response: Value =
+                serde_json::from_str(&line).map_err(|_| self.invalid(line.trim()))?;
+            match response.get("id") {
+                Some(response_id) if *response_id == json!(id) => (),
+                Some(_) => return Err(self.invalid(line.trim())),
+                None => continue,
+            }
+
+            if let Some(error) = response.get("error") {
+                return Err(ExternalError::Remote {
+                    command: self.config.command.clone(),
+                    code: error
+                        .get("code")
+                        .and_then(Value::as_i64)
+                        .unwrap_or_default(),
+                    message: error
+                        .get("message")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                });
+            }
+
+            return Ok(response.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, ExternalError> {
+        let mut state = self.state.lock().await;
+        self.ensure_running(&mut state).await?;
+        let result = self.send(&mut state, method, params).await;
+
+        // Only the adapters crashing repeatedly are given up
+        if result.is_ok() || matches!(result, Err(ExternalError::Remote { .. })) {
+            state.restarts = 0;
+        }
+        result
+    }
+}
+
+impl Dictionary for ExternalDictionary {
+    type Error = ExternalError;
+    type InitInput = ExternalConfig;
+
+    async fn init(input: &Self::InitInput) -> Result<Self, Self::Error> {
+        let dict = ExternalDictionary {
+            config: input.clone(),
+            state: Mutex::new(State {
+                process: None,
+                restarts: 0,
+                next_id: 0,
+            }),
+        };
+
+        dict.spawn(&mut *dict.state.lock().await).await?;
+
+        Ok(dict)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        match self
+            .call("exists", json!({ "word": word.as_str() }))
+            .await?
+        {
+            Value::Bool(exists) => Ok(exists),
+            response => Err(self.invalid(response.to_string())),
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn lookup_ditail(&self, word: &Word) -> Result<Option<String>, Self::Error> {
+        match self
+            .call("lookup", json!({ "word": word.as_str() }))
+            .await?
+        {
+            Value::String(doc) => Ok(Some(doc)),
+            Value::Null => Ok(None),
+            response => Err(self.invalid(response.to_string())),
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn prefix_search(
+        &self,
+        prefix: &Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        let response = match self
+            .call(
+                "prefix_search",
+                json!({ "prefix": prefix.as_str(), "limit": limit }),
+            )
+            .await
+        {
+            Err(ExternalError::Remote {
+                code: METHOD_NOT_FOUND,
+                ..
+            }) => return Ok(vec![]),
+            response => response?,
+        };
+
+        serde_json::from_value(response.clone()).map_err(|_| self.invalid(response.to_string()))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        let response = match self.call("headwords", json!({})).await {
+            Err(ExternalError::Remote {
+                code: METHOD_NOT_FOUND,
+                ..
+            }) => return Ok(vec![]),
+            response => response?,
+        };
+
+        let words: Vec<String> = serde_json::from_value(response.clone())
+            .map_err(|_| self.invalid(response.to_string()))?;
+        Ok(words.iter().map(|word| fold_case(word)).collect())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    /// An adapter in shell script, which knows only "apple"
+    const ADAPTER: &str = r##"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","method":"log","params":{}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":null}\n' "$id" ;;
+    *'"method":"exists"'*'"word":"apple"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":true}\n' "$id" ;;
+    *'"method":"exists"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":false}\n' "$id" ;;
+    *'"method":"lookup"'*'"word":"apple"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":"# apple\\n\\nA fruit.\\n"}\n' "$id" ;;
+    *'"method":"lookup"'*'"word":"error"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32000,"message":"broken"}}\n' "$id" ;;
+    *'"method":"lookup"'*'"word":"crash"'*)
+      exit 1 ;;
+    *'"method":"lookup"'*'"word":"sleep"'*)
+      sleep 5 ;;
+    *'"method":"lookup"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":null}\n' "$id" ;;
+    *'"method":"prefix_search"'*'"prefix":"ap"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":[{"word":"apple","gloss":"A fruit."}]}\n' "$id" ;;
+    *'"method":"headwords"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":["Apple"]}\n' "$id" ;;
+    *)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"Method not found"}}\n' "$id" ;;
+  esac
+done
+"##;
+
+    fn config(dir: &Path) -> ExternalConfig {
+        let path = dir.join("adapter.sh");
+        std::fs::write(&path, ADAPTER).unwrap();
+
+        ExternalConfig {
+            command: "sh".into(),
+            args: vec![path.display().to_string()],
+            env: BTreeMap::new(),
+            options: Value::Null,
+            timeout: 1000,
+            max_restarts: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_external_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = ExternalDictionary::init(&config(dir.path())).await.unwrap();
+
+        assert!(dict.exits(&"apple".into()).await.unwrap());
+        assert!(!dict.exits(&"banana".into()).await.unwrap());
+
+        assert_eq!(
+            dict.lookup_ditail(&"apple".into()).await.unwrap().unwrap(),
+            "# apple\n\nA fruit.\n"
+        );
+        assert_eq!(dict.lookup_ditail(&"banana".into()).await.unwrap(), None);
+
+        assert!(matches!(
+            dict.lookup_ditail(&"error".into()).await,
+            Err(ExternalError::Remote { code: -32000, .. })
+        ));
+        // Errors responded don't stop the adapter
+        assert!(dict.exits(&"apple".into()).await.unwrap());
+
+        assert_eq!(
+            dict.prefix_search(&"ap".into(), 10).await.unwrap(),
+            vec![Completion::new("apple", Some("A fruit.".into()))]
+        );
+        // Not implemented by the adapter
+        assert!(dict
+            .prefix_search(&"ba".into(), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(dict.headwords().await.unwrap(), vec!["apple"]);
+    }
+
+    #[tokio::test]
+    async fn test_external_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = ExternalDictionary::init(&config(dir.path())).await.unwrap();
+
+        assert!(matches!(
+            dict.lookup_ditail(&"crash".into()).await,
+            Err(ExternalError::Exited(_))
+        ));
+        // Restarted
+        assert!(dict.exits(&"apple".into()).await.unwrap());
+
+        // Restarted again, since the last request has succeeded
+        assert!(matches!(
+            dict.lookup_ditail(&"crash".into()).await,
+            Err(ExternalError::Exited(_))
+        ));
+        assert!(matches!(
+            dict.lookup_ditail(&"sleep".into()).await,
+            Err(ExternalError::Timeout(_))
+        ));
+        // Up to `max_restarts` in a row
+        assert!(matches!(
+            dict.exits(&"apple".into()).await,
+            Err(ExternalError::TooManyRestarts(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_external_spawn_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ExternalConfig {
+            command: dir.path().join("not-exist").display().to_string(),
+            ..config(dir.path())
+        };
+
+        assert!(matches!(
+            ExternalDictionary::init(&config).await,
+            Err(ExternalError::Spawn { .. })
+        ));
+    }
+}
@@ -0,0 +1,344 @@
+//! JMdict adapter
+//! Reads the XML(or gzipped one) of JMdict or JMnedict, and looks up by written forms or readings.
+
+mod parse;
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+
+use etymora_traits::{fold_case, Completion, Dictionary, Word};
+use flate2::read::MultiGzDecoder;
+use parse::{Entry, Sense};
+use thiserror::Error;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct JmdictConfig {
+    /// The path to the XML like `JMdict_e` or `JMnedict.xml.gz`
+    pub path: PathBuf,
+    /// The language of the glosses in ISO 639-2 like "eng" and "ger"
+    #[serde(default = "default_lang")]
+    pub lang: String,
+}
+
+fn default_lang() -> String {
+    "eng".into()
+}
+
+#[derive(Debug, Error)]
+pub enum JmdictError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid XML: {0}")]
+    Xml(#[source] quick_xml::Error),
+}
+
+#[derive(Debug)]
+pub struct JmdictDictionary {
+    entries: Vec<Entry>,
+    /// Case folded written forms and readings to the indices of `entries`. Sorted for `prefix_search`
+    index: BTreeMap<String, Vec<usize>>,
+}
+
+fn render_sense(number: usize, sense: &Sense) -> String {
+    let mut doc = format!("{number}. {}", sense.glosses.join("; "));
+
+    if !sense.pos.is_empty() {
+        doc.push_str(&format!("\n   - Part of speech: {}", sense.pos.join(", ")));
+    }
+    if !sense.tags.is_empty() {
+        doc.push_str(&format!("\n   - Tags: {}", sense.tags.join(", ")));
+    }
+    for info in &sense.info {
+        doc.push_str(&format!("\n   - Note: {info}"));
+    }
+
+    doc
+}
+
+fn render(entry: &Entry) -> String {
+    let readings = entry.readings.join("; ");
+    let heading = if entry.kanji.is_empty() {
+        readings
+    } else {
+        format!("{}【{readings}】", entry.kanji.join("; "))
+    };
+
+    let senses = entry
+        .senses
+        .iter()
+        .enumerate()
+        .map(|(i, sense)| render_sense(i + 1, sense))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("# {heading}\n\n{senses}\n")
+}
+
+impl Dictionary for JmdictDictionary {
+    type Error = JmdictError;
+    type InitInput = JmdictConfig;
+
+    async fn init(input: &Self::InitInput) -> Result<Self, Self::Error> {
+        let io = |source| JmdictError::Io {
+            path: input.path.clone(),
+            source,
+        };
+
+        let mut xml = tokio::fs::read(&input.path).await.map_err(io)?;
+        if xml.starts_with(&[0x1f, 0x8b]) {
+            let mut decompressed = vec![];
+            MultiGzDecoder::new(&xml[..])
+                .read_to_end(&mut decompressed)
+                .map_err(io)?;
+            xml = decompressed;
+        }
+
+        let entries = parse::parse(&xml, &input.lang)?;
+
+        let mut index: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            for form in entry.kanji.iter().chain(&entry.readings) {
+                let indices = index.entry(fold_case(form)).or_default();
+                if !indices.contains(&i) {
+                    indices.push(i);
+                }
+            }
+        }
+
+        tracing::info!(
+            "Loaded {} with {} entries",
+            input.path.display(),
+            entries.len()
+        );
+
+        Ok(JmdictDictionary { entries, index })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        Ok(self.index.contains_key(&fold_case(word.as_str())))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn lookup_ditail(&self, word: &Word) -> Result<Option<String>, Self::Error> {
+        let Some(indices) = self.index.get(&fold_case(word.as_str())) else {
+            return Ok(None);
+        };
+
+        let docs: Vec<String> = indices.iter().map(|i| render(&self.entries[*i])).collect();
+
+        Ok(Some(docs.join("\n---\n\n")))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn prefix_search(
+        &self,
+        prefix: &Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        let prefix = fold_case(prefix.as_str());
+
+        Ok(self
+            .index
+            .range(prefix.clone()..)
+            .take_while(|(form, _)| form.starts_with(&prefix))
+            .take(limit)
+            .map(|(form, indices)| {
+                let gloss = indices
+                    .first()
+                    .and_then(|i| self.entries[*i].senses.first())
+                    .map(|sense| sense.glosses.join("; "));
+                Completion::new(form.clone(), gloss)
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(self.index.keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+    use std::path::Path;
+
+    const JMDICT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMdict [
+<!ELEMENT JMdict (entry*)>
+<!ENTITY adj-na "adjectival nouns or quasi-adjectives (keiyodoshi)">
+<!ENTITY n "noun (common) (futsuumeishi)">
+<!ENTITY uk "word usually written using kana alone">
+<!ENTITY comp "computing">
+]>
+<!-- JMdict created: 2024-12-01 -->
+<JMdict>
+<entry>
+<ent_seq>1000220</ent_seq>
+<k_ele><keb>明白</keb></k_ele>
+<r_ele><reb>めいはく</reb></r_ele>
+<sense>
+<pos>&adj-na;</pos>
+<gloss>obvious</gloss>
+<gloss>clear &amp; plain</gloss>
+<gloss xml:lang="ger">offenkundig</gloss>
+</sense>
+</entry>
+<entry>
+<ent_seq>1000000</ent_seq>
+<k_ele><keb>此れ</keb></k_ele>
+<k_ele><keb>是</keb></k_ele>
+<r_ele><reb>これ</reb></r_ele>
+<sense>
+<pos>&n;</pos>
+<misc>&uk;</misc>
+<s_inf>indicates an item near the speaker</s_inf>
+<gloss>this</gloss>
+</sense>
+<sense>
+<gloss xml:lang="ger">dies</gloss>
+</sense>
+</entry>
+<entry>
+<ent_seq>1000001</ent_seq>
+<r_ele><reb>ファイル</reb></r_ele>
+<sense>
+<pos>&n;</pos>
+<field>&comp;</field>
+<gloss>file</gloss>
+</sense>
+</entry>
+</JMdict>
+"#;
+
+    fn write_jmdict(dir: &Path, compress: bool) -> PathBuf {
+        if compress {
+            let path = dir.join("JMdict_e.gz");
+            let mut encoder = flate2::write::GzEncoder::new(
+                std::fs::File::create(&path).unwrap(),
+                flate2::Compression::default(),
+            );
+            encoder.write_all(JMDICT.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+            path
+        } else {
+            let path = dir.join("JMdict_e");
+            std::fs::write(&path, JMDICT).unwrap();
+            path
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jmdict_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = JmdictDictionary::init(&JmdictConfig {
+            path: write_jmdict(dir.path(), false),
+            lang: default_lang(),
+        })
+        .await
+        .unwrap();
+
+        assert!(dict.exits(&"明白".into()).await.unwrap());
+        assert!(dict.exits(&"めいはく".into()).await.unwrap());
+        assert!(dict.exits(&"是".into()).await.unwrap());
+        assert!(!dict.exits(&"明らか".into()).await.unwrap());
+
+        assert_eq!(
+            dict.lookup_ditail(&"めいはく".into())
+                .await
+                .unwrap()
+                .unwrap(),
+            "# 明白【めいはく】
+
+1. obvious; clear & plain
+   - Part of speech: adjectival nouns or quasi-adjectives (keiyodoshi)
+"
+        );
+        assert_eq!(
+            dict.lookup_ditail(&"是".into()).await.unwrap().unwrap(),
+            "# 此れ; 是【これ】
+
+1. this
+   - Part of speech: noun (common) (futsuumeishi)
+   - Tags: word usually written using kana alone
+   - Note: indicates an item near the speaker
+"
+        );
+        assert_eq!(
+            dict.lookup_ditail(&"ファイル".into())
+                .await
+                .unwrap()
+                .unwrap(),
+            "# ファイル
+
+1. file
+   - Part of speech: noun (common) (futsuumeishi)
+   - Tags: computing
+"
+        );
+        assert_eq!(dict.lookup_ditail(&"明らか".into()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_jmdict_prefix_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = JmdictDictionary::init(&JmdictConfig {
+            path: write_jmdict(dir.path(), false),
+            lang: default_lang(),
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            dict.prefix_search(&"め".into(), 10).await.unwrap(),
+            vec![Completion::new(
+                "めいはく",
+                Some("obvious; clear & plain".into())
+            )]
+        );
+        assert_eq!(
+            dict.prefix_search(&"此".into(), 10).await.unwrap(),
+            vec![Completion::new("此れ", Some("this".into()))]
+        );
+        assert_eq!(dict.prefix_search(&"".into(), 2).await.unwrap().len(), 2);
+        assert!(dict
+            .headwords()
+            .await
+            .unwrap()
+            .contains(&"めいはく".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_jmdict_lang() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = JmdictDictionary::init(&JmdictConfig {
+            path: write_jmdict(dir.path(), true),
+            lang: "ger".into(),
+        })
+        .await
+        .unwrap();
+
+        assert!(dict
+            .lookup_ditail(&"明白".into())
+            .await
+            .unwrap()
+            .unwrap()
+            .contains("1. offenkundig\n"));
+        assert!(dict
+            .lookup_ditail(&"これ".into())
+            .await
+            .unwrap()
+            .unwrap()
+            .contains("1. dies\n"));
+        // No glosses in German
+        assert!(!dict.exits(&"ファイル".into()).await.unwrap());
+    }
+}
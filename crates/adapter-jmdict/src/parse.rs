@@ -0,0 +1,249 @@
+//! Parser of the XML of JMdict and JMnedict
+
+use quick_xml::{escape::resolve_predefined_entity, events::Event, Reader};
+use rustc_hash::FxHashMap;
+
+use crate::JmdictError;
+
+/// The language of the glosses without `xml:lang`
+const DEFAULT_LANG: &str = "eng";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Entry {
+    /// `keb`
+    pub(crate) kanji: Vec<String>,
+    /// `reb`
+    pub(crate) readings: Vec<String>,
+    pub(crate) senses: Vec<Sense>,
+}
+
+/// A sense of JMdict, or a translation(`trans`) of JMnedict
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Sense {
+    /// `pos` of JMdict, or `name_type` of JMnedict
+    pub(crate) pos: Vec<String>,
+    /// `misc`, `field` and `dial`
+    pub(crate) tags: Vec<String>,
+    /// `s_inf`
+    pub(crate) info: Vec<String>,
+    /// `gloss` of the language, or `trans_det`
+    pub(crate) glosses: Vec<String>,
+}
+
+/// The entities declared in the DTD like `<!ENTITY uk "word usually written using kana alone">`
+fn parse_entities(doctype: &str) -> FxHashMap<String, String> {
+    let mut entities = FxHashMap::default();
+    let mut rest = doctype;
+
+    while let Some(start) = rest.find("<!ENTITY") {
+        rest = &rest[start + "<!ENTITY".len()..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let declaration = rest[..end].trim();
+        rest = &rest[end..];
+
+        let Some((name, value)) = declaration.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let value = value.trim();
+        if let Some(value) = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        {
+            entities.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    entities
+}
+
+/// Parse the entries with the glosses of the language like "eng"
+pub(crate) fn parse(xml: &[u8], lang: &str) -> Result<Vec<Entry>, JmdictError> {
+    let mut reader = Reader::from_reader(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut entities = FxHashMap::default();
+    let mut entries = vec![];
+
+    let mut entry = Entry::default();
+    let mut sense = Sense::default();
+    // The parts of speech of the previous sense, which apply to the following senses without them
+    let mut pos = vec![];
+    // Whether the gloss being read is in the language
+    let mut in_lang = true;
+    let mut element = Vec::new();
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf).map_err(JmdictError::Xml)? {
+            Event::DocType(doctype) => {
+                entities = parse_entities(&String::from_utf8_lossy(&doctype));
+            }
+            Event::Start(start) => {
+                element = start.local_name().as_ref().to_vec();
+
+                match &element[..] {
+                    b"entry" => {
+                        entry = Entry::default();
+                        pos.clear();
+                    }
+                    b"sense" | b"trans" => sense = Sense::default(),
+                    b"gloss" => {
+                        in_lang = match start
+                            .try_get_attribute("xml:lang")
+                            .map_err(|e| JmdictError::Xml(e.into()))?
+                        {
+                            Some(attr) => attr.value.as_ref() == lang.as_bytes(),
+                            None => lang == DEFAULT_LANG,
+                        };
+                    }
+                    _ => (),
+                }
+            }
+            Event::Text(text) => {
+                let text = text
+                    .unescape_with(|entity| {
+                        entities
+                            .get(entity)
+                            .map(String::as_str)
+                            .or_else(|| resolve_predefined_entity(entity))
+                    })
+                    .map_err(JmdictError::Xml)?
+                    .into_owned();
+
+                match &element[..] {
+                    b"keb" => entry.kanji.push(text),
+                    b"reb" => entry.readings.push(text),
+                    b"pos" | b"name_type" => sense.pos.push(text),
+                    b"misc" | b"field" | b"dial" => sense.tags.push(text),
+                    b"s_inf" => sense.info.push(text),
+                    b"gloss" if in_lang => sense.glosses.push(text),
+                    b"trans_det" if in_lang => sense.glosses.push(text),
+                    _ => (),
+                }
+            }
+            Event::End(end) => {
+                element.clear();
+
+                if end.local_name().as_ref() == b"sense" {
+                    if sense.pos.is_empty() {
+                        sense.pos.clone_from(&pos);
+                    } else {
+                        pos.clone_from(&sense.pos);
+                    }
+                }
+
+                match end.local_name().as_ref() {
+                    // The senses without glosses in the language are skipped
+                    b"sense" | b"trans" if !sense.glosses.is_empty() => {
+                        entry.senses.push(std::mem::take(&mut sense));
+                    }
+                    b"entry" if !entry.senses.is_empty() => {
+                        entries.push(std::mem::take(&mut entry));
+                    }
+                    _ => (),
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+
+        buf.clear();
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entities() {
+        let entities = parse_entities(
+            r#"JMdict [
+<!ELEMENT JMdict (entry*)>
+<!ENTITY uk "word usually written using kana alone">
+<!ENTITY v5r 'Godan verb with "ru" ending'>
+]"#,
+        );
+
+        assert_eq!(entities["uk"], "word usually written using kana alone");
+        assert_eq!(entities["v5r"], "Godan verb with \"ru\" ending");
+        assert_eq!(entities.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_pos() {
+        let xml = r#"<JMdict>
+<entry>
+<r_ele><reb>はしる</reb></r_ele>
+<sense><pos>v5r</pos><gloss>to run</gloss></sense>
+<sense><gloss>to travel (of a vehicle)</gloss></sense>
+<sense><gloss xml:lang="ger">laufen</gloss></sense>
+</entry>
+<entry>
+<r_ele><reb>これ</reb></r_ele>
+<sense><gloss>this</gloss></sense>
+</entry>
+</JMdict>"#;
+
+        // The parts of speech of the previous sense are carried forward
+        let entries = parse(xml.as_bytes(), "eng").unwrap();
+        assert_eq!(entries[0].senses.len(), 2);
+        assert_eq!(entries[0].senses[1].pos, ["v5r"]);
+        // But not to the next entry
+        assert!(entries[1].senses[0].pos.is_empty());
+
+        // Even from the senses skipped for the language
+        let entries = parse(xml.as_bytes(), "ger").unwrap();
+        assert_eq!(
+            entries,
+            [Entry {
+                readings: vec!["はしる".into()],
+                senses: vec![Sense {
+                    pos: vec!["v5r".into()],
+                    glosses: vec!["laufen".into()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_jmnedict() {
+        let entries = parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMnedict [
+<!ENTITY surname "family or surname">
+]>
+<JMnedict>
+<entry>
+<ent_seq>5000000</ent_seq>
+<k_ele><keb>ゝ泉</keb></k_ele>
+<r_ele><reb>いずみ</reb></r_ele>
+<trans><name_type>&surname;</name_type><trans_det>Izumi</trans_det></trans>
+</entry>
+</JMnedict>"#
+                .as_bytes(),
+            "eng",
+        )
+        .unwrap();
+
+        assert_eq!(
+            entries,
+            [Entry {
+                kanji: vec!["ゝ泉".into()],
+                readings: vec!["いずみ".into()],
+                senses: vec![Sense {
+                    pos: vec!["family or surname".into()],
+                    glosses: vec!["Izumi".into()],
+                    ..Default::default()
+                }],
+            }]
+        );
+    }
+}
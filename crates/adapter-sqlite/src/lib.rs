@@ -0,0 +1,530 @@
+//! SQLite adapter
+//! Runs the configured queries against a SQLite database, and renders the columns of the results.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use etymora_traits::{fold_case, Completion, Dictionary, Word};
+use rusqlite::{types::ValueRef, Connection, OpenFlags, Statement};
+use thiserror::Error;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SqliteConfig {
+    /// The path to the database
+    pub path: PathBuf,
+    /// The query of the entries of `:word`
+    #[serde(default = "default_lookup_query")]
+    pub lookup_query: String,
+    /// The query whether `:word` is in the dictionary. `SELECT EXISTS(lookup_query)` if it's not set
+    #[serde(default, alias = "exits_query")]
+    pub exists_query: Option<String>,
+    /// The query of the headwords(and their glosses as the second column optionally)
+    /// which match the FTS5 query `:query`, up to `:limit` rows
+    #[serde(default)]
+    pub prefix_query: Option<String>,
+    /// The query of the entries which match the FTS5 query `:query`, up to `:limit` rows.
+    /// It's used when `lookup_query` finds nothing
+    #[serde(default)]
+    pub fuzzy_query: Option<String>,
+    /// The query of all the headwords in the first column, for spelling suggestions.
+    /// No words are suggested from the dictionary if it's not set
+    #[serde(default)]
+    pub headwords_query: Option<String>,
+    /// The max number of rows of `fuzzy_query`
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    /// The column of the headword
+    #[serde(default = "default_headword")]
+    pub headword: String,
+    /// The columns shown in order. All columns except the headword if it's empty
+    #[serde(default)]
+    pub fields: Vec<FieldMapping>,
+}
+
+/// A column shown as a field of entries
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FieldMapping {
+    pub column: String,
+    /// The heading of the field. The value is shown without a heading if it's not set
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+fn default_lookup_query() -> String {
+    "SELECT * FROM entries WHERE word = :word COLLATE NOCASE".into()
+}
+
+fn default_limit() -> usize {
+    5
+}
+
+fn default_headword() -> String {
+    "word".into()
+}
+
+#[derive(Debug, Error)]
+pub enum SqliteError {
+    #[error("Not found the database: {0}")]
+    NotFound(PathBuf),
+    #[error("Error occurs in SQLite: {0}")]
+    Sqlite(#[source] rusqlite::Error),
+    #[error("Not found the column {0} in the results")]
+    MissingColumn(String),
+}
+
+#[derive(Debug)]
+pub struct SqliteDictionary {
+    config: SqliteConfig,
+    exists_query: String,
+    /// FTS5 queries which are available in the database
+    prefix_query: Option<String>,
+    fuzzy_query: Option<String>,
+    headwords_query: Option<String>,
+    connection: Mutex<Connection>,
+}
+
+/// A row of the results. The columns which are NULL or BLOB are `None`
+type Row = Vec<(String, Option<String>)>;
+
+/// Bind only the parameters which the query has
+fn bind(
+    statement: &mut Statement,
+    params: &[(&str, &dyn rusqlite::ToSql)],
+) -> rusqlite::Result<()> {
+    for (name, value) in params {
+        if let Some(index) = statement.parameter_index(name)? {
+            statement.raw_bind_parameter(index, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn query_rows(
+    connection: &Connection,
+    query: &str,
+    params: &[(&str, &dyn rusqlite::ToSql)],
+) -> rusqlite::Result<Vec<Row>> {
+    let mut statement = connection.prepare_cached(query)?;
+    bind(&mut statement, params)?;
+
+    let names: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+    let mut rows = statement.raw_query();
+    let mut result = vec![];
+    while let Some(row) = rows.next()? {
+        let mut columns = Vec::with_capacity(names.len());
+        for (i, name) in names.iter().enumerate() {
+            let value = match row.get_ref(i)? {
+                ValueRef::Integer(i) => Some(i.to_string()),
+                ValueRef::Real(r) => Some(r.to_string()),
+                ValueRef::Text(t) => Some(String::from_utf8_lossy(t).into_owned()),
+                ValueRef::Null | ValueRef::Blob(_) => None,
+            };
+            columns.push((name.clone(), value));
+        }
+        result.push(columns);
+    }
+
+    Ok(result)
+}
+
+/// A FTS5 query of the phrase
+fn fts_phrase(word: &str) -> String {
+    format!("\"{}\"", word.replace('"', "\"\""))
+}
+
+/// Whether the query can be prepared, e.g. the FTS5 table exists
+fn available(connection: &Connection, query: Option<&str>) -> Option<String> {
+    let query = query?;
+    match connection.prepare(query) {
+        Ok(_) => Some(query.to_string()),
+        Err(e) => {
+            tracing::warn!("Disabled the query {query}: {e}");
+            None
+        }
+    }
+}
+
+impl SqliteDictionary {
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // The connection is still usable even if another lookup panicked
+        self.connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn render(&self, row: &Row) -> Result<String, SqliteError> {
+        let value = |column: &str| {
+            row.iter()
+                .find(|(name, _)| name == column)
+                .map(|(_, value)| value.as_deref())
+                .ok_or_else(|| SqliteError::MissingColumn(column.to_string()))
+        };
+
+        let mut doc = format!("# {}\n", value(&self.config.headword)?.unwrap_or_default());
+
+        if self.config.fields.is_empty() {
+            for (_, value) in row.iter().filter(|(name, _)| *name != self.config.headword) {
+                if let Some(value) = value.as_deref().filter(|v| !v.trim().is_empty()) {
+                    doc.push_str(&format!("\n{}\n", value.trim()));
+                }
+            }
+        }
+
+        for field in &self.config.fields {
+            let Some(value) = value(&field.column)?.filter(|v| !v.trim().is_empty()) else {
+                continue;
+            };
+            match &field.label {
+                Some(label) => doc.push_str(&format!("\n## {label}\n\n{}\n", value.trim())),
+                None => doc.push_str(&format!("\n{}\n", value.trim())),
+            }
+        }
+
+        Ok(doc)
+    }
+}
+
+impl Dictionary for SqliteDictionary {
+    type Error = SqliteError;
+    type InitInput = SqliteConfig;
+
+    async fn init(input: &Self::InitInput) -> Result<Self, Self::Error> {
+        if !tokio::fs::try_exists(&input.path).await.unwrap_or(false) {
+            return Err(SqliteError::NotFound(input.path.clone()));
+        }
+
+        let connection = Connection::open_with_flags(
+            &input.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(SqliteError::Sqlite)?;
+
+        let exists_query = input
+            .exists_query
+            .clone()
+            .unwrap_or_else(|| format!("SELECT EXISTS ({})", input.lookup_query));
+
+        // The queries for the words are required
+        connection
+            .prepare(&input.lookup_query)
+            .map_err(SqliteError::Sqlite)?;
+        connection
+            .prepare(&exists_query)
+            .map_err(SqliteError::Sqlite)?;
+
+        let prefix_query = available(&connection, input.prefix_query.as_deref());
+        let fuzzy_query = available(&connection, input.fuzzy_query.as_deref());
+        let headwords_query = available(&connection, input.headwords_query.as_deref());
+
+        tracing::info!("Opened {}", input.path.display());
+
+        Ok(SqliteDictionary {
+            config: input.clone(),
+            exists_query,
+            prefix_query,
+            fuzzy_query,
+            headwords_query,
+            connection: Mutex::new(connection),
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        let rows = query_rows(
+            &self.connection(),
+            &self.exists_query,
+            &[(":word", &word.as_str())],
+        )
+        .map_err(SqliteError::Sqlite)?;
+
+        // Either a row of a truthy value or some rows of the entries
+        Ok(match &rows[..] {
+            [] => false,
+            [row] if row.len() == 1 => row[0].1.as_deref().is_some_and(|v| v != "0"),
+            _ => true,
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn lookup_ditail(&self, word: &Word) -> Result<Option<String>, Self::Error> {
+        let mut rows = query_rows(
+            &self.connection(),
+            &self.config.lookup_query,
+            &[(":word", &word.as_str())],
+        )
+        .map_err(SqliteError::Sqlite)?;
+
+        if rows.is_empty() {
+            if let Some(query) = &self.fuzzy_query {
+                rows = query_rows(
+                    &self.connection(),
+                    query,
+                    &[
+                        (":query", &fts_phrase(word.as_str())),
+                        (":limit", &(self.config.limit as i64)),
+                    ],
+                )
+                .map_err(SqliteError::Sqlite)?;
+            }
+        }
+
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let docs = rows
+            .iter()
+            .map(|row| self.render(row))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(docs.join("\n---\n\n")))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn prefix_search(
+        &self,
+        prefix: &Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        let Some(query) = &self.prefix_query else {
+            return Ok(vec![]);
+        };
+
+        let rows = query_rows(
+            &self.connection(),
+            query,
+            &[
+                (":query", &format!("{} *", fts_phrase(prefix.as_str()))),
+                (":limit", &(limit as i64)),
+            ],
+        )
+        .map_err(SqliteError::Sqlite)?;
+
+        let mut completions: Vec<Completion> = vec![];
+        for row in rows {
+            let mut columns = row.into_iter().map(|(_, value)| value);
+            let Some(word) = columns.next().flatten() else {
+                continue;
+            };
+            if completions.iter().any(|completion| completion.word == word) {
+                continue;
+            }
+            completions.push(Completion::new(word, columns.next().flatten()));
+        }
+
+        Ok(completions)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        let Some(query) = &self.headwords_query else {
+            return Ok(vec![]);
+        };
+
+        let rows = query_rows(&self.connection(), query, &[]).map_err(SqliteError::Sqlite)?;
+
+        let mut headwords: Vec<String> = rows
+            .into_iter()
+            .filter_map(|row| row.into_iter().next().and_then(|(_, value)| value))
+            .map(|word| fold_case(&word))
+            .collect();
+        headwords.sort_unstable();
+        headwords.dedup();
+
+        Ok(headwords)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    fn write_database(dir: &Path) -> PathBuf {
+        let path = dir.join("glossary.sqlite");
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE terms (id INTEGER PRIMARY KEY, term TEXT, reading TEXT, definition TEXT, note TEXT);
+                 INSERT INTO terms (term, reading, definition, note) VALUES
+                     ('Etymology', 'ˌɛtɪˈmɒlədʒi', 'The study of the origin of words.', NULL),
+                     ('lemma', NULL, 'The base form of a word.', 'Plural: lemmas'),
+                     ('lemma', NULL, 'A proposition in mathematics.', '');
+                 CREATE VIRTUAL TABLE terms_fts USING fts5(term, definition, content='terms', content_rowid='id', tokenize='porter');
+                 INSERT INTO terms_fts (terms_fts) VALUES ('rebuild');",
+            )
+            .unwrap();
+        path
+    }
+
+    fn config(path: PathBuf) -> SqliteConfig {
+        SqliteConfig {
+            path,
+            lookup_query: "SELECT term, reading, definition, note FROM terms WHERE term = :word COLLATE NOCASE ORDER BY id".into(),
+            exists_query: None,
+            prefix_query: Some(
+                "SELECT term, definition FROM terms_fts WHERE terms_fts MATCH 'term : ' || :query ORDER BY term, rowid LIMIT :limit".into(),
+            ),
+            fuzzy_query: Some(
+                "SELECT terms.term, terms.reading, terms.definition, terms.note FROM terms_fts JOIN terms ON terms.id = terms_fts.rowid WHERE terms_fts MATCH 'term : ' || :query ORDER BY rank LIMIT :limit".into(),
+            ),
+            headwords_query: Some("SELECT term FROM terms".into()),
+            limit: 5,
+            headword: "term".into(),
+            fields: vec![
+                FieldMapping {
+                    column: "reading".into(),
+                    label: None,
+                },
+                FieldMapping {
+                    column: "definition".into(),
+                    label: None,
+                },
+                FieldMapping {
+                    column: "note".into(),
+                    label: Some("Note".into()),
+                },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = SqliteDictionary::init(&config(write_database(dir.path())))
+            .await
+            .unwrap();
+
+        assert!(dict.exits(&"etymology".into()).await.unwrap());
+        assert!(dict.exits(&"lemma".into()).await.unwrap());
+        assert!(!dict.exits(&"lemmas".into()).await.unwrap());
+
+        assert_eq!(
+            dict.lookup_ditail(&"etymology".into())
+                .await
+                .unwrap()
+                .unwrap(),
+            "# Etymology\n\nˌɛtɪˈmɒlədʒi\n\nThe study of the origin of words.\n"
+        );
+        assert_eq!(
+            dict.lookup_ditail(&"lemma".into()).await.unwrap().unwrap(),
+            "# lemma\n\nThe base form of a word.\n\n## Note\n\nPlural: lemmas\n\n---\n\n# lemma\n\nA proposition in mathematics.\n"
+        );
+        assert_eq!(dict.lookup_ditail(&"word".into()).await.unwrap(), None);
+
+        assert_eq!(dict.headwords().await.unwrap(), vec!["etymology", "lemma"]);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_fts() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = SqliteDictionary::init(&config(write_database(dir.path())))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            dict.prefix_search(&"etym".into(), 10).await.unwrap(),
+            [Completion::new(
+                "Etymology",
+                Some("The study of the origin of words.".into())
+            )]
+        );
+        // The duplicated headwords are merged
+        assert_eq!(
+            dict.prefix_search(&"lem".into(), 10).await.unwrap(),
+            [Completion::new(
+                "lemma",
+                Some("The base form of a word.".into())
+            )]
+        );
+        assert!(dict
+            .prefix_search(&"\"".into(), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // "lemmas" is stemmed by the porter tokenizer
+        assert!(dict
+            .lookup_ditail(&"lemmas".into())
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("# lemma\n"));
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_default_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dict.sqlite");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE entries (word TEXT, meaning TEXT, example BLOB);
+                 INSERT INTO entries VALUES ('cat', 'A small feline.', x'00');",
+            )
+            .unwrap();
+
+        let config: SqliteConfig = serde_json::from_value(
+            serde_json::json!({ "path": path, "fuzzy_query": "SELECT * FROM missing_fts" }),
+        )
+        .unwrap();
+        let dict = SqliteDictionary::init(&config).await.unwrap();
+
+        assert!(dict.exits(&"CAT".into()).await.unwrap());
+        assert!(!dict.exits(&"dog".into()).await.unwrap());
+        assert_eq!(
+            dict.lookup_ditail(&"cat".into()).await.unwrap().unwrap(),
+            "# cat\n\nA small feline.\n"
+        );
+        // The FTS5 table doesn't exist
+        assert!(dict.fuzzy_query.is_none());
+        assert!(dict
+            .prefix_search(&"ca".into(), 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(dict.headwords().await.unwrap().is_empty());
+
+        // The old spelling of the key
+        let config: SqliteConfig = serde_json::from_value(serde_json::json!({
+            "path": path,
+            "exits_query": "SELECT 1 FROM entries WHERE word = :word",
+        }))
+        .unwrap();
+        assert_eq!(
+            config.exists_query.as_deref(),
+            Some("SELECT 1 FROM entries WHERE word = :word")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_invalid_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(write_database(dir.path()));
+
+        config.headword = "missing".into();
+        let dict = SqliteDictionary::init(&config).await.unwrap();
+        assert!(matches!(
+            dict.lookup_ditail(&"lemma".into()).await,
+            Err(SqliteError::MissingColumn(_))
+        ));
+
+        config.lookup_query = "SELECT * FROM missing".into();
+        assert!(matches!(
+            SqliteDictionary::init(&config).await,
+            Err(SqliteError::Sqlite(_))
+        ));
+
+        config.path = dir.path().join("missing.sqlite");
+        assert!(matches!(
+            SqliteDictionary::init(&config).await,
+            Err(SqliteError::NotFound(_))
+        ));
+    }
+}
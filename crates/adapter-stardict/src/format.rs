@@ -0,0 +1,305 @@
+//! Parsers of the StarDict files(`.ifo`, `.idx`, `.syn` and the entries of `.dict`)
+
+use crate::StardictError;
+
+const IFO_MAGIC: &str = "StarDict's dict ifo file";
+
+/// The information of the dictionary from `.ifo`
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Ifo {
+    pub(crate) bookname: String,
+    pub(crate) wordcount: usize,
+    /// 32 or 64
+    pub(crate) idxoffsetbits: u8,
+    pub(crate) sametypesequence: Option<String>,
+}
+
+pub(crate) fn parse_ifo(s: &str) -> Result<Ifo, StardictError> {
+    let mut lines = s.trim_start_matches('\u{feff}').lines();
+
+    if lines.next().map(str::trim) != Some(IFO_MAGIC) {
+        return Err(StardictError::InvalidIfo("Missing the magic line".into()));
+    }
+
+    let mut version = None;
+    let mut bookname = None;
+    let mut wordcount = None;
+    let mut idxoffsetbits = 32;
+    let mut sametypesequence = None;
+
+    for line in lines {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+
+        match key.trim() {
+            "version" => version = Some(value.to_string()),
+            "bookname" => bookname = Some(value.to_string()),
+            "wordcount" => {
+                wordcount = Some(value.parse().map_err(|_| {
+                    StardictError::InvalidIfo(format!("Invalid wordcount: {value}"))
+                })?)
+            }
+            "idxoffsetbits" => {
+                idxoffsetbits = match value {
+                    "32" => 32,
+                    "64" => 64,
+                    _ => {
+                        return Err(StardictError::InvalidIfo(format!(
+                            "Invalid idxoffsetbits: {value}"
+                        )))
+                    }
+                }
+            }
+            "sametypesequence" if !value.is_empty() => sametypesequence = Some(value.to_string()),
+            _ => (),
+        }
+    }
+
+    match version.as_deref() {
+        Some("2.4.2") => idxoffsetbits = 32,
+        Some("3.0.0") => (),
+        Some(version) => return Err(StardictError::UnsupportedVersion(version.into())),
+        None => return Err(StardictError::InvalidIfo("Missing version".into())),
+    }
+
+    Ok(Ifo {
+        bookname: bookname.ok_or_else(|| StardictError::InvalidIfo("Missing bookname".into()))?,
+        wordcount: wordcount
+            .ok_or_else(|| StardictError::InvalidIfo("Missing wordcount".into()))?,
+        idxoffsetbits,
+        sametypesequence,
+    })
+}
+
+/// An entry of `.idx`
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct IdxEntry {
+    pub(crate) word: String,
+    pub(crate) offset: u64,
+    pub(crate) size: u32,
+}
+
+/// Split a NUL terminated UTF-8 string from the head of the bytes
+fn split_str(bytes: &[u8]) -> Result<(String, &[u8]), StardictError> {
+    let nul = bytes
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or(StardictError::Truncated)?;
+    let s = String::from_utf8_lossy(&bytes[..nul]).into_owned();
+    Ok((s, &bytes[nul + 1..]))
+}
+
+/// Split a big endian unsigned integer of `N` bytes from the head of the bytes
+fn split_be<const N: usize>(bytes: &[u8]) -> Result<(u64, &[u8]), StardictError> {
+    if bytes.len() < N {
+        return Err(StardictError::Truncated);
+    }
+    let value = bytes[..N]
+        .iter()
+        .fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+    Ok((value, &bytes[N..]))
+}
+
+pub(crate) fn parse_idx(
+    mut bytes: &[u8],
+    idxoffsetbits: u8,
+) -> Result<Vec<IdxEntry>, StardictError> {
+    let mut entries = vec![];
+
+    while !bytes.is_empty() {
+        let (word, rest) = split_str(bytes)?;
+        let (offset, rest) = if idxoffsetbits == 64 {
+            split_be::<8>(rest)?
+        } else {
+            split_be::<4>(rest)?
+        };
+        let (size, rest) = split_be::<4>(rest)?;
+
+        entries.push(IdxEntry {
+            word,
+            offset,
+            size: size as u32,
+        });
+        bytes = rest;
+    }
+
+    Ok(entries)
+}
+
+/// Parse `.syn` into synonyms and the indices of their original words in `.idx`
+pub(crate) fn parse_syn(mut bytes: &[u8]) -> Result<Vec<(String, u32)>, StardictError> {
+    let mut synonyms = vec![];
+
+    while !bytes.is_empty() {
+        let (word, rest) = split_str(bytes)?;
+        let (index, rest) = split_be::<4>(rest)?;
+
+        synonyms.push((word, index as u32));
+        bytes = rest;
+    }
+
+    Ok(synonyms)
+}
+
+/// A field of an entry in `.dict`
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Field {
+    /// Lower case types like `m`(plain text), `h`(HTML) and `t`(phonetic)
+    Text(char, String),
+    /// Upper case types like `W`(wav) and `P`(picture), which cannot be rendered
+    Binary(char),
+}
+
+/// Parse the data of an entry.
+/// With `sametypesequence`, the types are omitted and the last field has no terminator or size.
+pub(crate) fn parse_fields(
+    mut data: &[u8],
+    sametypesequence: Option<&str>,
+) -> Result<Vec<Field>, StardictError> {
+    let mut fields = vec![];
+
+    match sametypesequence {
+        Some(types) => {
+            let types: Vec<char> = types.chars().collect();
+            for (i, ty) in types.iter().enumerate() {
+                let is_last = i + 1 == types.len();
+                let field;
+                (field, data) = if is_last {
+                    (split_last_field(*ty, data), &[][..])
+                } else {
+                    split_field(*ty, data)?
+                };
+                fields.push(field);
+            }
+        }
+        None => {
+            while let Some((ty, rest)) = data.split_first() {
+                let field;
+                (field, data) = split_field(char::from(*ty), rest)?;
+                fields.push(field);
+            }
+        }
+    }
+
+    Ok(fields)
+}
+
+fn split_field(ty: char, data: &[u8]) -> Result<(Field, &[u8]), StardictError> {
+    if ty.is_ascii_uppercase() {
+        let (size, rest) = split_be::<4>(data)?;
+        let size = size as usize;
+        if rest.len() < size {
+            return Err(StardictError::Truncated);
+        }
+        Ok((Field::Binary(ty), &rest[size..]))
+    } else {
+        let (text, rest) = split_str(data)?;
+        Ok((Field::Text(ty, text), rest))
+    }
+}
+
+fn split_last_field(ty: char, data: &[u8]) -> Field {
+    if ty.is_ascii_uppercase() {
+        Field::Binary(ty)
+    } else {
+        Field::Text(ty, String::from_utf8_lossy(data).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ifo() {
+        let ifo = parse_ifo(
+            "StarDict's dict ifo file
+version=3.0.0
+bookname=Test Dictionary
+wordcount=2
+idxfilesize=30
+idxoffsetbits=64
+sametypesequence=tm
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            ifo,
+            Ifo {
+                bookname: "Test Dictionary".into(),
+                wordcount: 2,
+                idxoffsetbits: 64,
+                sametypesequence: Some("tm".into()),
+            }
+        );
+
+        assert!(parse_ifo("version=2.4.2\n").is_err());
+        assert!(matches!(
+            parse_ifo("StarDict's dict ifo file\nversion=1.0.0\nbookname=a\nwordcount=0\n"),
+            Err(StardictError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_idx() {
+        let mut bytes = b"apple\0".to_vec();
+        bytes.extend(0u32.to_be_bytes());
+        bytes.extend(5u32.to_be_bytes());
+        bytes.extend(b"banana\0");
+        bytes.extend(5u32.to_be_bytes());
+        bytes.extend(7u32.to_be_bytes());
+
+        assert_eq!(
+            parse_idx(&bytes, 32).unwrap(),
+            vec![
+                IdxEntry {
+                    word: "apple".into(),
+                    offset: 0,
+                    size: 5
+                },
+                IdxEntry {
+                    word: "banana".into(),
+                    offset: 5,
+                    size: 7
+                },
+            ]
+        );
+
+        // 64 bits offsets
+        let mut bytes = b"apple\0".to_vec();
+        bytes.extend((1u64 << 33).to_be_bytes());
+        bytes.extend(5u32.to_be_bytes());
+
+        assert_eq!(parse_idx(&bytes, 64).unwrap()[0].offset, 1 << 33);
+
+        assert!(parse_idx(b"apple\0\0\0", 32).is_err());
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        assert_eq!(
+            parse_fields(b"/t/\0text", Some("tm")).unwrap(),
+            vec![
+                Field::Text('t', "/t/".into()),
+                Field::Text('m', "text".into())
+            ]
+        );
+
+        let mut data = b"mtext\0W".to_vec();
+        data.extend(2u32.to_be_bytes());
+        data.extend([0xff, 0xff]);
+        data.extend(b"h<b>html</b>\0");
+
+        assert_eq!(
+            parse_fields(&data, None).unwrap(),
+            vec![
+                Field::Text('m', "text".into()),
+                Field::Binary('W'),
+                Field::Text('h', "<b>html</b>".into())
+            ]
+        );
+    }
+}
@@ -0,0 +1,454 @@
+//! StarDict adapter
+//! Reads `.ifo`, `.idx`(or `.idx.gz`), `.dict`(or `.dict.dz`) and `.syn` of a StarDict dictionary.
+
+mod format;
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use etymora_traits::{fold_case, Completion, Dictionary, Word};
+use flate2::read::MultiGzDecoder;
+use format::{Field, IdxEntry, Ifo};
+use rustc_hash::FxHashMap;
+use thiserror::Error;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StardictConfig {
+    /// The path to `.ifo`. The other files are found next to it with the same name
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Error)]
+pub enum StardictError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Not found the file: {0}")]
+    NotFound(PathBuf),
+    #[error("Invalid .ifo file: {0}")]
+    InvalidIfo(String),
+    #[error("Unsupported StarDict version: {0}")]
+    UnsupportedVersion(String),
+    #[error("The dictionary file is truncated")]
+    Truncated,
+}
+
+#[derive(Debug)]
+pub struct StardictDictionary {
+    ifo: Ifo,
+    entries: Vec<IdxEntry>,
+    /// Case folded headwords to the indices of `entries`. Sorted for `prefix_search`
+    headwords: BTreeMap<String, Vec<usize>>,
+    /// Case folded synonyms to the indices of `entries`
+    synonyms: FxHashMap<String, Vec<usize>>,
+    dict: Vec<u8>,
+}
+
+async fn read(path: &Path) -> Result<Vec<u8>, StardictError> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|source| StardictError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Read the file, or its gzipped(or dictzipped) one with `gz_extension`
+async fn read_maybe_gz(path: PathBuf, gz_extension: &str) -> Result<Vec<u8>, StardictError> {
+    if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        return read(&path).await;
+    }
+
+    let mut gz_path = path.into_os_string();
+    gz_path.push(gz_extension);
+    let gz_path = PathBuf::from(gz_path);
+
+    if !tokio::fs::try_exists(&gz_path).await.unwrap_or(false) {
+        return Err(StardictError::NotFound(gz_path));
+    }
+
+    let compressed = read(&gz_path).await?;
+    let mut bytes = vec![];
+    std::io::Read::read_to_end(&mut MultiGzDecoder::new(&compressed[..]), &mut bytes).map_err(
+        |source| StardictError::Io {
+            path: gz_path,
+            source,
+        },
+    )?;
+
+    Ok(bytes)
+}
+
+impl StardictDictionary {
+    /// The entry and the data of it
+    fn entry(&self, index: usize) -> Result<(&IdxEntry, &[u8]), StardictError> {
+        let entry = &self.entries[index];
+        let start = entry.offset as usize;
+        let end = start + entry.size as usize;
+        let data = self.dict.get(start..end).ok_or(StardictError::Truncated)?;
+        Ok((entry, data))
+    }
+
+    fn render(&self, headword: &str, fields: &[Field]) -> String {
+        let body = fields
+            .iter()
+            .filter_map(render_field)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        format!("# {headword}\n\n{body}\n")
+    }
+
+    /// The first line of the definition
+    fn gloss(&self, index: usize) -> Result<Option<String>, StardictError> {
+        let (_, data) = self.entry(index)?;
+        let fields = format::parse_fields(data, self.ifo.sametypesequence.as_deref())?;
+
+        Ok(fields
+            .iter()
+            .filter(|field| !matches!(field, Field::Text('t', _)))
+            .filter_map(render_field)
+            .find_map(|doc| {
+                doc.lines()
+                    .map(|line| line.trim())
+                    .find(|line| !line.is_empty())
+                    .map(str::to_string)
+            }))
+    }
+}
+
+/// Render a field into markdown. Binary fields are skipped
+fn render_field(field: &Field) -> Option<String> {
+    match field {
+        Field::Text(_, text) if text.trim().is_empty() => None,
+        Field::Text('t', phonetic) => Some(format!("/{}/", phonetic.trim().trim_matches('/'))),
+        Field::Text('h' | 'g' | 'x', markup) => Some(hard_breaks(&strip_markup(markup))),
+        Field::Text('r', _) => None,
+        Field::Text(_, text) => Some(hard_breaks(text)),
+        Field::Binary(_) => None,
+    }
+}
+
+/// Keep the line breaks of plain text in markdown
+fn hard_breaks(text: &str) -> String {
+    text.trim()
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("  \n")
+}
+
+/// Remove the tags of HTML, Pango markup and XDXF
+fn strip_markup(markup: &str) -> String {
+    let mut text = String::with_capacity(markup.len());
+    let mut rest = markup;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let tag = rest[start + 1..start + end].trim().to_ascii_lowercase();
+        if tag.starts_with("br") || tag == "/p" || tag == "/div" || tag == "/li" {
+            text.push('\n');
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+impl Dictionary for StardictDictionary {
+    type Error = StardictError;
+    type InitInput = StardictConfig;
+
+    async fn init(input: &Self::InitInput) -> Result<Self, Self::Error> {
+        let ifo = read(&input.path).await?;
+        let ifo = format::parse_ifo(&String::from_utf8_lossy(&ifo))?;
+
+        // Names like "stardict-gcide-2.4.2.ifo" have dots
+        let entries = format::parse_idx(
+            &read_maybe_gz(input.path.with_extension("idx"), ".gz").await?,
+            ifo.idxoffsetbits,
+        )?;
+        if entries.len() != ifo.wordcount {
+            tracing::warn!(
+                "wordcount is {}, but .idx has {} words",
+                ifo.wordcount,
+                entries.len()
+            );
+        }
+
+        let dict = read_maybe_gz(input.path.with_extension("dict"), ".dz").await?;
+
+        let mut headwords: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            headwords.entry(fold_case(&entry.word)).or_default().push(i);
+        }
+
+        let mut synonyms: FxHashMap<String, Vec<usize>> = FxHashMap::default();
+        let syn_path = input.path.with_extension("syn");
+        if tokio::fs::try_exists(&syn_path).await.unwrap_or(false) {
+            for (word, index) in format::parse_syn(&read(&syn_path).await?)? {
+                if (index as usize) < entries.len() {
+                    synonyms
+                        .entry(fold_case(&word))
+                        .or_default()
+                        .push(index as usize);
+                }
+            }
+        }
+
+        tracing::info!("Loaded {} with {} words", ifo.bookname, entries.len());
+
+        Ok(StardictDictionary {
+            ifo,
+            entries,
+            headwords,
+            synonyms,
+            dict,
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        let key = fold_case(word.as_str());
+        Ok(self.headwords.contains_key(&key) || self.synonyms.contains_key(&key))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn lookup_ditail(&self, word: &Word) -> Result<Option<String>, Self::Error> {
+        let key = fold_case(word.as_str());
+
+        let mut indices: Vec<usize> = vec![];
+        for index in self
+            .headwords
+            .get(&key)
+            .into_iter()
+            .chain(self.synonyms.get(&key))
+            .flatten()
+        {
+            if !indices.contains(index) {
+                indices.push(*index);
+            }
+        }
+
+        if indices.is_empty() {
+            return Ok(None);
+        }
+
+        let mut docs = Vec::with_capacity(indices.len());
+        for index in indices {
+            let (entry, data) = self.entry(index)?;
+            let fields = format::parse_fields(data, self.ifo.sametypesequence.as_deref())?;
+            docs.push(self.render(&entry.word, &fields));
+        }
+
+        Ok(Some(docs.join("\n---\n\n")))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn prefix_search(
+        &self,
+        prefix: &Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        let prefix = fold_case(prefix.as_str());
+
+        let mut completions = vec![];
+        for (_, indices) in self
+            .headwords
+            .range(prefix.clone()..)
+            .take_while(|(headword, _)| headword.starts_with(&prefix))
+            .take(limit)
+        {
+            let index = indices[0];
+            completions.push(Completion::new(
+                self.entries[index].word.clone(),
+                self.gloss(index)?,
+            ));
+        }
+
+        Ok(completions)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(self.headwords.keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    /// Write a dictionary `name.ifo` with "apple", "Banana" and the synonym "pomme" of "apple"
+    fn write_dictionary(dir: &Path, name: &str, compress: bool) -> PathBuf {
+        let apple = b"/\xcb\x88\xc3\xa6p\xc9\x99l/\0A round fruit.\nIt grows on trees.".to_vec();
+        let banana = b"\0<b>A long</b> yellow fruit &amp; a plant.".to_vec();
+
+        let mut idx = b"apple\0".to_vec();
+        idx.extend(0u32.to_be_bytes());
+        idx.extend((apple.len() as u32).to_be_bytes());
+        idx.extend(b"Banana\0");
+        idx.extend((apple.len() as u32).to_be_bytes());
+        idx.extend((banana.len() as u32).to_be_bytes());
+
+        let mut syn = b"pomme\0".to_vec();
+        syn.extend(0u32.to_be_bytes());
+
+        let dict = [apple, banana].concat();
+
+        std::fs::write(
+            dir.join(format!("{name}.ifo")),
+            "StarDict's dict ifo file\nversion=2.4.2\nbookname=Test\nwordcount=2\nsametypesequence=th\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join(format!("{name}.idx")), idx).unwrap();
+        std::fs::write(dir.join(format!("{name}.syn")), syn).unwrap();
+
+        if compress {
+            let mut encoder = flate2::write::GzEncoder::new(
+                std::fs::File::create(dir.join(format!("{name}.dict.dz"))).unwrap(),
+                flate2::Compression::default(),
+            );
+            encoder.write_all(&dict).unwrap();
+            encoder.finish().unwrap();
+        } else {
+            std::fs::write(dir.join(format!("{name}.dict")), dict).unwrap();
+        }
+
+        dir.join(format!("{name}.ifo"))
+    }
+
+    #[tokio::test]
+    async fn test_stardict_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_dictionary(dir.path(), "test", false);
+
+        let dict = StardictDictionary::init(&StardictConfig { path })
+            .await
+            .unwrap();
+
+        assert!(dict.exits(&"apple".into()).await.unwrap());
+        assert!(dict.exits(&"banana".into()).await.unwrap());
+        assert!(dict.exits(&"pomme".into()).await.unwrap());
+        assert!(!dict.exits(&"cherry".into()).await.unwrap());
+
+        assert_eq!(
+            dict.lookup_ditail(&"apple".into()).await.unwrap().unwrap(),
+            "# apple\n\n/ˈæpəl/\n\nA round fruit.  \nIt grows on trees.\n"
+        );
+        assert_eq!(
+            dict.lookup_ditail(&"banana".into()).await.unwrap().unwrap(),
+            "# Banana\n\nA long yellow fruit & a plant.\n"
+        );
+
+        // The synonym shows the original word
+        assert!(dict
+            .lookup_ditail(&"pomme".into())
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("# apple\n"));
+
+        assert_eq!(dict.lookup_ditail(&"cherry".into()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stardict_prefix_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_dictionary(dir.path(), "test", false);
+
+        let dict = StardictDictionary::init(&StardictConfig { path })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            dict.prefix_search(&"BA".into(), 10).await.unwrap(),
+            vec![Completion::new(
+                "Banana",
+                Some("A long yellow fruit & a plant.".into())
+            )]
+        );
+        // The phonetic isn't a gloss
+        assert_eq!(
+            dict.prefix_search(&"".into(), 1).await.unwrap(),
+            vec![Completion::new("apple", Some("A round fruit.".into()))]
+        );
+        assert!(dict
+            .prefix_search(&"c".into(), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(dict.headwords().await.unwrap(), vec!["apple", "banana"]);
+    }
+
+    #[tokio::test]
+    async fn test_stardict_dictzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_dictionary(dir.path(), "test", true);
+
+        let dict = StardictDictionary::init(&StardictConfig { path })
+            .await
+            .unwrap();
+
+        assert!(dict
+            .lookup_ditail(&"apple".into())
+            .await
+            .unwrap()
+            .unwrap()
+            .contains("A round fruit."));
+    }
+
+    #[tokio::test]
+    async fn test_stardict_dotted_name() {
+        for compress in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let path = write_dictionary(dir.path(), "dict-1.2", compress);
+
+            let dict = StardictDictionary::init(&StardictConfig { path })
+                .await
+                .unwrap();
+            assert!(dict.exits(&"pomme".into()).await.unwrap());
+            assert!(dict.lookup_ditail(&"apple".into()).await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stardict_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_dictionary(dir.path(), "test", false);
+        std::fs::remove_file(dir.path().join("test.dict")).unwrap();
+
+        assert!(matches!(
+            StardictDictionary::init(&StardictConfig { path }).await,
+            Err(StardictError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_strip_markup() {
+        assert_eq!(
+            strip_markup("<p>a<br/>b</p><i>c</i> &lt;d&gt;"),
+            "a\nb\nc <d>"
+        );
+    }
+}
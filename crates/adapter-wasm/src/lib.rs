@@ -0,0 +1,485 @@
+//! WASM adapter
+//! Loads a dictionary adapter as a WASM component, which implements `wit/dictionary.wit`.
+//! The component can read only the data directory, and can't access the network or the environment.
+//! Each call is interrupted after `timeout`, and the memory is limited to `max_memory`.
+//! The component is instantiated again after it has trapped.
+//! `adapter-example` is a sample of the guest.
+
+use std::{path::PathBuf, sync::mpsc, time::Duration};
+
+use etymora_traits::{fold_case, Completion, Dictionary, Word};
+use thiserror::Error;
+use tokio::sync::{Mutex, MutexGuard};
+use wasmtime::{
+    component::{Component, Linker, ResourceTable},
+    Engine, Store, StoreLimits, StoreLimitsBuilder,
+};
+use wasmtime_wasi::{DirPerms, FilePerms, WasiCtx, WasiCtxBuilder, WasiView};
+
+wasmtime::component::bindgen!({
+    path: "wit",
+    world: "adapter",
+    async: true,
+});
+
+/// The path of the data directory in the guest
+const DATA_DIR: &str = "/data";
+
+/// The interval of the epochs, which is the resolution of `timeout`
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WasmConfig {
+    /// The path to the component(`.wasm`)
+    pub path: PathBuf,
+    /// Mounted at `/data` read-only
+    pub data_dir: Option<PathBuf>,
+    /// Passed to `init` as JSON
+    #[serde(default)]
+    pub options: serde_json::Value,
+    /// The timeout of each call in milliseconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// The max size of the linear memories in MiB
+    #[serde(default = "default_max_memory")]
+    pub max_memory: usize,
+}
+
+fn default_timeout() -> u64 {
+    5000
+}
+
+fn default_max_memory() -> usize {
+    256
+}
+
+#[derive(Debug, Error)]
+pub enum WasmError {
+    #[error("Failed to load {path}: {source:#}")]
+    Load {
+        path: PathBuf,
+        source: wasmtime::Error,
+    },
+    #[error("{path} has trapped: {source:#}")]
+    Trap {
+        path: PathBuf,
+        source: wasmtime::Error,
+    },
+    #[error("{path}: {message}")]
+    Guest { path: PathBuf, message: String },
+}
+
+struct Host {
+    ctx: WasiCtx,
+    table: ResourceTable,
+    limits: StoreLimits,
+}
+
+impl WasiView for Host {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.ctx
+    }
+}
+
+struct Instance {
+    store: Store<Host>,
+    adapter: Adapter,
+}
+
+pub struct WasmDictionary {
+    config: WasmConfig,
+    engine: Engine,
+    component: Component,
+    linker: Linker<Host>,
+    /// `None` after the instance has trapped
+    instance: Mutex<Option<Instance>>,
+    /// Stops the thread incrementing the epoch when it's dropped
+    _ticker: mpsc::Sender<()>,
+}
+
+impl std::fmt::Debug for WasmDictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmDictionary")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WasmDictionary {
+    fn trap(&self, source: wasmtime::Error) -> WasmError {
+        WasmError::Trap {
+            path: self.config.path.clone(),
+            source,
+        }
+    }
+
+    fn guest(&self, message: String) -> WasmError {
+        WasmError::Guest {
+            path: self.config.path.clone(),
+            message,
+        }
+    }
+
+    fn load(&self, source: wasmtime::Error) -> WasmError {
+        WasmError::Load {
+            path: self.config.path.clone(),
+            source,
+        }
+    }
+
+    /// Increment the epoch of the engine until the sender is dropped
+    fn start_ticker(engine: Engine) -> mpsc::Sender<()> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = receiver.recv_timeout(EPOCH_TICK) {
+                engine.increment_epoch();
+            }
+        });
+        sender
+    }
+
+    /// A fresh store, whose instance is initialized with the options
+    async fn instantiate(&self) -> Result<Instance, WasmError> {
+        let mut ctx = WasiCtxBuilder::new();
+        ctx.inherit_stderr();
+        if let Some(data_dir) = &self.config.data_dir {
+            ctx.preopened_dir(data_dir, DATA_DIR, DirPerms::READ, FilePerms::READ)
+                .map_err(|e| self.load(e))?;
+        }
+
+        let mut store = Store::new(
+            &self.engine,
+            Host {
+                ctx: ctx.build(),
+                table: ResourceTable::new(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(self.config.max_memory.saturating_mul(1024 * 1024))
+                    .trap_on_grow_failure(true)
+                    .build(),
+            },
+        );
+        store.limiter(|host| &mut host.limits);
+        store.set_epoch_deadline(self.deadline());
+
+        let adapter = Adapter::instantiate_async(&mut store, &self.component, &self.linker)
+            .await
+            .map_err(|e| self.load(e))?;
+        adapter
+            .etymora_dictionary_dictionary()
+            .call_init(&mut store, &self.config.options.to_string())
+            .await
+            .map_err(|e| self.trap(e))?
+            .map_err(|e| self.guest(e))?;
+
+        Ok(Instance { store, adapter })
+    }
+
+    /// The epochs until the timeout
+    fn deadline(&self) -> u64 {
+        (self.config.timeout / EPOCH_TICK.as_millis() as u64).max(1)
+    }
+
+    /// The instance with the deadline of a call. It's instantiated again if it has trapped
+    async fn instance(&self) -> Result<MutexGuard<'_, Option<Instance>>, WasmError> {
+        let mut instance = self.instance.lock().await;
+        match instance.as_mut() {
+            Some(Instance { store, .. }) => store.set_epoch_deadline(self.deadline()),
+            None => {
+                tracing::warn!("Instantiating {} again", self.config.path.display());
+                *instance = Some(self.instantiate().await?);
+            }
+        }
+        Ok(instance)
+    }
+
+    /// Drop the instance if it has trapped, since its state may be broken
+    fn check<T>(
+        &self,
+        instance: &mut Option<Instance>,
+        result: wasmtime::Result<Result<T, String>>,
+    ) -> Result<T, WasmError> {
+        match result {
+            Ok(result) => result.map_err(|e| self.guest(e)),
+            Err(e) => {
+                *instance = None;
+                Err(self.trap(e))
+            }
+        }
+    }
+}
+
+impl Dictionary for WasmDictionary {
+    type Error = WasmError;
+    type InitInput = WasmConfig;
+
+    async fn init(input: &Self::InitInput) -> Result<Self, Self::Error> {
+        let load = |source| WasmError::Load {
+            path: input.path.clone(),
+            source,
+        };
+
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.async_support(true).epoch_interruption(true);
+        let engine = Engine::new(&engine_config).map_err(load)?;
+
+        let component = Component::from_file(&engine, &input.path).map_err(load)?;
+
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker_async(&mut linker).map_err(load)?;
+
+        let dict = WasmDictionary {
+            config: input.clone(),
+            _ticker: Self::start_ticker(engine.clone()),
+            engine,
+            component,
+            linker,
+            instance: Mutex::new(None),
+        };
+        *dict.instance.lock().await = Some(dict.instantiate().await?);
+
+        tracing::info!("Loaded {}", input.path.display());
+        Ok(dict)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        let mut instance = self.instance().await?;
+        let Some(Instance { store, adapter }) = instance.as_mut() else {
+            unreachable!("instantiated");
+        };
+        let result = adapter
+            .etymora_dictionary_dictionary()
+            .call_exists(store, word.as_str())
+            .await;
+        self.check(&mut instance, result)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn lookup_ditail(&self, word: &Word) -> Result<Option<String>, Self::Error> {
+        let mut instance = self.instance().await?;
+        let Some(Instance { store, adapter }) = instance.as_mut() else {
+            unreachable!("instantiated");
+        };
+        let result = adapter
+            .etymora_dictionary_dictionary()
+            .call_lookup_detail(store, word.as_str())
+            .await;
+        self.check(&mut instance, result)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn prefix_search(
+        &self,
+        prefix: &Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        let mut instance = self.instance().await?;
+        let Some(Instance { store, adapter }) = instance.as_mut() else {
+            unreachable!("instantiated");
+        };
+        let result = adapter
+            .etymora_dictionary_dictionary()
+            .call_prefix_search(store, prefix.as_str(), limit.try_into().unwrap_or(u32::MAX))
+            .await;
+
+        Ok(self
+            .check(&mut instance, result)?
+            .into_iter()
+            .map(|completion| Completion::new(completion.word, completion.gloss))
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        let mut instance = self.instance().await?;
+        let Some(Instance { store, adapter }) = instance.as_mut() else {
+            unreachable!("instantiated");
+        };
+        let result = adapter
+            .etymora_dictionary_dictionary()
+            .call_headwords(store)
+            .await;
+
+        Ok(self
+            .check(&mut instance, result)?
+            .iter()
+            .map(|word| fold_case(word))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    /// A component which knows only the words with 5 letters, and looks up(and completes) a word as it is.
+    /// Its headword is only "Apple". `init` fails unless the options is `{}`.
+    /// Looking up a word with 4 letters never ends, and one with 3 letters grows the memory by 64 MiB
+    const COMPONENT: &str = r#"
+(component
+  (core module $m
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (data (i32.const 64) "invalid options")
+    (data (i32.const 96) "Apple")
+    (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ptr i32)
+      (local.set $ptr (i32.and
+        (i32.add (global.get $heap) (i32.sub (local.get 2) (i32.const 1)))
+        (i32.sub (i32.const 0) (local.get 2))))
+      (global.set $heap (i32.add (local.get $ptr) (local.get 3)))
+      (local.get $ptr))
+    (func (export "init") (param i32 i32) (result i32)
+      (if (i32.eq (local.get 1) (i32.const 2))
+        (then (i32.store8 (i32.const 0) (i32.const 0)))
+        (else
+          (i32.store8 (i32.const 0) (i32.const 1))
+          (i32.store (i32.const 4) (i32.const 64))
+          (i32.store (i32.const 8) (i32.const 15))))
+      (i32.const 0))
+    (func (export "exists") (param i32 i32) (result i32)
+      (i32.store8 (i32.const 0) (i32.const 0))
+      (i32.store8 (i32.const 4) (i32.eq (local.get 1) (i32.const 5)))
+      (i32.const 0))
+    (func (export "prefix-search") (param i32 i32 i32) (result i32)
+      (i32.store (i32.const 32) (local.get 0))
+      (i32.store (i32.const 36) (local.get 1))
+      (i32.store8 (i32.const 40) (i32.const 0))
+      (i32.store8 (i32.const 0) (i32.const 0))
+      (i32.store (i32.const 4) (i32.const 32))
+      (i32.store (i32.const 8) (i32.const 1))
+      (i32.const 0))
+    (func (export "headwords") (result i32)
+      (i32.store (i32.const 128) (i32.const 96))
+      (i32.store (i32.const 132) (i32.const 5))
+      (i32.store8 (i32.const 0) (i32.const 0))
+      (i32.store (i32.const 4) (i32.const 128))
+      (i32.store (i32.const 8) (i32.const 1))
+      (i32.const 0))
+    (func (export "lookup-detail") (param i32 i32) (result i32)
+      (if (i32.eq (local.get 1) (i32.const 4))
+        (then (loop $forever (br $forever))))
+      (if (i32.eq (local.get 1) (i32.const 3))
+        (then (drop (memory.grow (i32.const 1024)))))
+      (i32.store8 (i32.const 0) (i32.const 0))
+      (i32.store8 (i32.const 4) (i32.const 1))
+      (i32.store (i32.const 8) (local.get 0))
+      (i32.store (i32.const 12) (local.get 1))
+      (i32.const 0))
+  )
+  (core instance $i (instantiate $m))
+  (func $init (param "options" string) (result (result (error string)))
+    (canon lift (core func $i "init") (memory $i "memory") (realloc (func $i "cabi_realloc"))))
+  (func $exists (param "word" string) (result (result bool (error string)))
+    (canon lift (core func $i "exists") (memory $i "memory") (realloc (func $i "cabi_realloc"))))
+  (func $lookup-detail (param "word" string) (result (result (option string) (error string)))
+    (canon lift (core func $i "lookup-detail") (memory $i "memory") (realloc (func $i "cabi_realloc"))))
+  (type $completion (record (field "word" string) (field "gloss" (option string))))
+  (export $completion-type "completion" (type $completion))
+  (func $prefix-search (param "prefix" string) (param "limit" u32) (result (result (list $completion-type) (error string)))
+    (canon lift (core func $i "prefix-search") (memory $i "memory") (realloc (func $i "cabi_realloc"))))
+  (func $headwords (result (result (list string) (error string)))
+    (canon lift (core func $i "headwords") (memory $i "memory") (realloc (func $i "cabi_realloc"))))
+  (instance $dictionary
+    (export "init" (func $init))
+    (export "exists" (func $exists))
+    (export "lookup-detail" (func $lookup-detail))
+    (export "prefix-search" (func $prefix-search))
+    (export "headwords" (func $headwords))
+    (export "completion" (type $completion-type)))
+  (export "etymora:dictionary/dictionary@0.1.0" (instance $dictionary))
+)
+"#;
+
+    fn config(dir: &Path) -> WasmConfig {
+        let path = dir.join("adapter.wat");
+        std::fs::write(&path, COMPONENT).unwrap();
+
+        WasmConfig {
+            path,
+            data_dir: Some(dir.to_path_buf()),
+            options: serde_json::json!({}),
+            timeout: 100,
+            max_memory: 16,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wasm_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = WasmDictionary::init(&config(dir.path())).await.unwrap();
+
+        assert!(dict.exits(&"apple".into()).await.unwrap());
+        assert!(!dict.exits(&"kiwi".into()).await.unwrap());
+        assert_eq!(
+            dict.lookup_ditail(&"apple".into()).await.unwrap().unwrap(),
+            "apple"
+        );
+        assert_eq!(
+            dict.prefix_search(&"ap".into(), 10).await.unwrap(),
+            vec![Completion::new("ap", None)]
+        );
+        assert_eq!(dict.headwords().await.unwrap(), vec!["apple"]);
+    }
+
+    #[tokio::test]
+    async fn test_wasm_trap() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = WasmDictionary::init(&config(dir.path())).await.unwrap();
+
+        // Interrupted at the deadline
+        assert!(matches!(
+            dict.lookup_ditail(&"loop".into()).await,
+            Err(WasmError::Trap { .. })
+        ));
+        // Instantiated again
+        assert!(dict.exits(&"apple".into()).await.unwrap());
+
+        // Over `max_memory`
+        assert!(matches!(
+            dict.lookup_ditail(&"oom".into()).await,
+            Err(WasmError::Trap { .. })
+        ));
+        assert_eq!(
+            dict.lookup_ditail(&"apple".into()).await.unwrap().unwrap(),
+            "apple"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wasm_errors() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config = WasmConfig {
+            options: serde_json::json!({ "lang": "en" }),
+            ..config(dir.path())
+        };
+        assert!(matches!(
+            WasmDictionary::init(&config).await,
+            Err(WasmError::Guest { message, .. }) if message == "invalid options"
+        ));
+
+        let config = WasmConfig {
+            data_dir: Some(dir.path().join("not-exist")),
+            ..config
+        };
+        assert!(matches!(
+            WasmDictionary::init(&config).await,
+            Err(WasmError::Load { .. })
+        ));
+
+        let path = dir.path().join("broken.wasm");
+        std::fs::write(&path, b"\0asm").unwrap();
+        let config = WasmConfig { path, ..config };
+        assert!(matches!(
+            WasmDictionary::init(&config).await,
+            Err(WasmError::Load { .. })
+        ));
+    }
+}
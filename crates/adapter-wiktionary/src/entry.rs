@@ -0,0 +1,185 @@
+//! Entries of wiktextract and rendering them into markdown
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Entry {
+    pub(crate) word: String,
+    #[serde(default)]
+    pub(crate) lang: Option<String>,
+    #[serde(default)]
+    pub(crate) pos: Option<String>,
+    #[serde(default)]
+    pub(crate) etymology_text: Option<String>,
+    #[serde(default)]
+    pub(crate) sounds: Vec<Sound>,
+    #[serde(default)]
+    pub(crate) forms: Vec<Form>,
+    #[serde(default)]
+    pub(crate) senses: Vec<Sense>,
+    #[serde(default)]
+    pub(crate) translations: Vec<Translation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Sound {
+    #[serde(default)]
+    pub(crate) ipa: Option<String>,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Form {
+    pub(crate) form: String,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Sense {
+    #[serde(default)]
+    pub(crate) glosses: Vec<String>,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Translation {
+    #[serde(default)]
+    pub(crate) lang: Option<String>,
+    #[serde(default)]
+    pub(crate) code: Option<String>,
+    #[serde(default)]
+    pub(crate) word: Option<String>,
+}
+
+/// Forms which are not inflections
+const IGNORED_FORM_TAGS: [&str; 3] = ["table-tags", "inflection-template", "romanization"];
+
+/// Push the item if it's not pushed yet
+fn push_unique(items: &mut Vec<String>, item: String) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+fn with_tags(text: &str, tags: &[String]) -> String {
+    if tags.is_empty() {
+        text.to_string()
+    } else {
+        format!("{text} ({})", tags.join(", "))
+    }
+}
+
+/// Capitalize the first letter of parts of speech like "noun"
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Render the entries of a word. Etymologies come first.
+/// `translation_langs` filters the translations by language codes
+pub(crate) fn render(entries: &[Entry], translation_langs: Option<&[String]>) -> String {
+    let Some(first) = entries.first() else {
+        return String::new();
+    };
+
+    let mut etymologies = vec![];
+    let mut pronunciations = vec![];
+    let mut translations = vec![];
+
+    for entry in entries {
+        if let Some(etymology) = &entry.etymology_text {
+            if !etymology.trim().is_empty() {
+                push_unique(&mut etymologies, etymology.trim().to_string());
+            }
+        }
+
+        for sound in &entry.sounds {
+            if let Some(ipa) = &sound.ipa {
+                push_unique(
+                    &mut pronunciations,
+                    format!("- {}", with_tags(ipa, &sound.tags)),
+                );
+            }
+        }
+
+        for translation in &entry.translations {
+            let (Some(lang), Some(word)) = (&translation.lang, &translation.word) else {
+                continue;
+            };
+            let shown = translation_langs.is_none_or(|langs| {
+                translation
+                    .code
+                    .as_ref()
+                    .is_some_and(|code| langs.contains(code))
+            });
+            if shown {
+                push_unique(&mut translations, format!("- {lang}: {word}"));
+            }
+        }
+    }
+
+    let mut doc = format!("# {}\n", first.word);
+
+    if !etymologies.is_empty() {
+        doc.push_str(&format!("\n## Etymology\n\n{}\n", etymologies.join("\n\n")));
+    }
+
+    if !pronunciations.is_empty() {
+        doc.push_str(&format!(
+            "\n## Pronunciation\n\n{}\n",
+            pronunciations.join("\n")
+        ));
+    }
+
+    for entry in entries {
+        let pos = entry.pos.as_deref().map(capitalize).unwrap_or_default();
+        match &entry.lang {
+            Some(lang) => doc.push_str(&format!("\n## {pos} ({lang})\n\n")),
+            None => doc.push_str(&format!("\n## {pos}\n\n")),
+        }
+
+        let senses: Vec<String> = entry
+            .senses
+            .iter()
+            .filter(|sense| !sense.glosses.is_empty())
+            .enumerate()
+            .map(|(i, sense)| {
+                // The last gloss is the most specific one
+                let gloss = sense.glosses.last().unwrap();
+                format!("{}. {}", i + 1, with_tags(gloss, &sense.tags))
+            })
+            .collect();
+        doc.push_str(&senses.join("\n"));
+        doc.push('\n');
+
+        let mut forms = vec![];
+        for form in &entry.forms {
+            if form.form != entry.word
+                && !form
+                    .tags
+                    .iter()
+                    .any(|tag| IGNORED_FORM_TAGS.contains(&tag.as_str()))
+            {
+                push_unique(&mut forms, with_tags(&form.form, &form.tags));
+            }
+        }
+        if !forms.is_empty() {
+            doc.push_str(&format!("\nInflections: {}\n", forms.join(", ")));
+        }
+    }
+
+    if !translations.is_empty() {
+        doc.push_str(&format!(
+            "\n## Translations\n\n{}\n",
+            translations.join("\n")
+        ));
+    }
+
+    doc
+}
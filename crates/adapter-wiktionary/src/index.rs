@@ -0,0 +1,206 @@
+//! The index of a JSONL file, from case folded words to the lines of their entries.
+//! It is built once and persisted next to the JSONL file.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
+};
+
+use etymora_traits::fold_case;
+
+use crate::WiktionaryError;
+
+const INDEX_VERSION: &str = "etymora-wiktionary-index 1";
+
+/// The byte range of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Line {
+    pub(crate) offset: u64,
+    pub(crate) len: u32,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Index {
+    /// Sorted for the prefix search
+    pub(crate) words: BTreeMap<String, Vec<Line>>,
+}
+
+/// Only the fields needed for indexing
+#[derive(serde::Deserialize)]
+struct IndexEntry {
+    word: String,
+    #[serde(default)]
+    lang_code: Option<String>,
+}
+
+/// Identify the version of the JSONL file by its size and modified time
+async fn source_stamp(path: &Path) -> Result<String, WiktionaryError> {
+    let metadata = fs::metadata(path)
+        .await
+        .map_err(|e| WiktionaryError::io(path, e))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs());
+    Ok(format!("{} {modified}", metadata.len()))
+}
+
+/// `<path>.etymora-index`
+pub(crate) fn index_path(path: &Path) -> PathBuf {
+    let mut index = path.as_os_str().to_owned();
+    index.push(".etymora-index");
+    PathBuf::from(index)
+}
+
+impl Index {
+    /// Load the persisted index, or build and persist it if it's missing or outdated
+    pub(crate) async fn load_or_build(
+        path: &Path,
+        lang_code: Option<&str>,
+    ) -> Result<Self, WiktionaryError> {
+        let header = format!(
+            "{INDEX_VERSION} {} {}",
+            source_stamp(path).await?,
+            lang_code.unwrap_or("*")
+        );
+        let index_path = index_path(path);
+
+        match Self::load(&index_path, &header).await {
+            Ok(Some(index)) => return Ok(index),
+            Ok(None) => tracing::info!("The index is outdated, rebuilding it"),
+            Err(e) => tracing::info!("Building the index: {e}"),
+        }
+
+        let index = Self::build(path, lang_code).await?;
+
+        if let Err(e) = index.save(&index_path, &header).await {
+            // The index works without persisting
+            tracing::warn!("Failed to save the index: {e}");
+        }
+
+        Ok(index)
+    }
+
+    /// `Ok(None)` if the index is for another version of the file
+    async fn load(index_path: &Path, header: &str) -> Result<Option<Self>, WiktionaryError> {
+        let file = fs::File::open(index_path)
+            .await
+            .map_err(|e| WiktionaryError::io(index_path, e))?;
+        let mut lines = BufReader::new(file).lines();
+
+        if lines
+            .next_line()
+            .await
+            .map_err(|e| WiktionaryError::io(index_path, e))?
+            .as_deref()
+            != Some(header)
+        {
+            return Ok(None);
+        }
+
+        let mut index = Index::default();
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| WiktionaryError::io(index_path, e))?
+        {
+            let mut columns = line.split('\t');
+            let (Some(word), Some(offset), Some(len)) =
+                (columns.next(), columns.next(), columns.next())
+            else {
+                return Err(WiktionaryError::InvalidIndex(index_path.to_path_buf()));
+            };
+            let (Ok(offset), Ok(len)) = (offset.parse(), len.parse()) else {
+                return Err(WiktionaryError::InvalidIndex(index_path.to_path_buf()));
+            };
+
+            index
+                .words
+                .entry(word.to_string())
+                .or_default()
+                .push(Line { offset, len });
+        }
+
+        Ok(Some(index))
+    }
+
+    async fn save(&self, index_path: &Path, header: &str) -> Result<(), WiktionaryError> {
+        let file = fs::File::create(index_path)
+            .await
+            .map_err(|e| WiktionaryError::io(index_path, e))?;
+        let mut writer = BufWriter::new(file);
+
+        let mut content = format!("{header}\n");
+        for (word, lines) in &self.words {
+            for line in lines {
+                content.push_str(&format!("{word}\t{}\t{}\n", line.offset, line.len));
+            }
+            if content.len() > 1 << 16 {
+                writer
+                    .write_all(content.as_bytes())
+                    .await
+                    .map_err(|e| WiktionaryError::io(index_path, e))?;
+                content.clear();
+            }
+        }
+
+        writer
+            .write_all(content.as_bytes())
+            .await
+            .map_err(|e| WiktionaryError::io(index_path, e))?;
+        writer
+            .flush()
+            .await
+            .map_err(|e| WiktionaryError::io(index_path, e))
+    }
+
+    async fn build(path: &Path, lang_code: Option<&str>) -> Result<Self, WiktionaryError> {
+        let file = fs::File::open(path)
+            .await
+            .map_err(|e| WiktionaryError::io(path, e))?;
+        let mut reader = BufReader::new(file);
+
+        let mut index = Index::default();
+        let mut offset = 0;
+        let mut line = vec![];
+
+        loop {
+            line.clear();
+            let len = reader
+                .read_until(b'\n', &mut line)
+                .await
+                .map_err(|e| WiktionaryError::io(path, e))?;
+            if len == 0 {
+                break;
+            }
+
+            match serde_json::from_slice::<IndexEntry>(&line) {
+                Ok(entry) if lang_code.is_none() || entry.lang_code.as_deref() == lang_code => {
+                    let word = fold_case(&entry.word);
+                    // Tabs and line breaks cannot be saved in the index
+                    if !word.contains(['\t', '\n', '\r']) {
+                        index.words.entry(word).or_default().push(Line {
+                            offset,
+                            len: len as u32,
+                        });
+                    }
+                }
+                Ok(_) => (),
+                // Empty lines
+                Err(_) if line.iter().all(u8::is_ascii_whitespace) => (),
+                Err(e) => tracing::warn!("Skipped an invalid line at {offset}: {e}"),
+            }
+
+            offset += len as u64;
+        }
+
+        Ok(index)
+    }
+}
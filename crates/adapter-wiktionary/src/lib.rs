@@ -0,0 +1,287 @@
+//! Wiktionary adapter
+//! Reads a JSONL dump of wiktextract. The index of it is persisted next to the file.
+
+mod entry;
+mod index;
+
+use std::path::{Path, PathBuf};
+
+use etymora_traits::{fold_case, Completion, Dictionary, Word};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WiktionaryConfig {
+    /// The path to the JSONL file
+    pub path: PathBuf,
+    /// Only the entries of the language like "en" are used. All if it's not set
+    #[serde(default)]
+    pub lang_code: Option<String>,
+    /// The language codes of the translations shown. All if it's not set
+    #[serde(default)]
+    pub translation_langs: Option<Vec<String>>,
+}
+
+#[derive(Debug, Error)]
+pub enum WiktionaryError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid index file: {0}")]
+    InvalidIndex(PathBuf),
+    #[error("Invalid entry: {0}")]
+    InvalidEntry(#[source] serde_json::Error),
+}
+
+impl WiktionaryError {
+    fn io(path: &Path, source: std::io::Error) -> Self {
+        WiktionaryError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WiktionaryDictionary {
+    config: WiktionaryConfig,
+    index: index::Index,
+}
+
+impl WiktionaryDictionary {
+    async fn entries(&self, lines: &[index::Line]) -> Result<Vec<entry::Entry>, WiktionaryError> {
+        let path = &self.config.path;
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .map_err(|e| WiktionaryError::io(path, e))?;
+
+        let mut entries = Vec::with_capacity(lines.len());
+        for line in lines {
+            file.seek(std::io::SeekFrom::Start(line.offset))
+                .await
+                .map_err(|e| WiktionaryError::io(path, e))?;
+
+            let mut buf = vec![0; line.len as usize];
+            file.read_exact(&mut buf)
+                .await
+                .map_err(|e| WiktionaryError::io(path, e))?;
+
+            entries.push(serde_json::from_slice(&buf).map_err(WiktionaryError::InvalidEntry)?);
+        }
+
+        Ok(entries)
+    }
+}
+
+impl Dictionary for WiktionaryDictionary {
+    type Error = WiktionaryError;
+    type InitInput = WiktionaryConfig;
+
+    async fn init(input: &Self::InitInput) -> Result<Self, Self::Error> {
+        let index = index::Index::load_or_build(&input.path, input.lang_code.as_deref()).await?;
+
+        tracing::info!(
+            "Loaded {} with {} words",
+            input.path.display(),
+            index.words.len()
+        );
+
+        Ok(WiktionaryDictionary {
+            config: input.clone(),
+            index,
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        Ok(self.index.words.contains_key(&fold_case(word.as_str())))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn lookup_ditail(&self, word: &Word) -> Result<Option<String>, Self::Error> {
+        let Some(lines) = self.index.words.get(&fold_case(word.as_str())) else {
+            return Ok(None);
+        };
+
+        let entries = self.entries(lines).await?;
+
+        Ok(Some(entry::render(
+            &entries,
+            self.config.translation_langs.as_deref(),
+        )))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn prefix_search(
+        &self,
+        prefix: &Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        let prefix = fold_case(prefix.as_str());
+
+        let mut completions = vec![];
+        for (word, lines) in self
+            .index
+            .words
+            .range(prefix.clone()..)
+            .take_while(|(word, _)| word.starts_with(&prefix))
+            .take(limit)
+        {
+            // The first sense of the first entry
+            let Some(entry) = self
+                .entries(lines.get(..1).unwrap_or_default())
+                .await?
+                .pop()
+            else {
+                completions.push(Completion::new(word.clone(), None));
+                continue;
+            };
+            let gloss = entry
+                .senses
+                .first()
+                .and_then(|sense| sense.glosses.last())
+                .cloned();
+            completions.push(Completion::new(entry.word, gloss));
+        }
+
+        Ok(completions)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(self.index.words.keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSONL: &str = r#"{"word": "Dog", "lang": "English", "lang_code": "en", "pos": "noun", "etymology_text": "From Middle English dogge.", "sounds": [{"ipa": "/dɒɡ/", "tags": ["Received-Pronunciation"]}, {"audio": "dog.ogg"}], "forms": [{"form": "dogs", "tags": ["plural"]}], "senses": [{"glosses": ["A mammal."]}, {"glosses": ["A mammal.", "A male dog."], "tags": ["specifically"]}], "translations": [{"lang": "French", "code": "fr", "word": "chien"}, {"lang": "German", "code": "de", "word": "Hund"}]}
+{"word": "dog", "lang": "English", "lang_code": "en", "pos": "verb", "etymology_text": "From Middle English dogge.", "sounds": [{"ipa": "/dɒɡ/", "tags": ["Received-Pronunciation"]}], "forms": [{"form": "dogged", "tags": ["past"]}], "senses": [{"glosses": ["To follow."]}]}
+
+{"word": "dog", "lang": "Dutch", "lang_code": "nl", "pos": "noun", "senses": [{"glosses": ["mastiff"]}]}
+{"word": "cat", "lang": "English", "lang_code": "en", "pos": "noun", "senses": [{"glosses": ["A small feline."]}]}
+"#;
+
+    async fn write_jsonl(dir: &Path) -> PathBuf {
+        let path = dir.join("wiktionary.jsonl");
+        tokio::fs::write(&path, JSONL).await.unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_wiktionary_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_jsonl(dir.path()).await;
+
+        let dict = WiktionaryDictionary::init(&WiktionaryConfig {
+            path,
+            lang_code: Some("en".into()),
+            translation_langs: Some(vec!["fr".into()]),
+        })
+        .await
+        .unwrap();
+
+        assert!(dict.exits(&"dog".into()).await.unwrap());
+        assert!(dict.exits(&"cat".into()).await.unwrap());
+        assert!(!dict.exits(&"cow".into()).await.unwrap());
+
+        assert_eq!(
+            dict.lookup_ditail(&"dog".into()).await.unwrap().unwrap(),
+            "# Dog
+
+## Etymology
+
+From Middle English dogge.
+
+## Pronunciation
+
+- /dɒɡ/ (Received-Pronunciation)
+
+## Noun (English)
+
+1. A mammal.
+2. A male dog. (specifically)
+
+Inflections: dogs (plural)
+
+## Verb (English)
+
+1. To follow.
+
+Inflections: dogged (past)
+
+## Translations
+
+- French: chien
+"
+        );
+
+        assert_eq!(dict.lookup_ditail(&"cow".into()).await.unwrap(), None);
+
+        assert_eq!(
+            dict.prefix_search(&"d".into(), 10).await.unwrap(),
+            vec![Completion::new("Dog", Some("A mammal.".into()))]
+        );
+        assert_eq!(dict.prefix_search(&"".into(), 10).await.unwrap().len(), 2);
+        assert_eq!(dict.headwords().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wiktionary_all_languages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_jsonl(dir.path()).await;
+
+        let dict = WiktionaryDictionary::init(&WiktionaryConfig {
+            path,
+            lang_code: None,
+            translation_langs: None,
+        })
+        .await
+        .unwrap();
+
+        let doc = dict.lookup_ditail(&"dog".into()).await.unwrap().unwrap();
+        assert!(doc.contains("## Noun (Dutch)\n\n1. mastiff\n"));
+        assert!(doc.contains("- German: Hund\n"));
+    }
+
+    #[tokio::test]
+    async fn test_wiktionary_index_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_jsonl(dir.path()).await;
+        let config = WiktionaryConfig {
+            path: path.clone(),
+            lang_code: Some("en".into()),
+            translation_langs: None,
+        };
+
+        WiktionaryDictionary::init(&config).await.unwrap();
+
+        let index_path = index::index_path(&path);
+        let index = tokio::fs::read_to_string(&index_path).await.unwrap();
+        assert!(index.lines().any(|line| line.starts_with("cat\t")));
+
+        // The persisted index is used
+        let header = index.lines().next().unwrap();
+        tokio::fs::write(&index_path, format!("{header}\nonly\t0\t10\n"))
+            .await
+            .unwrap();
+        let dict = WiktionaryDictionary::init(&config).await.unwrap();
+        assert!(dict.exits(&"only".into()).await.unwrap());
+        assert!(!dict.exits(&"cat".into()).await.unwrap());
+
+        // The index is rebuilt for another language
+        let dict = WiktionaryDictionary::init(&WiktionaryConfig {
+            lang_code: Some("nl".into()),
+            ..config
+        })
+        .await
+        .unwrap();
+        assert!(dict.exits(&"dog".into()).await.unwrap());
+        assert!(!dict.exits(&"only".into()).await.unwrap());
+    }
+}
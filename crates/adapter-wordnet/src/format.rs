@@ -0,0 +1,265 @@
+//! Parsers of the WordNet database files(`index.*`, `data.*` and `*.exc`)
+
+use crate::WordnetError;
+
+/// The syntactic categories of WordNet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Pos {
+    Noun,
+    Verb,
+    Adj,
+    Adv,
+}
+
+impl Pos {
+    pub(crate) const ALL: [Pos; 4] = [Pos::Noun, Pos::Verb, Pos::Adj, Pos::Adv];
+
+    /// The suffix of the file names
+    pub(crate) fn file_suffix(self) -> &'static str {
+        match self {
+            Pos::Noun => "noun",
+            Pos::Verb => "verb",
+            Pos::Adj => "adj",
+            Pos::Adv => "adv",
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Pos::Noun => "Noun",
+            Pos::Verb => "Verb",
+            Pos::Adj => "Adjective",
+            Pos::Adv => "Adverb",
+        }
+    }
+
+    /// From `ss_type` or `pos` of the files. Adjective satellites are adjectives
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "n" => Some(Pos::Noun),
+            "v" => Some(Pos::Verb),
+            "a" | "s" => Some(Pos::Adj),
+            "r" => Some(Pos::Adv),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the line is a part of the license header
+fn is_header(line: &str) -> bool {
+    line.starts_with("  ")
+}
+
+/// Parse `index.*` into the lemmas and the offsets of their synsets
+pub(crate) fn parse_index(s: &str) -> Result<Vec<(String, Vec<u64>)>, WordnetError> {
+    let mut lemmas = vec![];
+
+    for line in s
+        .lines()
+        .filter(|line| !is_header(line) && !line.is_empty())
+    {
+        let invalid = || WordnetError::InvalidData(format!("Invalid index line: {line}"));
+        let mut fields = line.split_ascii_whitespace();
+
+        let lemma = fields.next().ok_or_else(invalid)?;
+        let _pos = fields.next().ok_or_else(invalid)?;
+        let synset_cnt: usize = parse_next(&mut fields).ok_or_else(invalid)?;
+        let p_cnt: usize = parse_next(&mut fields).ok_or_else(invalid)?;
+        // The pointer symbols, sense_cnt and tagsense_cnt
+        let mut fields = fields.skip(p_cnt + 2);
+
+        let offsets = (0..synset_cnt)
+            .map(|_| parse_next(&mut fields).ok_or_else(invalid))
+            .collect::<Result<Vec<u64>, _>>()?;
+
+        lemmas.push((lemma.to_string(), offsets));
+    }
+
+    Ok(lemmas)
+}
+
+/// Parse `*.exc` into the inflected forms and their base forms
+pub(crate) fn parse_exc(s: &str) -> Vec<(String, Vec<String>)> {
+    s.lines()
+        .filter_map(|line| {
+            let mut words = line.split_ascii_whitespace();
+            let form = words.next()?;
+            let bases: Vec<String> = words.map(str::to_string).collect();
+            (!bases.is_empty()).then(|| (form.to_string(), bases))
+        })
+        .collect()
+}
+
+/// A pointer from a synset(or a word of it) to another one
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Pointer {
+    pub(crate) symbol: String,
+    pub(crate) offset: u64,
+    pub(crate) pos: Pos,
+    /// The 1-based index of the word in the synset. 0 if the pointer is semantic
+    pub(crate) source: usize,
+    /// The 1-based index of the word in the target synset. 0 if the pointer is semantic
+    pub(crate) target: usize,
+}
+
+/// A synset of `data.*`
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Synset {
+    pub(crate) pos: Pos,
+    /// The words, with spaces instead of underscores
+    pub(crate) words: Vec<String>,
+    pub(crate) pointers: Vec<Pointer>,
+    pub(crate) gloss: String,
+}
+
+/// Parse a line of `data.*`
+pub(crate) fn parse_synset(line: &str) -> Result<Synset, WordnetError> {
+    let invalid = || WordnetError::InvalidData(format!("Invalid data line: {line}"));
+
+    let (data, gloss) = line.split_once(" | ").unwrap_or((line, ""));
+    let mut fields = data.split_ascii_whitespace();
+
+    let _offset = fields.next().ok_or_else(invalid)?;
+    let _lex_filenum = fields.next().ok_or_else(invalid)?;
+    let pos = fields
+        .next()
+        .and_then(Pos::from_symbol)
+        .ok_or_else(invalid)?;
+
+    let w_cnt = fields
+        .next()
+        .and_then(|w_cnt| usize::from_str_radix(w_cnt, 16).ok())
+        .ok_or_else(invalid)?;
+    let words = (0..w_cnt)
+        .map(|_| {
+            let word = fields.next().ok_or_else(invalid)?;
+            let _lex_id = fields.next().ok_or_else(invalid)?;
+            Ok(strip_marker(word).replace('_', " "))
+        })
+        .collect::<Result<Vec<_>, WordnetError>>()?;
+
+    let p_cnt: usize = parse_next(&mut fields).ok_or_else(invalid)?;
+    let pointers = (0..p_cnt)
+        .map(|_| {
+            let symbol = fields.next().ok_or_else(invalid)?;
+            let offset = parse_next(&mut fields).ok_or_else(invalid)?;
+            let pos = fields
+                .next()
+                .and_then(Pos::from_symbol)
+                .ok_or_else(invalid)?;
+            let source_target = fields.next().filter(|s| s.len() == 4).ok_or_else(invalid)?;
+            let source = usize::from_str_radix(&source_target[..2], 16).map_err(|_| invalid())?;
+            let target = usize::from_str_radix(&source_target[2..], 16).map_err(|_| invalid())?;
+
+            Ok(Pointer {
+                symbol: symbol.to_string(),
+                offset,
+                pos,
+                source,
+                target,
+            })
+        })
+        .collect::<Result<Vec<_>, WordnetError>>()?;
+
+    Ok(Synset {
+        pos,
+        words,
+        pointers,
+        gloss: gloss.trim().to_string(),
+    })
+}
+
+/// Remove the syntactic marker of adjectives like `(a)`, `(p)` and `(ip)`
+fn strip_marker(word: &str) -> &str {
+    match word.strip_suffix(')').and_then(|w| w.rsplit_once('(')) {
+        Some((word, "a" | "p" | "ip")) => word,
+        _ => word,
+    }
+}
+
+fn parse_next<'a, T: std::str::FromStr>(fields: &mut impl Iterator<Item = &'a str>) -> Option<T> {
+    fields.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_index() {
+        let index = "  1 This software and database is being provided\n\
+                     dog n 3 4 @ ~ #m %p 3 1 02084071 10114209 03907626  \n\
+                     hot_dog n 1 1 @ 1 0 07697537  \n";
+
+        assert_eq!(
+            parse_index(index).unwrap(),
+            [
+                ("dog".into(), vec![2084071, 10114209, 3907626]),
+                ("hot_dog".into(), vec![7697537]),
+            ]
+        );
+
+        assert!(parse_index("dog n 3").is_err());
+    }
+
+    #[test]
+    fn test_parse_exc() {
+        assert_eq!(
+            parse_exc("mice mouse\nbetter good well\nalone\n"),
+            [
+                ("mice".into(), vec!["mouse".into()]),
+                ("better".into(), vec!["good".into(), "well".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_synset() {
+        let synset = parse_synset(
+            "00001740 00 a 01 able(p) 0 002 = 05207437 n 0000 ! 00002098 a 0101 | (usually followed by `to') having the necessary means  ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            synset,
+            Synset {
+                pos: Pos::Adj,
+                words: vec!["able".into()],
+                pointers: vec![
+                    Pointer {
+                        symbol: "=".into(),
+                        offset: 5207437,
+                        pos: Pos::Noun,
+                        source: 0,
+                        target: 0,
+                    },
+                    Pointer {
+                        symbol: "!".into(),
+                        offset: 2098,
+                        pos: Pos::Adj,
+                        source: 1,
+                        target: 1,
+                    },
+                ],
+                gloss: "(usually followed by `to') having the necessary means".into(),
+            }
+        );
+
+        // Verbs have the frames after the pointers
+        let synset = parse_synset(
+            "01166351 34 v 02 eat 0 feed 1 001 @ 01157517 v 0000 01 + 08 00 | take in food",
+        )
+        .unwrap();
+        assert_eq!(synset.words, ["eat", "feed"]);
+        assert_eq!(synset.gloss, "take in food");
+
+        assert!(parse_synset("00001740 00 x 01 able 0 000 | gloss").is_err());
+    }
+
+    #[test]
+    fn test_strip_marker() {
+        assert_eq!(strip_marker("able(p)"), "able");
+        assert_eq!(strip_marker("elect(ip)"), "elect");
+        assert_eq!(strip_marker("A(b)"), "A(b)");
+    }
+}
@@ -0,0 +1,458 @@
+//! WordNet adapter
+//! Reads the database files(`index.*`, `data.*` and `*.exc`) of Princeton WordNet or Open English WordNet.
+
+mod format;
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use etymora_traits::{fold_case, Completion, Dictionary, Word};
+use format::{Pos, Synset};
+use rustc_hash::FxHashMap;
+use thiserror::Error;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WordnetConfig {
+    /// The directory which has the database files like `index.noun` and `data.noun`
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Error)]
+pub enum WordnetError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Not found the file: {0}")]
+    NotFound(PathBuf),
+    #[error("Invalid WordNet database: {0}")]
+    InvalidData(String),
+}
+
+/// The pointer symbols of the relations shown
+const ANTONYM: &str = "!";
+const HYPERNYMS: &[&str] = &["@", "@i"];
+
+#[derive(Debug)]
+pub struct WordnetDictionary {
+    /// Case folded lemmas to the offsets of their synsets for each part of speech. Sorted for `prefix_search`
+    index: BTreeMap<String, Vec<(Pos, Vec<u64>)>>,
+    /// Case folded inflected forms to their base forms from the exception lists
+    exceptions: FxHashMap<String, Vec<(Pos, String)>>,
+    data: FxHashMap<Pos, Vec<u8>>,
+}
+
+/// The key of a lemma in the files, which has underscores instead of spaces
+fn key(lemma: &str) -> String {
+    fold_case(&lemma.replace('_', " "))
+}
+
+async fn read(path: &Path) -> Result<Vec<u8>, WordnetError> {
+    if !tokio::fs::try_exists(path).await.unwrap_or(false) {
+        return Err(WordnetError::NotFound(path.to_path_buf()));
+    }
+
+    tokio::fs::read(path)
+        .await
+        .map_err(|source| WordnetError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+impl WordnetDictionary {
+    fn offsets(&self, lemma: &str, pos: Pos) -> Option<&[u64]> {
+        self.index
+            .get(lemma)?
+            .iter()
+            .find(|(p, _)| *p == pos)
+            .map(|(_, offsets)| &offsets[..])
+    }
+
+    fn synset(&self, pos: Pos, offset: u64) -> Result<Synset, WordnetError> {
+        let invalid = || WordnetError::InvalidData(format!("Invalid offset: {offset}"));
+
+        let data = self.data.get(&pos).ok_or_else(invalid)?;
+        let line = data.get(offset as usize..).ok_or_else(invalid)?;
+        let line = line.split(|b| *b == b'\n').next().unwrap_or_default();
+
+        format::parse_synset(&String::from_utf8_lossy(line))
+    }
+
+    /// The lemmas of the word in the part of speech, through the exception list
+    fn lemmas(&self, key: &str, pos: Pos) -> Vec<String> {
+        let mut lemmas = vec![];
+        if self.offsets(key, pos).is_some() {
+            lemmas.push(key.to_string());
+        }
+
+        for (_, base) in self
+            .exceptions
+            .get(key)
+            .into_iter()
+            .flatten()
+            .filter(|(p, _)| *p == pos)
+        {
+            let base = self::key(base);
+            if self.offsets(&base, pos).is_some() && !lemmas.contains(&base) {
+                lemmas.push(base);
+            }
+        }
+
+        lemmas
+    }
+
+    fn render_synset(&self, lemma: &str, synset: &Synset) -> Result<String, WordnetError> {
+        let mut doc = synset.gloss.clone();
+
+        let synonyms: Vec<&str> = synset
+            .words
+            .iter()
+            .filter(|word| fold_case(word) != lemma)
+            .map(String::as_str)
+            .collect();
+        if !synonyms.is_empty() {
+            doc.push_str(&format!("\n   - Synonyms: {}", synonyms.join(", ")));
+        }
+
+        let mut hypernyms = vec![];
+        let mut antonyms = vec![];
+        for pointer in &synset.pointers {
+            let is_hypernym = HYPERNYMS.contains(&pointer.symbol.as_str());
+            let is_antonym = pointer.symbol == ANTONYM
+                && (pointer.source == 0
+                    || synset
+                        .words
+                        .get(pointer.source - 1)
+                        .is_some_and(|word| fold_case(word) == lemma));
+            if !is_hypernym && !is_antonym {
+                continue;
+            }
+
+            let target = self.synset(pointer.pos, pointer.offset)?;
+            let words = match target.words.get(pointer.target.wrapping_sub(1)) {
+                Some(word) => word.clone(),
+                None => target.words.join(", "),
+            };
+
+            if is_hypernym {
+                hypernyms.push(words);
+            } else {
+                antonyms.push(words);
+            }
+        }
+
+        if !hypernyms.is_empty() {
+            doc.push_str(&format!("\n   - Hypernyms: {}", hypernyms.join("; ")));
+        }
+        if !antonyms.is_empty() {
+            doc.push_str(&format!("\n   - Antonyms: {}", antonyms.join(", ")));
+        }
+
+        Ok(doc)
+    }
+}
+
+impl Dictionary for WordnetDictionary {
+    type Error = WordnetError;
+    type InitInput = WordnetConfig;
+
+    async fn init(input: &Self::InitInput) -> Result<Self, Self::Error> {
+        let mut index: BTreeMap<String, Vec<(Pos, Vec<u64>)>> = BTreeMap::new();
+        let mut exceptions: FxHashMap<String, Vec<(Pos, String)>> = FxHashMap::default();
+        let mut data = FxHashMap::default();
+
+        for pos in Pos::ALL {
+            let suffix = pos.file_suffix();
+
+            let index_file = read(&input.path.join(format!("index.{suffix}"))).await?;
+            for (lemma, offsets) in format::parse_index(&String::from_utf8_lossy(&index_file))? {
+                index.entry(key(&lemma)).or_default().push((pos, offsets));
+            }
+
+            data.insert(pos, read(&input.path.join(format!("data.{suffix}"))).await?);
+
+            // The exception lists are optional
+            let exc_path = input.path.join(format!("{suffix}.exc"));
+            if tokio::fs::try_exists(&exc_path).await.unwrap_or(false) {
+                for (form, bases) in
+                    format::parse_exc(&String::from_utf8_lossy(&read(&exc_path).await?))
+                {
+                    let forms = exceptions.entry(key(&form)).or_default();
+                    forms.extend(bases.into_iter().map(|base| (pos, base)));
+                }
+            }
+        }
+
+        tracing::info!(
+            "Loaded WordNet in {} with {} lemmas",
+            input.path.display(),
+            index.len()
+        );
+
+        Ok(WordnetDictionary {
+            index,
+            exceptions,
+            data,
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn exits(&self, word: &Word) -> Result<bool, Self::Error> {
+        let key = fold_case(word.as_str());
+        Ok(Pos::ALL
+            .into_iter()
+            .any(|pos| !self.lemmas(&key, pos).is_empty()))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn lookup_ditail(&self, word: &Word) -> Result<Option<String>, Self::Error> {
+        let key = fold_case(word.as_str());
+
+        let mut sections = vec![];
+        for pos in Pos::ALL {
+            for lemma in self.lemmas(&key, pos) {
+                let mut senses = vec![];
+                for (i, offset) in self
+                    .offsets(&lemma, pos)
+                    .unwrap_or_default()
+                    .iter()
+                    .enumerate()
+                {
+                    let synset = self.synset(pos, *offset)?;
+                    senses.push(format!(
+                        "{}. {}",
+                        i + 1,
+                        self.render_synset(&lemma, &synset)?
+                    ));
+                }
+
+                let heading = if lemma == key {
+                    pos.name().to_string()
+                } else {
+                    format!("{} ({lemma})", pos.name())
+                };
+                sections.push(format!("## {heading}\n\n{}\n", senses.join("\n")));
+            }
+        }
+
+        if sections.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(format!(
+            "# {}\n\n{}",
+            word.as_str(),
+            sections.join("\n")
+        )))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn prefix_search(
+        &self,
+        prefix: &Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        let prefix = fold_case(prefix.as_str());
+
+        let mut completions = vec![];
+        for (lemma, synsets) in self
+            .index
+            .range(prefix.clone()..)
+            .take_while(|(lemma, _)| lemma.starts_with(&prefix))
+            .take(limit)
+        {
+            // The definition of the first sense without the examples
+            let gloss = match synsets.first() {
+                Some((pos, offsets)) if !offsets.is_empty() => {
+                    let synset = self.synset(*pos, offsets[0])?;
+                    let definition = synset.gloss.split("; \"").next().unwrap_or_default();
+                    Some(definition.trim().to_string())
+                }
+                _ => None,
+            };
+            completions.push(Completion::new(lemma.clone(), gloss));
+        }
+
+        Ok(completions)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(self.index.keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build `data.*` from the lines at their offsets, and `index.*` for the lemmas.
+    /// The gaps between the lines are filled with the lines like the license header
+    fn write_pos(dir: &Path, suffix: &str, lines: &[&str], index: &str) {
+        let mut data = String::new();
+        for line in lines {
+            let offset: usize = line[..8].parse().unwrap();
+            assert!(data.len() + 3 <= offset, "The offset of {line}");
+            data.push_str(&format!("{:1$}\n", "", offset - data.len() - 1));
+            data.push_str(line);
+            data.push('\n');
+        }
+
+        std::fs::write(dir.join(format!("data.{suffix}")), data).unwrap();
+        std::fs::write(dir.join(format!("index.{suffix}")), index).unwrap();
+    }
+
+    fn write_wordnet(dir: &Path) {
+        write_pos(
+            dir,
+            "noun",
+            &[
+                "00000100 05 n 01 rodent 0 000 | relatively small placental mammals",
+                "00000300 05 n 02 mouse 0 Mus_musculus 0 001 @ 00000100 n 0000 | any of numerous small rodents",
+                "00000500 06 n 02 mouse 0 computer_mouse 0 000 | a hand-operated electronic device",
+            ],
+            "mouse n 2 1 @ 2 1 00000300 00000500  \nrodent n 1 0 1 0 00000100  \nmus_musculus n 1 1 @ 1 0 00000300  \ncomputer_mouse n 1 0 1 0 00000500  \n",
+        );
+        write_pos(
+            dir,
+            "verb",
+            &["00000100 35 v 01 mouse 0 000 01 + 02 00 | to go stealthily"],
+            "mouse v 1 0 1 0 00000100  \n",
+        );
+        write_pos(
+            dir,
+            "adj",
+            &[
+                "00000100 00 a 01 good 0 001 ! 00000300 a 0101 | having desirable qualities",
+                "00000300 00 a 01 bad 0 001 ! 00000100 a 0101 | having undesirable qualities",
+            ],
+            "good a 1 1 ! 1 0 00000100  \nbad a 1 1 ! 1 0 00000300  \n",
+        );
+        write_pos(dir, "adv", &[], "");
+
+        std::fs::write(dir.join("noun.exc"), "mice mouse\n").unwrap();
+        std::fs::write(dir.join("adj.exc"), "better good\n").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wordnet_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wordnet(dir.path());
+
+        let dict = WordnetDictionary::init(&WordnetConfig {
+            path: dir.path().to_path_buf(),
+        })
+        .await
+        .unwrap();
+
+        assert!(dict.exits(&"mouse".into()).await.unwrap());
+        assert!(dict.exits(&"computer mouse".into()).await.unwrap());
+        assert!(!dict.exits(&"cat".into()).await.unwrap());
+
+        assert_eq!(
+            dict.lookup_ditail(&"Mouse".into()).await.unwrap().unwrap(),
+            "# Mouse
+
+## Noun
+
+1. any of numerous small rodents
+   - Synonyms: Mus musculus
+   - Hypernyms: rodent
+2. a hand-operated electronic device
+   - Synonyms: computer mouse
+
+## Verb
+
+1. to go stealthily
+"
+        );
+
+        assert_eq!(
+            dict.lookup_ditail(&"good".into()).await.unwrap().unwrap(),
+            "# good\n\n## Adjective\n\n1. having desirable qualities\n   - Antonyms: bad\n"
+        );
+
+        assert_eq!(dict.lookup_ditail(&"cat".into()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_wordnet_prefix_search() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wordnet(dir.path());
+
+        let dict = WordnetDictionary::init(&WordnetConfig {
+            path: dir.path().to_path_buf(),
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            dict.prefix_search(&"Mo".into(), 10).await.unwrap(),
+            vec![Completion::new(
+                "mouse",
+                Some("any of numerous small rodents".into())
+            )]
+        );
+        assert_eq!(
+            dict.prefix_search(&"".into(), 2)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|completion| completion.word)
+                .collect::<Vec<_>>(),
+            vec!["bad", "computer mouse"]
+        );
+
+        assert!(dict
+            .headwords()
+            .await
+            .unwrap()
+            .contains(&"mouse".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_wordnet_exceptions() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wordnet(dir.path());
+
+        let dict = WordnetDictionary::init(&WordnetConfig {
+            path: dir.path().to_path_buf(),
+        })
+        .await
+        .unwrap();
+
+        assert!(dict.exits(&"mice".into()).await.unwrap());
+        assert!(dict
+            .lookup_ditail(&"mice".into())
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("# mice\n\n## Noun (mouse)\n\n1. any of numerous small rodents\n"));
+        assert!(dict
+            .lookup_ditail(&"better".into())
+            .await
+            .unwrap()
+            .unwrap()
+            .contains("## Adjective (good)\n"));
+    }
+
+    #[tokio::test]
+    async fn test_wordnet_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wordnet(dir.path());
+        std::fs::remove_file(dir.path().join("data.verb")).unwrap();
+
+        assert!(matches!(
+            WordnetDictionary::init(&WordnetConfig {
+                path: dir.path().to_path_buf(),
+            })
+            .await,
+            Err(WordnetError::NotFound(_))
+        ));
+    }
+}
@@ -5,6 +5,16 @@ impl Word {
     pub fn inner(self) -> String {
         self.0
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Fold the case with the full Unicode case folding.
+/// The server and the adapters share it, so the words folded by them agree
+pub fn fold_case(word: &str) -> String {
+    caseless::default_case_fold_str(word)
 }
 
 impl<T: Into<String>> From<T> for Word {
@@ -20,6 +30,41 @@ impl std::fmt::Display for Word {
     }
 }
 
+/// A word with its range in the text document
+#[derive(Debug, PartialEq, Eq)]
+pub struct WordSpan {
+    pub word: Word,
+    pub range: lsp_types::Range,
+}
+
+impl WordSpan {
+    pub fn new(word: impl Into<Word>, range: lsp_types::Range) -> Self {
+        WordSpan {
+            word: word.into(),
+            range,
+        }
+    }
+}
+
+/// A word which starts with the prefix, found by `Dictionary::prefix_search`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Completion {
+    /// The headword as it is in the dictionary
+    pub word: String,
+    /// A short description of the word, like the first definition
+    #[serde(default)]
+    pub gloss: Option<String>,
+}
+
+impl Completion {
+    pub fn new(word: impl Into<String>, gloss: Option<String>) -> Self {
+        Completion {
+            word: word.into(),
+            gloss,
+        }
+    }
+}
+
 pub trait Dictionary: Sized {
     type Error;
     type InitInput: serde::Serialize;
@@ -37,15 +82,41 @@ pub trait Dictionary: Sized {
         &self,
         word: &Word,
     ) -> impl std::future::Future<Output = Result<Option<String>, Self::Error>> + Send;
+
+    /// The words which start with the prefix(case insensitive), up to `limit`.
+    /// Dictionaries which can't list their words find nothing
+    fn prefix_search(
+        &self,
+        _prefix: &Word,
+        _limit: usize,
+    ) -> impl std::future::Future<Output = Result<Vec<Completion>, Self::Error>> + Send {
+        async { Ok(vec![]) }
+    }
+
+    /// All the headwords(case folded), to suggest the words close to unknown ones.
+    /// Dictionaries which can't list their words have none
+    fn headwords(
+        &self,
+    ) -> impl std::future::Future<Output = Result<Vec<String>, Self::Error>> + Send {
+        async { Ok(vec![]) }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Word;
+    use super::{fold_case, Word};
 
     #[test]
     fn test_word() {
         let word: Word = "  lang\n\r".into();
         assert_eq!(word.inner(), "lang".to_string());
     }
+
+    #[test]
+    fn test_fold_case() {
+        assert_eq!(fold_case("Straße"), "strasse");
+        assert_eq!(fold_case("NAÏVE"), "naïve");
+        assert_eq!(fold_case("ΟΔΟΣ"), "οδοσ");
+        assert_eq!(fold_case("ПРИВЕТ"), "привет");
+    }
 }
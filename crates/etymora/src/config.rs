@@ -0,0 +1,386 @@
+//! Config
+//! The config is merged from the layers below, and each top-level key replaces the one in the lower layers.
+//! 1. The user config file, `$XDG_CONFIG_HOME/etymora/etymora.toml`(`~/.config/etymora/etymora.toml`)
+//! 2. `initializationOptions` of the client
+//! 3. The project config file, `etymora.toml` found first walking up from the document
+//! 4. The settings of the client by `workspace/configuration` or `workspace/didChangeConfiguration`
+//!
+//! The config files have the same keys as `initializationOptions`.
+//! The project config files can't use the dictionaries running code(`external` and `wasm`),
+//! unless the user config file or `initializationOptions` lists the project in `trusted_projects`.
+//! Relative paths in the project config files are relative to the files.
+
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::{
+    detect::DetectConfig,
+    diagnostics::SpellCheckConfig,
+    dict_handler,
+    error::{EtymoraError, Result},
+    routing::Route,
+    text_document::SegmentConfig,
+};
+
+/// The name of the config files
+pub(crate) const CONFIG_FILE: &str = "etymora.toml";
+
+/// The adapters which run code, allowed only in the project config files of the trusted projects
+const UNTRUSTED_ADAPTERS: &[&str] = &["external", "wasm"];
+
+#[derive(Debug, serde::Deserialize, Default)]
+pub(crate) struct Config {
+    /// A dictionary, or the list of them in priority order
+    #[serde(default)]
+    pub(crate) dict_config: dict_handler::DictChainConfig,
+    #[serde(default)]
+    pub(crate) lookup_mode: dict_handler::LookupMode,
+    #[serde(default)]
+    pub(crate) segment: SegmentConfig,
+    #[serde(default)]
+    pub(crate) spell_check: SpellCheckConfig,
+    /// The dictionaries for the documents by their `languageId`s and paths. The first matching route is used
+    #[serde(default)]
+    pub(crate) routes: Vec<Route>,
+    /// Guess the language of the sentence around the hovered word
+    #[serde(default)]
+    pub(crate) detect_language: DetectConfig,
+}
+
+impl Config {
+    /// Parse the options. Empty options are the default
+    pub(crate) fn parse(options: Value) -> Result<Config> {
+        if options.is_null() {
+            Ok(Config::default())
+        } else {
+            serde_json::from_value(options).map_err(EtymoraError::Desirialize)
+        }
+    }
+}
+
+/// Merge the upper layer into the options.
+/// Each key replaces the one in the options, and `null` keeps the options
+pub(crate) fn merge_settings(options: &mut Value, settings: Value) {
+    match (options, settings) {
+        (_, Value::Null) => {}
+        (Value::Object(options), Value::Object(settings)) => options.extend(settings),
+        (options, settings) => *options = settings,
+    }
+}
+
+/// The layers of the config except the project config file, which depends on the document
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Layers {
+    pub(crate) user: Value,
+    pub(crate) init_options: Value,
+    pub(crate) settings: Value,
+}
+
+impl Layers {
+    /// Merge the layers with the options of the project config file
+    pub(crate) fn merge(&self, project: &Value) -> Value {
+        let mut options = Value::Null;
+        for layer in [&self.user, &self.init_options, project, &self.settings] {
+            merge_settings(&mut options, layer.clone());
+        }
+        options
+    }
+
+    /// Whether the project config file in the dir can run code.
+    /// Only the user config file and `initializationOptions` can trust the projects by `trusted_projects`
+    pub(crate) fn trusts(&self, dir: &Path) -> bool {
+        let mut options = Value::Null;
+        for layer in [&self.user, &self.init_options] {
+            merge_settings(&mut options, layer.clone());
+        }
+
+        options
+            .get("trusted_projects")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(Path::new)
+            .any(|trusted| trusted.is_absolute() && dir.starts_with(trusted))
+    }
+}
+
+/// The path of the user config file in the XDG config dir
+pub(crate) fn user_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        // Relative paths are ignored by the spec
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("etymora").join(CONFIG_FILE))
+}
+
+/// Find the project config file in the dir or its ancestors
+pub(crate) fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Read the config file as options. The options of a missing file are `null`
+pub(crate) fn read_config_file(path: &Path) -> Result<Value> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Value::Null),
+        Err(e) => {
+            return Err(EtymoraError::ConfigFileRead {
+                path: path.to_path_buf(),
+                source: e,
+            })
+        }
+    };
+
+    toml::from_str(&text).map_err(|e| EtymoraError::ConfigFileParse {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+/// The keys of the paths in the configs of the dictionaries
+const PATH_KEYS: &[&str] = &["path", "data_dir"];
+
+/// Resolve the relative paths of the dictionaries and the segmenter against the dir
+fn resolve_paths(options: &mut Value, dir: &Path) {
+    let resolve = |value: &mut Value| {
+        if let Some(path) = value
+            .as_str()
+            .map(Path::new)
+            .filter(|path| path.is_relative())
+        {
+            *value = Value::String(dir.join(path).display().to_string());
+        }
+    };
+
+    if let Some(dictionary) = options
+        .get_mut("segment")
+        .and_then(|segment| segment.get_mut("japanese_dictionary"))
+    {
+        resolve(dictionary);
+    }
+
+    let dicts: Vec<&mut Value> = match options.get_mut("dict_config") {
+        Some(Value::Array(entries)) => entries
+            .iter_mut()
+            .filter_map(|entry| entry.get_mut("dict"))
+            .collect(),
+        Some(dict) => vec![dict],
+        None => vec![],
+    };
+    // { "sqlite": { "path": "glossary.sqlite" } }
+    for config in dicts
+        .into_iter()
+        .filter_map(Value::as_object_mut)
+        .flat_map(|dict| dict.values_mut())
+        .filter_map(Value::as_object_mut)
+    {
+        for key in PATH_KEYS {
+            if let Some(path) = config.get_mut(*key) {
+                resolve(path);
+            }
+        }
+    }
+}
+
+/// Read the project config file, resolving the relative paths against its dir.
+/// The dictionaries running code are rejected unless the project is trusted
+pub(crate) fn read_project_config(path: &Path, trusted: bool) -> Result<Value> {
+    let mut options = read_config_file(path)?;
+    if let Some(dir) = path.parent() {
+        resolve_paths(&mut options, dir);
+    }
+    if trusted {
+        return Ok(options);
+    }
+
+    let dicts: Vec<&Value> = match options.get("dict_config") {
+        Some(Value::Array(entries)) => entries
+            .iter()
+            .filter_map(|entry| entry.get("dict"))
+            .collect(),
+        Some(dict) => vec![dict],
+        None => vec![],
+    };
+    if let Some(adapter) = dicts
+        .iter()
+        .filter_map(|dict| dict.as_object())
+        .flat_map(|dict| dict.keys())
+        .find(|adapter| UNTRUSTED_ADAPTERS.contains(&adapter.as_str()))
+    {
+        return Err(EtymoraError::UntrustedProject {
+            path: path.to_path_buf(),
+            adapter: adapter.clone(),
+        });
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_settings() {
+        let mut options = serde_json::json!({ "dict_config": "example", "lookup_mode": "merge" });
+
+        merge_settings(&mut options, Value::Null);
+        assert_eq!(options["dict_config"], "example");
+
+        merge_settings(
+            &mut options,
+            serde_json::json!({ "dict_config": { "wordnet": { "path": "/wn" } } }),
+        );
+        assert_eq!(
+            options,
+            serde_json::json!({ "dict_config": { "wordnet": { "path": "/wn" } }, "lookup_mode": "merge" })
+        );
+
+        let config = Config::parse(options).unwrap();
+        assert_eq!(config.lookup_mode, dict_handler::LookupMode::Merge);
+        assert!(Config::parse(Value::Null).is_ok());
+        assert!(Config::parse(serde_json::json!({ "lookup_mode": "all" })).is_err());
+    }
+
+    #[test]
+    fn test_layers() {
+        let layers = Layers {
+            user: serde_json::json!({ "dict_config": "example", "lookup_mode": "merge" }),
+            init_options: serde_json::json!({ "lookup_mode": "first", "segment": { "hyphen": "split" } }),
+            settings: serde_json::json!({ "spell_check": { "enabled": true } }),
+        };
+
+        let options = layers.merge(&serde_json::json!({ "segment": { "apostrophe": "join" } }));
+        assert_eq!(
+            options,
+            serde_json::json!({
+                "dict_config": "example",
+                "lookup_mode": "first",
+                "segment": { "apostrophe": "join" },
+                "spell_check": { "enabled": true },
+            })
+        );
+
+        assert_eq!(Layers::default().merge(&Value::Null), Value::Null);
+    }
+
+    #[test]
+    fn test_project_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("docs").join("guide");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(find_project_config(&nested), None);
+        assert_eq!(
+            read_config_file(&dir.path().join(CONFIG_FILE)).unwrap(),
+            Value::Null
+        );
+
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "lookup_mode = \"merge\"\n\n[spell_check]\nenabled = true\nignore = [\"etymora\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(find_project_config(&nested), Some(path.clone()));
+
+        let config = Config::parse(read_config_file(&path).unwrap()).unwrap();
+        assert_eq!(config.lookup_mode, dict_handler::LookupMode::Merge);
+        assert!(config.spell_check.enabled);
+        assert_eq!(config.spell_check.ignore, vec!["etymora"]);
+
+        std::fs::write(&path, "lookup_mode = ").unwrap();
+        assert!(matches!(
+            read_config_file(&path),
+            Err(EtymoraError::ConfigFileParse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_untrusted_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+
+        for text in [
+            "dict_config = { external = { command = \"sh\" } }\n",
+            "[[dict_config]]\ndict = \"example\"\n\n[[dict_config]]\ndict = { wasm = { path = \"adapter.wasm\" } }\n",
+        ] {
+            std::fs::write(&path, text).unwrap();
+            assert!(matches!(
+                read_project_config(&path, false),
+                Err(EtymoraError::UntrustedProject { .. })
+            ));
+            assert!(read_project_config(&path, true).is_ok());
+        }
+
+        std::fs::write(&path, "dict_config = { wordnet = { path = \"/wn\" } }\n").unwrap();
+        assert!(read_project_config(&path, false).is_ok());
+
+        let trusted = serde_json::json!({ "trusted_projects": [dir.path()] });
+        let layers = Layers {
+            user: trusted.clone(),
+            ..Default::default()
+        };
+        assert!(layers.trusts(&dir.path().join("docs")));
+        assert!(!layers.trusts(Path::new("/elsewhere")));
+        // Only by the user
+        let layers = Layers {
+            settings: trusted,
+            ..Default::default()
+        };
+        assert!(!layers.trusts(dir.path()));
+        assert!(!Layers::default().trusts(dir.path()));
+    }
+
+    #[test]
+    fn test_project_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "[[dict_config]]\ndict = { sqlite = { path = \"dicts/glossary.sqlite\" } }\n\n\
+             [[dict_config]]\ndict = { wordnet = { path = \"/usr/share/wordnet\" } }\n\n\
+             [segment]\njapanese_dictionary = \"ipadic\"\n",
+        )
+        .unwrap();
+
+        let options = read_project_config(&path, false).unwrap();
+        assert_eq!(
+            options["dict_config"][0]["dict"]["sqlite"]["path"],
+            dir.path()
+                .join("dicts/glossary.sqlite")
+                .display()
+                .to_string()
+        );
+        // Absolute paths are kept
+        assert_eq!(
+            options["dict_config"][1]["dict"]["wordnet"]["path"],
+            "/usr/share/wordnet"
+        );
+        assert_eq!(
+            options["segment"]["japanese_dictionary"],
+            dir.path().join("ipadic").display().to_string()
+        );
+
+        std::fs::write(&path, "dict_config = { wasm = { data_dir = \"data\" } }\n").unwrap();
+        assert_eq!(
+            read_project_config(&path, true).unwrap()["dict_config"]["wasm"]["data_dir"],
+            dir.path().join("data").display().to_string()
+        );
+    }
+
+    #[test]
+    fn test_config_example() {
+        let options: Value =
+            toml::from_str(include_str!("../../../config-examples/etymora.toml")).unwrap();
+
+        assert!(Config::parse(options).is_ok());
+    }
+}
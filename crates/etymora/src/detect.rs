@@ -0,0 +1,235 @@
+//! Language detection
+//! Guess the language of the sentence around the hovered word with the trigrams of the languages, offline.
+//! The language marker in the document takes precedence, and the language of the route is used if the result isn't confident.
+
+use serde::Deserialize;
+use whatlang::Lang;
+
+use crate::error::{EtymoraError, Result};
+
+/// The ISO 639-1 codes of the languages detected by whatlang, which uses ISO 639-3
+const ISO_639_1: &[(Lang, &str)] = &[
+    (Lang::Afr, "af"),
+    (Lang::Aka, "ak"),
+    (Lang::Amh, "am"),
+    (Lang::Ara, "ar"),
+    (Lang::Aze, "az"),
+    (Lang::Bel, "be"),
+    (Lang::Ben, "bn"),
+    (Lang::Bul, "bg"),
+    (Lang::Cat, "ca"),
+    (Lang::Ces, "cs"),
+    (Lang::Cmn, "zh"),
+    (Lang::Dan, "da"),
+    (Lang::Deu, "de"),
+    (Lang::Ell, "el"),
+    (Lang::Eng, "en"),
+    (Lang::Epo, "eo"),
+    (Lang::Est, "et"),
+    (Lang::Fin, "fi"),
+    (Lang::Fra, "fr"),
+    (Lang::Guj, "gu"),
+    (Lang::Heb, "he"),
+    (Lang::Hin, "hi"),
+    (Lang::Hrv, "hr"),
+    (Lang::Hun, "hu"),
+    (Lang::Hye, "hy"),
+    (Lang::Ind, "id"),
+    (Lang::Ita, "it"),
+    (Lang::Jav, "jv"),
+    (Lang::Jpn, "ja"),
+    (Lang::Kan, "kn"),
+    (Lang::Kat, "ka"),
+    (Lang::Khm, "km"),
+    (Lang::Kor, "ko"),
+    (Lang::Lat, "la"),
+    (Lang::Lav, "lv"),
+    (Lang::Lit, "lt"),
+    (Lang::Mal, "ml"),
+    (Lang::Mar, "mr"),
+    (Lang::Mkd, "mk"),
+    (Lang::Mya, "my"),
+    (Lang::Nep, "ne"),
+    (Lang::Nld, "nl"),
+    (Lang::Nob, "nb"),
+    (Lang::Ori, "or"),
+    (Lang::Pan, "pa"),
+    (Lang::Pes, "fa"),
+    (Lang::Pol, "pl"),
+    (Lang::Por, "pt"),
+    (Lang::Ron, "ro"),
+    (Lang::Rus, "ru"),
+    (Lang::Sin, "si"),
+    (Lang::Slk, "sk"),
+    (Lang::Slv, "sl"),
+    (Lang::Sna, "sn"),
+    (Lang::Spa, "es"),
+    (Lang::Srp, "sr"),
+    (Lang::Swe, "sv"),
+    (Lang::Tam, "ta"),
+    (Lang::Tel, "te"),
+    (Lang::Tgl, "tl"),
+    (Lang::Tha, "th"),
+    (Lang::Tuk, "tk"),
+    (Lang::Tur, "tr"),
+    (Lang::Ukr, "uk"),
+    (Lang::Urd, "ur"),
+    (Lang::Uzb, "uz"),
+    (Lang::Vie, "vi"),
+    (Lang::Yid, "yi"),
+    (Lang::Zul, "zu"),
+];
+
+fn default_min_confidence() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct DetectConfig {
+    pub(crate) enabled: bool,
+    /// The detected language is ignored below it(0.0 to 1.0)
+    pub(crate) min_confidence: f64,
+    /// The languages(ISO 639-1) to choose from. All the supported languages if it's empty
+    pub(crate) languages: Vec<String>,
+}
+
+impl Default for DetectConfig {
+    fn default() -> Self {
+        DetectConfig {
+            enabled: false,
+            min_confidence: default_min_confidence(),
+            languages: vec![],
+        }
+    }
+}
+
+/// The language detected in the text
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Detection {
+    /// ISO 639-1
+    pub(crate) code: &'static str,
+    /// The name in English like "German"
+    pub(crate) name: &'static str,
+    pub(crate) confidence: f64,
+}
+
+#[derive(Debug)]
+pub(crate) struct Detector {
+    config: DetectConfig,
+    detector: whatlang::Detector,
+}
+
+impl Detector {
+    pub(crate) fn new(config: DetectConfig) -> Result<Self> {
+        let detector = if config.languages.is_empty() {
+            whatlang::Detector::new()
+        } else {
+            let languages = config
+                .languages
+                .iter()
+                .map(|code| {
+                    ISO_639_1
+                        .iter()
+                        .find(|(_, c)| c == code)
+                        .map(|(lang, _)| *lang)
+                        .ok_or_else(|| EtymoraError::UnknownLanguage(code.clone()))
+                })
+                .collect::<Result<_>>()?;
+            whatlang::Detector::with_allowlist(languages)
+        };
+
+        Ok(Detector { config, detector })
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// The language of the text. `None` if it's not confident
+    pub(crate) fn detect(&self, text: &str) -> Option<Detection> {
+        let info = self.detector.detect(text)?;
+        if info.confidence() < self.config.min_confidence {
+            tracing::debug!(
+                "Not confident of {:?}({}) in {text:?}",
+                info.lang(),
+                info.confidence()
+            );
+            return None;
+        }
+
+        let code = ISO_639_1
+            .iter()
+            .find(|(lang, _)| *lang == info.lang())
+            .map(|(_, code)| *code)?;
+
+        Some(Detection {
+            code,
+            name: info.lang().eng_name(),
+            confidence: info.confidence(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iso_639_1() {
+        // Every language of whatlang has the code
+        for lang in Lang::all() {
+            assert!(ISO_639_1.iter().any(|(l, _)| l == lang), "{lang:?}");
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let detector = Detector::new(DetectConfig {
+            enabled: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let detection = detector
+            .detect("Das ist ein Wörterbuch für die deutsche Sprache.")
+            .unwrap();
+        assert_eq!(detection.code, "de");
+        assert_eq!(detection.name, "German");
+
+        assert_eq!(
+            detector
+                .detect("The quick brown fox jumps over the lazy dog, and then it runs away.")
+                .map(|detection| detection.code),
+            Some("en")
+        );
+
+        // Too short to be confident
+        assert_eq!(detector.detect("Hallo Welt"), None);
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let detector = Detector::new(DetectConfig {
+            enabled: true,
+            min_confidence: 0.0,
+            languages: vec!["en".to_string(), "fr".to_string()],
+        })
+        .unwrap();
+        assert_eq!(
+            detector
+                .detect("Das ist ein Wörterbuch für die deutsche Sprache.")
+                .map(|detection| detection.code)
+                .filter(|code| *code == "de"),
+            None
+        );
+
+        assert!(matches!(
+            Detector::new(DetectConfig {
+                languages: vec!["xx".to_string()],
+                ..Default::default()
+            }),
+            Err(EtymoraError::UnknownLanguage(_))
+        ));
+    }
+}
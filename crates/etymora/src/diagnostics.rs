@@ -0,0 +1,243 @@
+//! Spell checking
+//! Words which no dictionary knows are reported as diagnostics.
+//! Whether a word is known is cached per scope, so only the new words are looked up when the document changes.
+
+use std::{num::NonZeroUsize, sync::Mutex};
+
+use etymora_traits::{fold_case, Word, WordSpan};
+use lru::LruCache;
+use lsp_types::{Diagnostic, DiagnosticSeverity};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Deserialize;
+
+use crate::{
+    dict_handler::{DictChain, Scope},
+    error::Result,
+    lemmatizer,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+    #[default]
+    Information,
+    Hint,
+}
+
+impl From<Severity> for DiagnosticSeverity {
+    fn from(value: Severity) -> Self {
+        match value {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Information => DiagnosticSeverity::INFORMATION,
+            Severity::Hint => DiagnosticSeverity::HINT,
+        }
+    }
+}
+
+fn default_debounce() -> u64 {
+    500
+}
+
+/// The max number of the words cached per scope. The least recently checked ones are dropped
+const KNOWN_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct SpellCheckConfig {
+    pub(crate) enabled: bool,
+    pub(crate) severity: Severity,
+    /// Words never reported, case-insensitively
+    pub(crate) ignore: Vec<String>,
+    /// Milliseconds to wait after the last change before checking
+    pub(crate) debounce: u64,
+}
+
+impl Default for SpellCheckConfig {
+    fn default() -> Self {
+        SpellCheckConfig {
+            enabled: false,
+            severity: Severity::default(),
+            ignore: vec![],
+            debounce: default_debounce(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct SpellChecker {
+    config: SpellCheckConfig,
+    ignore: FxHashSet<String>,
+    /// Whether the words are known by the dictionaries of the scopes
+    known: Mutex<FxHashMap<Scope, LruCache<String, bool>>>,
+}
+
+impl SpellChecker {
+    pub(crate) fn new(config: SpellCheckConfig) -> Self {
+        let ignore = config.ignore.iter().map(|word| fold_case(word)).collect();
+
+        SpellChecker {
+            config,
+            ignore,
+            known: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn config(&self) -> &SpellCheckConfig {
+        &self.config
+    }
+
+    /// Whether the word or its lemmas are in any dictionary
+    async fn is_known(&self, word: &Word, dicts: &DictChain, scope: &Scope) -> Result<bool> {
+        if let Some(known) = self
+            .known
+            .lock()
+            .unwrap()
+            .get_mut(scope)
+            .and_then(|known| known.get(word.as_str()).copied())
+        {
+            return Ok(known);
+        }
+
+        let mut known = dicts.exits(word, scope).await?;
+        if !known {
+            for lemma in lemmatizer::lemmas(word, scope.language.as_deref()) {
+                if dicts.exits(&lemma, scope).await? {
+                    known = true;
+                    break;
+                }
+            }
+        }
+
+        self.known
+            .lock()
+            .unwrap()
+            .entry(scope.clone())
+            .or_insert_with(|| LruCache::new(NonZeroUsize::new(KNOWN_CAPACITY).unwrap()))
+            .put(word.as_str().to_string(), known);
+
+        Ok(known)
+    }
+
+    /// Diagnostics for the words unknown by the dictionaries of the scope
+    pub(crate) async fn check(
+        &self,
+        words: Vec<WordSpan>,
+        dicts: &DictChain,
+        scope: &Scope,
+    ) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = vec![];
+
+        for span in words {
+            if self.ignore.contains(span.word.as_str())
+                || self.is_known(&span.word, dicts, scope).await?
+            {
+                continue;
+            }
+
+            diagnostics.push(Diagnostic {
+                range: span.range,
+                severity: Some(self.config.severity.into()),
+                source: Some("etymora".to_string()),
+                message: format!("Unknown word: {}", span.word),
+                ..Default::default()
+            });
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dict_handler::{DictChainConfig, HeadwordCache, LookupMode};
+
+    async fn chain(dir: &std::path::Path) -> DictChain {
+        let path = dir.join("JMdict_e");
+        std::fs::write(
+            &path,
+            "<JMdict><entry><r_ele><reb>dog</reb></r_ele><sense><gloss>dog</gloss></sense></entry>\
+             <entry><r_ele><reb>run</reb></r_ele><sense><gloss>run</gloss></sense></entry></JMdict>",
+        )
+        .unwrap();
+
+        let config: DictChainConfig =
+            serde_json::from_value(serde_json::json!({ "jmdict": { "path": path } })).unwrap();
+
+        DictChain::init(&config, LookupMode::First, &HeadwordCache::default())
+            .await
+            .unwrap()
+    }
+
+    fn span(word: &str, character: u32) -> WordSpan {
+        WordSpan::new(
+            word,
+            lsp_types::Range::new(
+                lsp_types::Position::new(0, character),
+                lsp_types::Position::new(0, character + word.len() as u32),
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let dicts = chain(dir.path()).await;
+
+        let checker = SpellChecker::new(
+            serde_json::from_value(serde_json::json!({
+                "enabled": true,
+                "severity": "warning",
+                "ignore": ["Etymora"],
+            }))
+            .unwrap(),
+        );
+        assert_eq!(checker.config().debounce, 500);
+
+        let diagnostics = checker
+            .check(
+                vec![
+                    span("dogs", 0),
+                    span("ran", 5),
+                    span("etymora", 9),
+                    span("dgo", 17),
+                ],
+                &dicts,
+                &Scope::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unknown word: dgo");
+        assert_eq!(diagnostics[0].range.start.character, 17);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+
+        assert_eq!(
+            checker.known.lock().unwrap()[&Scope::default()].peek("dgo"),
+            Some(&false)
+        );
+
+        // Cached per scope
+        let scope = Scope {
+            language: None,
+            dictionaries: vec!["WordNet".to_string()],
+        };
+        let diagnostics = checker
+            .check(vec![span("dog", 0)], &dicts, &scope)
+            .await
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+
+        // The English lemmas aren't tried for the other languages
+        let diagnostics = checker
+            .check(vec![span("dogs", 0)], &dicts, &Scope::language(Some("de")))
+            .await
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
@@ -1,9 +1,24 @@
-use crate::error::EtymoraError;
-use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, OnceLock, Weak,
+};
+
+use crate::{error::EtymoraError, suggest};
+use etymora_traits::{Completion, Dictionary, Word};
+use rustc_hash::FxHashMap;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 
 #[derive(Debug)]
 pub(crate) enum Dicts {
     ExampleDict(adapter_example::ExampleDictionary),
+    Stardict(adapter_stardict::StardictDictionary),
+    Dictd(adapter_dictd::DictdDictionary),
+    Wiktionary(adapter_wiktionary::WiktionaryDictionary),
+    Wordnet(adapter_wordnet::WordnetDictionary),
+    Sqlite(adapter_sqlite::SqliteDictionary),
+    Jmdict(adapter_jmdict::JmdictDictionary),
+    External(adapter_external::ExternalDictionary),
+    Wasm(adapter_wasm::WasmDictionary),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -12,8 +27,32 @@ pub(crate) enum Dicts {
 pub(crate) enum DictConfigs {
     #[default]
     Example,
+    Stardict(adapter_stardict::StardictConfig),
+    Dictd(adapter_dictd::DictdConfig),
+    Wiktionary(adapter_wiktionary::WiktionaryConfig),
+    Wordnet(adapter_wordnet::WordnetConfig),
+    Sqlite(adapter_sqlite::SqliteConfig),
+    Jmdict(adapter_jmdict::JmdictConfig),
+    External(adapter_external::ExternalConfig),
+    Wasm(adapter_wasm::WasmConfig),
 }
 
+impl DictConfigs {
+    /// The name of the kind of the dictionary
+    fn kind(&self) -> &'static str {
+        match self {
+            DictConfigs::Example => "example",
+            DictConfigs::Stardict(_) => "stardict",
+            DictConfigs::Dictd(_) => "dictd",
+            DictConfigs::Wiktionary(_) => "wiktionary",
+            DictConfigs::Wordnet(_) => "wordnet",
+            DictConfigs::Sqlite(_) => "sqlite",
+            DictConfigs::Jmdict(_) => "jmdict",
+            DictConfigs::External(_) => "external",
+            DictConfigs::Wasm(_) => "wasm",
+        }
+    }
+}
 
 impl etymora_traits::Dictionary for Dicts {
     type Error = EtymoraError;
@@ -26,12 +65,60 @@ impl etymora_traits::Dictionary for Dicts {
                     .await
                     .map_err(EtymoraError::ExampleAdapter)?,
             )),
+            DictConfigs::Stardict(config) => Ok(Dicts::Stardict(
+                adapter_stardict::StardictDictionary::init(config)
+                    .await
+                    .map_err(EtymoraError::StardictAdapter)?,
+            )),
+            DictConfigs::Dictd(config) => Ok(Dicts::Dictd(
+                adapter_dictd::DictdDictionary::init(config)
+                    .await
+                    .map_err(EtymoraError::DictdAdapter)?,
+            )),
+            DictConfigs::Wiktionary(config) => Ok(Dicts::Wiktionary(
+                adapter_wiktionary::WiktionaryDictionary::init(config)
+                    .await
+                    .map_err(EtymoraError::WiktionaryAdapter)?,
+            )),
+            DictConfigs::Wordnet(config) => Ok(Dicts::Wordnet(
+                adapter_wordnet::WordnetDictionary::init(config)
+                    .await
+                    .map_err(EtymoraError::WordnetAdapter)?,
+            )),
+            DictConfigs::Sqlite(config) => Ok(Dicts::Sqlite(
+                adapter_sqlite::SqliteDictionary::init(config)
+                    .await
+                    .map_err(EtymoraError::SqliteAdapter)?,
+            )),
+            DictConfigs::Jmdict(config) => Ok(Dicts::Jmdict(
+                adapter_jmdict::JmdictDictionary::init(config)
+                    .await
+                    .map_err(EtymoraError::JmdictAdapter)?,
+            )),
+            DictConfigs::External(config) => Ok(Dicts::External(
+                adapter_external::ExternalDictionary::init(config)
+                    .await
+                    .map_err(EtymoraError::ExternalAdapter)?,
+            )),
+            DictConfigs::Wasm(config) => Ok(Dicts::Wasm(
+                adapter_wasm::WasmDictionary::init(config)
+                    .await
+                    .map_err(EtymoraError::WasmAdapter)?,
+            )),
         }
     }
 
     async fn exits(&self, word: &etymora_traits::Word) -> Result<bool, Self::Error> {
         match self {
             Dicts::ExampleDict(d) => d.exits(word).await.map_err(EtymoraError::ExampleAdapter),
+            Dicts::Stardict(d) => d.exits(word).await.map_err(EtymoraError::StardictAdapter),
+            Dicts::Dictd(d) => d.exits(word).await.map_err(EtymoraError::DictdAdapter),
+            Dicts::Wiktionary(d) => d.exits(word).await.map_err(EtymoraError::WiktionaryAdapter),
+            Dicts::Wordnet(d) => d.exits(word).await.map_err(EtymoraError::WordnetAdapter),
+            Dicts::Sqlite(d) => d.exits(word).await.map_err(EtymoraError::SqliteAdapter),
+            Dicts::Jmdict(d) => d.exits(word).await.map_err(EtymoraError::JmdictAdapter),
+            Dicts::External(d) => d.exits(word).await.map_err(EtymoraError::ExternalAdapter),
+            Dicts::Wasm(d) => d.exits(word).await.map_err(EtymoraError::WasmAdapter),
         }
     }
 
@@ -44,6 +131,827 @@ impl etymora_traits::Dictionary for Dicts {
                 .lookup_ditail(word)
                 .await
                 .map_err(EtymoraError::ExampleAdapter),
+            Dicts::Stardict(d) => d
+                .lookup_ditail(word)
+                .await
+                .map_err(EtymoraError::StardictAdapter),
+            Dicts::Dictd(d) => d
+                .lookup_ditail(word)
+                .await
+                .map_err(EtymoraError::DictdAdapter),
+            Dicts::Wiktionary(d) => d
+                .lookup_ditail(word)
+                .await
+                .map_err(EtymoraError::WiktionaryAdapter),
+            Dicts::Wordnet(d) => d
+                .lookup_ditail(word)
+                .await
+                .map_err(EtymoraError::WordnetAdapter),
+            Dicts::Sqlite(d) => d
+                .lookup_ditail(word)
+                .await
+                .map_err(EtymoraError::SqliteAdapter),
+            Dicts::Jmdict(d) => d
+                .lookup_ditail(word)
+                .await
+                .map_err(EtymoraError::JmdictAdapter),
+            Dicts::External(d) => d
+                .lookup_ditail(word)
+                .await
+                .map_err(EtymoraError::ExternalAdapter),
+            Dicts::Wasm(d) => d
+                .lookup_ditail(word)
+                .await
+                .map_err(EtymoraError::WasmAdapter),
+        }
+    }
+
+    async fn prefix_search(
+        &self,
+        prefix: &etymora_traits::Word,
+        limit: usize,
+    ) -> Result<Vec<Completion>, Self::Error> {
+        match self {
+            Dicts::ExampleDict(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::ExampleAdapter),
+            Dicts::Stardict(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::StardictAdapter),
+            Dicts::Dictd(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::DictdAdapter),
+            Dicts::Wiktionary(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::WiktionaryAdapter),
+            Dicts::Wordnet(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::WordnetAdapter),
+            Dicts::Sqlite(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::SqliteAdapter),
+            Dicts::Jmdict(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::JmdictAdapter),
+            Dicts::External(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::ExternalAdapter),
+            Dicts::Wasm(d) => d
+                .prefix_search(prefix, limit)
+                .await
+                .map_err(EtymoraError::WasmAdapter),
         }
     }
+
+    async fn headwords(&self) -> Result<Vec<String>, Self::Error> {
+        match self {
+            Dicts::ExampleDict(d) => d.headwords().await.map_err(EtymoraError::ExampleAdapter),
+            Dicts::Stardict(d) => d.headwords().await.map_err(EtymoraError::StardictAdapter),
+            Dicts::Dictd(d) => d.headwords().await.map_err(EtymoraError::DictdAdapter),
+            Dicts::Wiktionary(d) => d.headwords().await.map_err(EtymoraError::WiktionaryAdapter),
+            Dicts::Wordnet(d) => d.headwords().await.map_err(EtymoraError::WordnetAdapter),
+            Dicts::Sqlite(d) => d.headwords().await.map_err(EtymoraError::SqliteAdapter),
+            Dicts::Jmdict(d) => d.headwords().await.map_err(EtymoraError::JmdictAdapter),
+            Dicts::External(d) => d.headwords().await.map_err(EtymoraError::ExternalAdapter),
+            Dicts::Wasm(d) => d.headwords().await.map_err(EtymoraError::WasmAdapter),
+        }
+    }
+}
+
+/// How the chain of dictionaries is looked up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LookupMode {
+    /// Only the first dictionary in the order which has the word
+    #[default]
+    First,
+    /// All dictionaries which have the word, with a section for each
+    Merge,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A dictionary of the chain with its settings
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DictEntry {
+    dict: DictConfigs,
+    /// The name shown in merged hovers. The kind of the dictionary if it's not set
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    /// The languages(ISO 639-1 like "en" and "ja") of the words looked up in it. All if it's empty
+    #[serde(default)]
+    languages: Vec<String>,
+}
+
+/// A dictionary, or the list of them in priority order
+#[derive(Debug)]
+pub(crate) enum DictChainConfig {
+    Chain(Vec<DictEntry>),
+    Single(DictConfigs),
+}
+
+/// Chosen by the shape, not untagged, to report the errors of the fields
+impl<'de> Deserialize<'de> for DictChainConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::Array(entries) => entries
+                .into_iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    DictEntry::deserialize(entry)
+                        .map_err(|e| D::Error::custom(format!("dict_config[{i}]: {e}")))
+                })
+                .collect::<Result<_, _>>()
+                .map(DictChainConfig::Chain),
+            dict => DictConfigs::deserialize(dict)
+                .map(DictChainConfig::Single)
+                .map_err(|e| D::Error::custom(format!("dict_config: {e}"))),
+        }
+    }
+}
+
+impl Default for DictChainConfig {
+    fn default() -> Self {
+        DictChainConfig::Single(DictConfigs::default())
+    }
+}
+
+#[derive(Debug)]
+struct ChainedDict {
+    name: String,
+    languages: Vec<String>,
+    dict: Arc<Dicts>,
+    /// The single-word headwords of the dictionary for suggestions, built in the background
+    headwords: Arc<Headwords>,
+}
+
+/// The BK-tree of the headwords of a dictionary
+#[derive(Debug, Default)]
+struct Headwords {
+    tree: OnceLock<suggest::BkTree>,
+    /// Whether the tree is being built
+    building: AtomicBool,
+}
+
+impl ChainedDict {
+    /// Build the BK-tree of the headwords in the background unless it's built or being built.
+    /// The tree is left unbuilt on failure, and built again on the next use
+    fn index_headwords(&self) {
+        if self.headwords.tree.get().is_some()
+            || self.headwords.building.swap(true, Ordering::AcqRel)
+        {
+            return;
+        }
+
+        let name = self.name.clone();
+        let dict = Arc::clone(&self.dict);
+        let headwords = Arc::clone(&self.headwords);
+        tokio::spawn(async move {
+            match dict.headwords().await {
+                Ok(words) => {
+                    tracing::info!("Indexing {} headwords of {name}", words.len());
+
+                    // Millions of words take seconds
+                    let tree = tokio::task::spawn_blocking(move || {
+                        words
+                            .into_iter()
+                            .filter(|word| !word.contains(char::is_whitespace))
+                            .collect()
+                    })
+                    .await;
+                    match tree {
+                        Ok(tree) => {
+                            let _ = headwords.tree.set(tree);
+                        }
+                        Err(e) => tracing::warn!("Failed to index the headwords of {name}: {e}"),
+                    }
+                }
+                Err(e) => tracing::warn!("{name} failed to list the headwords: {e}"),
+            }
+            headwords.building.store(false, Ordering::Release);
+        });
+    }
+
+    /// The BK-tree of the headwords. `None` until it's built
+    fn headwords(&self) -> Option<&suggest::BkTree> {
+        let tree = self.headwords.tree.get();
+        if tree.is_none() {
+            self.index_headwords();
+        }
+        tree
+    }
+
+    /// Whether the words in the scope are looked up. Words of unknown languages are looked up in all
+    fn accepts(&self, scope: &Scope) -> bool {
+        (scope.dictionaries.is_empty() || scope.dictionaries.contains(&self.name))
+            && (self.languages.is_empty()
+                || scope
+                    .language
+                    .as_ref()
+                    .is_none_or(|language| self.languages.contains(language)))
+    }
+}
+
+/// Which dictionaries of the chain are used
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct Scope {
+    /// The language(ISO 639-1) of the word. `None` if it's unknown
+    pub(crate) language: Option<String>,
+    /// The names of the dictionaries to use. All if it's empty
+    pub(crate) dictionaries: Vec<String>,
+}
+
+impl Scope {
+    /// All the dictionaries for the language
+    pub(crate) fn language(language: Option<&str>) -> Self {
+        Scope {
+            language: language.map(str::to_string),
+            dictionaries: vec![],
+        }
+    }
+
+    /// The scope for a word, whose language is known by its script or not
+    pub(crate) fn with_language(&self, language: Option<&str>) -> Self {
+        Scope {
+            language: language.map(str::to_string).or(self.language.clone()),
+            dictionaries: self.dictionaries.clone(),
+        }
+    }
+}
+
+/// The description of a word in a dictionary of the chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    /// The name of the dictionary
+    pub(crate) dictionary: String,
+    /// The description in markdown
+    pub(crate) description: String,
+}
+
+/// The BK-trees of the headwords, shared by the chains which have the dictionaries of the same configs.
+/// The trees are dropped with the last chain using them
+#[derive(Debug, Default, Clone)]
+pub(crate) struct HeadwordCache(Arc<Mutex<FxHashMap<String, Weak<Headwords>>>>);
+
+impl HeadwordCache {
+    fn get(&self, config: &DictConfigs) -> Arc<Headwords> {
+        // Configs are compared by the debug representations, which differ for different configs
+        let key = format!("{config:?}");
+
+        let mut trees = self.0.lock().unwrap();
+        trees.retain(|_, tree| tree.strong_count() > 0);
+        if let Some(tree) = trees.get(&key).and_then(Weak::upgrade) {
+            return tree;
+        }
+
+        let tree = Arc::default();
+        trees.insert(key, Arc::downgrade(&tree));
+        tree
+    }
+}
+
+/// The enabled dictionaries in priority order
+#[derive(Debug, Default)]
+pub(crate) struct DictChain {
+    dicts: Vec<ChainedDict>,
+    mode: LookupMode,
+    /// The names of the dictionaries which failed to initialize, and the errors
+    failures: Vec<(String, EtymoraError)>,
+}
+
+impl DictChain {
+    /// The dictionaries failing to initialize are skipped.
+    /// It fails only when none of the enabled dictionaries is initialized
+    pub(crate) async fn init(
+        config: &DictChainConfig,
+        mode: LookupMode,
+        headwords: &HeadwordCache,
+    ) -> Result<Self, EtymoraError> {
+        let entries: Vec<(&DictConfigs, Option<&str>, &[String])> = match config {
+            DictChainConfig::Single(dict) => vec![(dict, None, &[])],
+            DictChainConfig::Chain(entries) => entries
+                .iter()
+                .filter(|entry| entry.enabled)
+                .map(|entry| (&entry.dict, entry.name.as_deref(), &entry.languages[..]))
+                .collect(),
+        };
+
+        let mut names: Vec<&str> = Vec::with_capacity(entries.len());
+        let mut dicts: Vec<ChainedDict> = Vec::with_capacity(entries.len());
+        let mut failures = vec![];
+        for (config, name, languages) in entries {
+            // Routes and `etymora/lookup` choose the dictionaries by the names
+            let name = name.unwrap_or(config.kind());
+            if names.contains(&name) {
+                return Err(EtymoraError::DuplicateDictionary(name.to_string()));
+            }
+            names.push(name);
+
+            match Dicts::init(config).await {
+                Ok(dict) => dicts.push(ChainedDict {
+                    name: name.to_string(),
+                    languages: languages.to_vec(),
+                    dict: Arc::new(dict),
+                    headwords: headwords.get(config),
+                }),
+                Err(e) => {
+                    tracing::warn!("Skipped the dictionary {name}: {e}");
+                    failures.push((name.to_string(), e));
+                }
+            }
+        }
+
+        for dict in &dicts {
+            dict.index_headwords();
+        }
+
+        if dicts.is_empty() {
+            if let Some((_, e)) = failures.pop() {
+                return Err(e);
+            }
+        }
+
+        Ok(DictChain {
+            dicts,
+            mode,
+            failures,
+        })
+    }
+
+    /// Wait until the headwords of all the dictionaries are indexed
+    #[cfg(test)]
+    pub(crate) async fn indexed(&self) {
+        while self
+            .dicts
+            .iter()
+            .any(|dict| dict.headwords.tree.get().is_none())
+        {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    }
+
+    /// The dictionaries skipped by the errors of the initialization
+    pub(crate) fn failures(&self) -> &[(String, EtymoraError)] {
+        &self.failures
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.dicts.is_empty()
+    }
+
+    /// Whether the chain has the dictionary named so, including the ones failed to initialize
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.dicts.iter().any(|dict| dict.name == name)
+            || self.failures.iter().any(|(failed, _)| failed == name)
+    }
+
+    fn dicts_for<'a>(&'a self, scope: &'a Scope) -> impl Iterator<Item = &'a ChainedDict> {
+        self.dicts.iter().filter(move |dict| dict.accepts(scope))
+    }
+
+    /// Whether any dictionary in the scope has the word.
+    /// Errors of some dictionaries are only logged if the others work
+    pub(crate) async fn exits(&self, word: &Word, scope: &Scope) -> Result<bool, EtymoraError> {
+        let mut error = None;
+        let mut succeeded = false;
+
+        for dict in self.dicts_for(scope) {
+            match dict.dict.exits(word).await {
+                Ok(true) => return Ok(true),
+                Ok(false) => succeeded = true,
+                Err(e) => {
+                    tracing::warn!("{} failed: {e}", dict.name);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        match error {
+            Some(e) if !succeeded => Err(e),
+            _ => Ok(false),
+        }
+    }
+
+    /// Look up the word in the dictionaries in the scope by `LookupMode`, rendered in markdown.
+    /// Errors of some dictionaries are only logged if the others work
+    pub(crate) async fn lookup_ditail(
+        &self,
+        word: &Word,
+        scope: &Scope,
+    ) -> Result<Option<String>, EtymoraError> {
+        let entries = self.lookup_entries(word, scope).await?;
+
+        Ok(self.render(&entries))
+    }
+
+    /// Look up the word in the dictionaries in the scope by `LookupMode`.
+    /// Errors of some dictionaries are only logged if the others work
+    pub(crate) async fn lookup_entries(
+        &self,
+        word: &Word,
+        scope: &Scope,
+    ) -> Result<Vec<Entry>, EtymoraError> {
+        let mut error = None;
+        let mut succeeded = false;
+        let mut entries = vec![];
+
+        for dict in self.dicts_for(scope) {
+            match dict.dict.lookup_ditail(word).await {
+                Ok(Some(description)) => {
+                    entries.push(Entry {
+                        dictionary: dict.name.clone(),
+                        description,
+                    });
+                    if self.mode == LookupMode::First {
+                        return Ok(entries);
+                    }
+                }
+                Ok(None) => succeeded = true,
+                Err(e) => {
+                    tracing::warn!("{} failed: {e}", dict.name);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        match error {
+            Some(e) if entries.is_empty() && !succeeded => Err(e),
+            _ => Ok(entries),
+        }
+    }
+
+    /// Render the entries as hovers. Merged entries have a section for each dictionary
+    pub(crate) fn render(&self, entries: &[Entry]) -> Option<String> {
+        match (self.mode, entries) {
+            (_, []) => None,
+            (LookupMode::First, [entry, ..]) => Some(entry.description.clone()),
+            (LookupMode::Merge, entries) => Some(
+                entries
+                    .iter()
+                    .map(|entry| format!("**{}**\n\n{}", entry.dictionary, entry.description))
+                    .collect::<Vec<_>>()
+                    .join("\n---\n\n"),
+            ),
+        }
+    }
+
+    /// The words which start with the prefix in the dictionaries in the scope, up to `limit`.
+    /// The words found in multiple dictionaries have the gloss of the first.
+    /// Errors of some dictionaries are only logged if the others work
+    pub(crate) async fn prefix_search(
+        &self,
+        prefix: &Word,
+        scope: &Scope,
+        limit: usize,
+    ) -> Result<Vec<Completion>, EtymoraError> {
+        let mut error = None;
+        let mut succeeded = false;
+        let mut completions: Vec<Completion> = vec![];
+
+        for dict in self.dicts_for(scope) {
+            if completions.len() >= limit {
+                break;
+            }
+
+            match dict.dict.prefix_search(prefix, limit).await {
+                Ok(found) => {
+                    succeeded = true;
+                    for completion in found {
+                        if completions.len() < limit
+                            && !completions.iter().any(|c| c.word == completion.word)
+                        {
+                            completions.push(completion);
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("{} failed: {e}", dict.name);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        match error {
+            Some(e) if !succeeded => Err(e),
+            _ => Ok(completions),
+        }
+    }
+
+    /// The headwords close to the case folded word in the dictionaries in the scope, up to `limit`.
+    /// Closer words come first, and then the words with closer lengths.
+    /// The dictionaries whose headwords are still being indexed are skipped
+    pub(crate) fn suggest(&self, word: &Word, scope: &Scope, limit: usize) -> Vec<String> {
+        let max_distance = suggest::max_distance(word.as_str());
+        let length = word.as_str().chars().count();
+
+        let mut suggestions: Vec<(usize, usize, &str)> = vec![];
+        for dict in self.dicts_for(scope) {
+            let Some(headwords) = dict.headwords() else {
+                continue;
+            };
+            for (distance, headword) in headwords.find(word.as_str(), max_distance) {
+                if distance > 0 && !suggestions.iter().any(|(_, _, s)| *s == headword) {
+                    suggestions.push((
+                        distance,
+                        headword.chars().count().abs_diff(length),
+                        headword,
+                    ));
+                }
+            }
+        }
+
+        suggestions.sort();
+        suggestions
+            .into_iter()
+            .take(limit)
+            .map(|(_, _, headword)| headword.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    fn write_jmdict(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("JMdict_e");
+        std::fs::write(
+            &path,
+            "<JMdict><entry><r_ele><reb>これ</reb></r_ele><sense><gloss>this</gloss></sense></entry></JMdict>",
+        )
+        .unwrap();
+        path
+    }
+
+    async fn chain(dir: &Path, mode: LookupMode) -> DictChain {
+        let config: DictChainConfig = serde_json::from_value(serde_json::json!([
+            { "dict": { "jmdict": { "path": write_jmdict(dir) } }, "name": "JMdict", "languages": ["ja"] },
+            { "dict": { "stardict": { "path": "/not-exist.ifo" } }, "enabled": false },
+            { "dict": "example", "languages": ["en"] },
+        ]))
+        .unwrap();
+
+        DictChain::init(&config, mode, &HeadwordCache::default())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_chain_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let chain = chain(dir.path(), LookupMode::First).await;
+
+        // The disabled dictionary isn't initialized
+        assert_eq!(chain.dicts.len(), 2);
+
+        assert!(chain
+            .exits(&"これ".into(), &Scope::language(Some("ja")))
+            .await
+            .unwrap());
+        assert!(!chain
+            .exits(&"dog".into(), &Scope::language(Some("ja")))
+            .await
+            .unwrap());
+        assert!(chain
+            .exits(&"dog".into(), &Scope::language(Some("en")))
+            .await
+            .unwrap());
+
+        assert!(chain
+            .lookup_ditail(&"これ".into(), &Scope::language(None))
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("# これ\n\n1. this"));
+        // Falls back to the next
+        assert!(chain
+            .lookup_ditail(&"dog".into(), &Scope::language(None))
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("# dog\n\nThis message"));
+        assert_eq!(
+            chain
+                .lookup_ditail(&"dog".into(), &Scope::language(Some("ja")))
+                .await
+                .unwrap(),
+            None
+        );
+
+        assert_eq!(
+            chain
+                .prefix_search(&"こ".into(), &Scope::language(None), 10)
+                .await
+                .unwrap(),
+            vec![Completion::new("これ", Some("this".into()))]
+        );
+        assert!(chain
+            .prefix_search(&"こ".into(), &Scope::language(Some("en")), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        chain.indexed().await;
+        assert_eq!(
+            chain.suggest(&"こえ".into(), &Scope::language(None), 5),
+            vec!["これ"]
+        );
+        assert!(chain
+            .suggest(&"こえ".into(), &Scope::language(Some("en")), 5)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chain_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let chain = chain(dir.path(), LookupMode::Merge).await;
+
+        let desc = chain
+            .lookup_ditail(&"これ".into(), &Scope::language(None))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(desc.starts_with("**JMdict**\n\n# これ\n"));
+        assert!(desc.contains("\n---\n\n**example**\n\n# これ\n"));
+
+        let scope = Scope {
+            language: None,
+            dictionaries: vec!["example".into()],
+        };
+        let entries = chain.lookup_entries(&"これ".into(), &scope).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].dictionary, "example");
+        assert!(chain.contains("JMdict"));
+        assert!(!chain.contains("WordNet"));
+
+        // The language of the document is used for the words in unknown languages
+        let scope = Scope::language(Some("de")).with_language(None);
+        assert_eq!(scope.language.as_deref(), Some("de"));
+        assert_eq!(
+            chain.lookup_ditail(&"dog".into(), &scope).await.unwrap(),
+            None
+        );
+
+        // Only the dictionary for the language
+        assert!(chain
+            .lookup_ditail(&"これ".into(), &Scope::language(Some("ja")))
+            .await
+            .unwrap()
+            .unwrap()
+            .ends_with("1. this\n"));
+    }
+
+    #[test]
+    fn test_chain_config() {
+        let single: DictChainConfig =
+            serde_json::from_value(serde_json::json!({ "wordnet": { "path": "/wn" } })).unwrap();
+        assert!(matches!(
+            single,
+            DictChainConfig::Single(DictConfigs::Wordnet(_))
+        ));
+
+        let single: DictChainConfig = serde_json::from_value(serde_json::json!("example")).unwrap();
+        assert!(matches!(
+            single,
+            DictChainConfig::Single(DictConfigs::Example)
+        ));
+
+        let DictChainConfig::Chain(entries) =
+            serde_json::from_value(serde_json::json!([{ "dict": "example" }])).unwrap()
+        else {
+            panic!("Not a chain");
+        };
+        assert!(entries[0].enabled);
+        assert!(entries[0].languages.is_empty());
+
+        // The errors of the fields are reported
+        let error = serde_json::from_value::<DictChainConfig>(serde_json::json!([
+            { "dict": "example" },
+            { "dict": { "wordnet": { "path": "/wn" } }, "langauges": ["en"] },
+        ]))
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("dict_config[1]"), "{error}");
+        assert!(error.contains("langauges"), "{error}");
+
+        let error = serde_json::from_value::<DictChainConfig>(
+            serde_json::json!({ "wordnet": { "pth": "/wn" } }),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("path"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_headword_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: DictChainConfig = serde_json::from_value(
+            serde_json::json!({ "jmdict": { "path": write_jmdict(dir.path()) } }),
+        )
+        .unwrap();
+        let cache = HeadwordCache::default();
+
+        let chain = DictChain::init(&config, LookupMode::First, &cache)
+            .await
+            .unwrap();
+        // Built in the background, and no suggestions until then
+        assert!(chain.dicts[0].headwords.tree.get().is_none());
+        assert!(chain
+            .suggest(&"こえ".into(), &Scope::default(), 5)
+            .is_empty());
+
+        let other = DictChain::init(&config, LookupMode::Merge, &cache)
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(
+            &chain.dicts[0].headwords,
+            &other.dicts[0].headwords
+        ));
+
+        chain.indexed().await;
+        assert!(other.dicts[0].headwords.tree.get().is_some());
+        assert_eq!(
+            other.suggest(&"こえ".into(), &Scope::default(), 5),
+            vec!["これ"]
+        );
+
+        // Dropped with the chains
+        drop((chain, other));
+        let chain = DictChain::init(&config, LookupMode::First, &cache)
+            .await
+            .unwrap();
+        assert!(chain.dicts[0].headwords.tree.get().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_names() {
+        let config: DictChainConfig = serde_json::from_value(serde_json::json!([
+            { "dict": "example", "name": "Dict" },
+            { "dict": "example", "name": "Dict" },
+        ]))
+        .unwrap();
+        assert!(matches!(
+            DictChain::init(&config, LookupMode::First, &HeadwordCache::default()).await,
+            Err(EtymoraError::DuplicateDictionary(name)) if name == "Dict"
+        ));
+
+        // Disabled ones are not in the chain
+        let config: DictChainConfig = serde_json::from_value(serde_json::json!([
+            { "dict": "example" },
+            { "dict": "example", "enabled": false },
+        ]))
+        .unwrap();
+        assert!(
+            DictChain::init(&config, LookupMode::First, &HeadwordCache::default())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: DictChainConfig = serde_json::from_value(serde_json::json!([
+            { "dict": { "jmdict": { "path": dir.path().join("missing") } }, "name": "Missing" },
+            { "dict": { "jmdict": { "path": write_jmdict(dir.path()) } }, "name": "JMdict" },
+        ]))
+        .unwrap();
+
+        // Skipped, and the others are used
+        let chain = DictChain::init(&config, LookupMode::First, &HeadwordCache::default())
+            .await
+            .unwrap();
+        assert_eq!(chain.failures().len(), 1);
+        assert_eq!(chain.failures()[0].0, "Missing");
+        assert!(chain.contains("Missing"));
+        assert!(chain
+            .exits(&"これ".into(), &Scope::default())
+            .await
+            .unwrap());
+
+        // No dictionary
+        let config: DictChainConfig = serde_json::from_value(serde_json::json!([
+            { "dict": { "jmdict": { "path": dir.path().join("missing") } } },
+        ]))
+        .unwrap();
+        assert!(matches!(
+            DictChain::init(&config, LookupMode::First, &HeadwordCache::default()).await,
+            Err(EtymoraError::JmdictAdapter(_))
+        ));
+    }
 }
@@ -8,6 +8,22 @@ pub(crate) enum EtymoraError {
     #[error("{0}")]
     ExampleAdapter(#[source] adapter_example::ExampleError),
     #[error("{0}")]
+    StardictAdapter(#[source] adapter_stardict::StardictError),
+    #[error("{0}")]
+    DictdAdapter(#[source] adapter_dictd::DictdError),
+    #[error("{0}")]
+    WiktionaryAdapter(#[source] adapter_wiktionary::WiktionaryError),
+    #[error("{0}")]
+    WordnetAdapter(#[source] adapter_wordnet::WordnetError),
+    #[error("{0}")]
+    SqliteAdapter(#[source] adapter_sqlite::SqliteError),
+    #[error("{0}")]
+    JmdictAdapter(#[source] adapter_jmdict::JmdictError),
+    #[error("{0}")]
+    ExternalAdapter(#[source] adapter_external::ExternalError),
+    #[error("{0}")]
+    WasmAdapter(#[source] adapter_wasm::WasmError),
+    #[error("{0}")]
     Protocol(#[source] lsp_server::ProtocolError),
     #[error("Error occurs in desirializing, this is a type of ProtocolError: {0}")]
     Desirialize(#[source] serde_json::Error),
@@ -17,19 +33,62 @@ pub(crate) enum EtymoraError {
     SendMessage(lsp_server::Message),
     #[error("{0}")]
     Fs(crate::text_document::FsError),
+    #[error("{0}")]
+    Segment(#[source] crate::text_document::SegmentError),
+    #[error("No dictionary is named {0}")]
+    UnknownDictionary(String),
+    #[error("Dictionaries are named {0} twice. Set `name` to tell them apart")]
+    DuplicateDictionary(String),
+    #[error("Invalid glob in routes: {0}")]
+    Glob(#[source] globset::Error),
+    #[error("Language {0} is not supported by the detection")]
+    UnknownLanguage(String),
+    #[error("Failed to read {}: {source}", path.display())]
+    ConfigFileRead {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to parse {}: {source}", path.display())]
+    ConfigFileParse {
+        path: std::path::PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("{} can't use the {adapter} dictionary, unless the project is in `trusted_projects` of the user config", path.display())]
+    UntrustedProject {
+        path: std::path::PathBuf,
+        adapter: String,
+    },
 }
 
 impl From<&EtymoraError> for ErrorCode {
     fn from(value: &EtymoraError) -> Self {
         match value {
             EtymoraError::ExampleAdapter(_) => ErrorCode::InternalError,
+            EtymoraError::StardictAdapter(_) => ErrorCode::InternalError,
+            EtymoraError::DictdAdapter(_) => ErrorCode::InternalError,
+            EtymoraError::WiktionaryAdapter(_) => ErrorCode::InternalError,
+            EtymoraError::WordnetAdapter(_) => ErrorCode::InternalError,
+            EtymoraError::SqliteAdapter(_) => ErrorCode::InternalError,
+            EtymoraError::JmdictAdapter(_) => ErrorCode::InternalError,
+            EtymoraError::ExternalAdapter(_) => ErrorCode::InternalError,
+            EtymoraError::WasmAdapter(_) => ErrorCode::InternalError,
             EtymoraError::StdIO(_) => ErrorCode::InternalError,
             EtymoraError::SendMessage(_) => ErrorCode::InternalError,
+            EtymoraError::Segment(_) => ErrorCode::InternalError,
+            EtymoraError::ConfigFileRead { .. } => ErrorCode::InternalError,
+            EtymoraError::ConfigFileParse { .. } => ErrorCode::InternalError,
+            EtymoraError::UntrustedProject { .. } => ErrorCode::InternalError,
+            EtymoraError::Glob(_) => ErrorCode::InternalError,
+            EtymoraError::UnknownLanguage(_) => ErrorCode::InternalError,
+            EtymoraError::DuplicateDictionary(_) => ErrorCode::InternalError,
 
             EtymoraError::Protocol(_) => ErrorCode::InvalidRequest,
 
             EtymoraError::Desirialize(_) => ErrorCode::InvalidParams,
             EtymoraError::Fs(_) => ErrorCode::InvalidParams,
+            EtymoraError::UnknownDictionary(_) => ErrorCode::InvalidParams,
         }
     }
 }
@@ -0,0 +1,345 @@
+//! Lemmatizer
+//! Guess the base forms(lemmas) of an English word, for dictionaries which only have headwords.
+//! Irregular forms are looked up in the table, and the others are stemmed by the suffix rules.
+
+use etymora_traits::Word;
+
+/// Irregular forms and their lemmas.
+/// A form can appear more than once when it has some lemmas.
+const IRREGULAR_FORMS: &[(&str, &str)] = &[
+    // Verbs
+    ("am", "be"),
+    ("are", "be"),
+    ("is", "be"),
+    ("was", "be"),
+    ("were", "be"),
+    ("been", "be"),
+    ("being", "be"),
+    ("has", "have"),
+    ("had", "have"),
+    ("does", "do"),
+    ("did", "do"),
+    ("done", "do"),
+    ("went", "go"),
+    ("gone", "go"),
+    ("goes", "go"),
+    ("arose", "arise"),
+    ("arisen", "arise"),
+    ("ate", "eat"),
+    ("eaten", "eat"),
+    ("became", "become"),
+    ("began", "begin"),
+    ("begun", "begin"),
+    ("bent", "bend"),
+    ("bit", "bite"),
+    ("bitten", "bite"),
+    ("blew", "blow"),
+    ("blown", "blow"),
+    ("bore", "bear"),
+    ("born", "bear"),
+    ("borne", "bear"),
+    ("bought", "buy"),
+    ("broke", "break"),
+    ("broken", "break"),
+    ("brought", "bring"),
+    ("built", "build"),
+    ("caught", "catch"),
+    ("chose", "choose"),
+    ("chosen", "choose"),
+    ("came", "come"),
+    ("dealt", "deal"),
+    ("drew", "draw"),
+    ("drawn", "draw"),
+    ("drank", "drink"),
+    ("drunk", "drink"),
+    ("drove", "drive"),
+    ("driven", "drive"),
+    ("dug", "dig"),
+    ("fell", "fall"),
+    ("fallen", "fall"),
+    ("fed", "feed"),
+    ("felt", "feel"),
+    ("fought", "fight"),
+    ("found", "find"),
+    ("fled", "flee"),
+    ("flew", "fly"),
+    ("flown", "fly"),
+    ("forgot", "forget"),
+    ("forgotten", "forget"),
+    ("froze", "freeze"),
+    ("frozen", "freeze"),
+    ("got", "get"),
+    ("gotten", "get"),
+    ("gave", "give"),
+    ("given", "give"),
+    ("grew", "grow"),
+    ("grown", "grow"),
+    ("hung", "hang"),
+    ("heard", "hear"),
+    ("hid", "hide"),
+    ("hidden", "hide"),
+    ("held", "hold"),
+    ("kept", "keep"),
+    ("knew", "know"),
+    ("known", "know"),
+    ("laid", "lay"),
+    ("led", "lead"),
+    ("left", "leave"),
+    ("lent", "lend"),
+    ("lay", "lie"),
+    ("lain", "lie"),
+    ("lost", "lose"),
+    ("made", "make"),
+    ("meant", "mean"),
+    ("met", "meet"),
+    ("paid", "pay"),
+    ("ran", "run"),
+    ("rang", "ring"),
+    ("rung", "ring"),
+    ("rode", "ride"),
+    ("ridden", "ride"),
+    ("rose", "rise"),
+    ("risen", "rise"),
+    ("said", "say"),
+    ("saw", "see"),
+    ("seen", "see"),
+    ("sought", "seek"),
+    ("sold", "sell"),
+    ("sent", "send"),
+    ("shook", "shake"),
+    ("shaken", "shake"),
+    ("shone", "shine"),
+    ("shot", "shoot"),
+    ("showed", "show"),
+    ("shown", "show"),
+    ("sang", "sing"),
+    ("sung", "sing"),
+    ("sank", "sink"),
+    ("sunk", "sink"),
+    ("sat", "sit"),
+    ("slept", "sleep"),
+    ("slid", "slide"),
+    ("spoke", "speak"),
+    ("spoken", "speak"),
+    ("spent", "spend"),
+    ("stood", "stand"),
+    ("stole", "steal"),
+    ("stolen", "steal"),
+    ("stuck", "stick"),
+    ("struck", "strike"),
+    ("swore", "swear"),
+    ("sworn", "swear"),
+    ("swam", "swim"),
+    ("swum", "swim"),
+    ("took", "take"),
+    ("taken", "take"),
+    ("taught", "teach"),
+    ("tore", "tear"),
+    ("torn", "tear"),
+    ("told", "tell"),
+    ("thought", "think"),
+    ("threw", "throw"),
+    ("thrown", "throw"),
+    ("understood", "understand"),
+    ("woke", "wake"),
+    ("woken", "wake"),
+    ("wore", "wear"),
+    ("worn", "wear"),
+    ("won", "win"),
+    ("wrote", "write"),
+    ("written", "write"),
+    // Nouns
+    ("men", "man"),
+    ("women", "woman"),
+    ("children", "child"),
+    ("feet", "foot"),
+    ("teeth", "tooth"),
+    ("geese", "goose"),
+    ("mice", "mouse"),
+    ("lice", "louse"),
+    ("oxen", "ox"),
+    ("people", "person"),
+    ("dice", "die"),
+    ("criteria", "criterion"),
+    ("phenomena", "phenomenon"),
+    ("data", "datum"),
+    ("media", "medium"),
+    ("analyses", "analysis"),
+    ("crises", "crisis"),
+    ("theses", "thesis"),
+    ("indices", "index"),
+    ("matrices", "matrix"),
+    ("vertices", "vertex"),
+    ("cacti", "cactus"),
+    ("fungi", "fungus"),
+    ("nuclei", "nucleus"),
+    ("radii", "radius"),
+    ("stimuli", "stimulus"),
+    // Adjectives and adverbs
+    ("better", "good"),
+    ("best", "good"),
+    ("better", "well"),
+    ("best", "well"),
+    ("worse", "bad"),
+    ("worst", "bad"),
+    ("more", "many"),
+    ("most", "many"),
+    ("more", "much"),
+    ("most", "much"),
+    ("less", "little"),
+    ("least", "little"),
+    ("further", "far"),
+    ("furthest", "far"),
+    ("farther", "far"),
+    ("farthest", "far"),
+];
+
+/// Suffix rules `(suffix, replacement)`, applied in the order
+const SUFFIX_RULES: &[(&str, &str)] = &[
+    // Nouns and verbs in the third person
+    ("ies", "y"),
+    ("ves", "f"),
+    ("ves", "fe"),
+    ("sses", "ss"),
+    ("shes", "sh"),
+    ("ches", "ch"),
+    ("xes", "x"),
+    ("zes", "z"),
+    ("oes", "o"),
+    ("s", ""),
+    // Past tense and past participles
+    ("ied", "y"),
+    ("ed", ""),
+    ("ed", "e"),
+    // Present participles
+    ("ying", "ie"),
+    ("ing", ""),
+    ("ing", "e"),
+    // Comparatives and superlatives
+    ("ier", "y"),
+    ("iest", "y"),
+    ("er", ""),
+    ("er", "e"),
+    ("est", ""),
+    ("est", "e"),
+];
+
+/// The shortest lemma guessed by the suffix rules
+const MIN_LEMMA_LEN: usize = 2;
+
+/// Whether the char is a consonant which can be doubled before a suffix("running", "stopped")
+fn is_doubling_consonant(c: char) -> bool {
+    c.is_ascii_alphabetic() && !matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'w' | 'x' | 'y')
+}
+
+/// Candidate lemmas of the word in order, without the word itself.
+/// Phrases and the words of the languages other than English(ISO 639-1 `en`, or unknown) have no lemmas.
+pub(crate) fn lemmas(word: &Word, language: Option<&str>) -> Vec<Word> {
+    let word = word.as_str();
+    if word.contains(char::is_whitespace) || language.is_some_and(|language| language != "en") {
+        return vec![];
+    }
+
+    let mut lemmas: Vec<String> = IRREGULAR_FORMS
+        .iter()
+        .filter(|(form, _)| *form == word)
+        .map(|(_, lemma)| lemma.to_string())
+        .collect();
+
+    for (suffix, replacement) in SUFFIX_RULES {
+        let Some(stem) = word.strip_suffix(suffix) else {
+            continue;
+        };
+        if stem.chars().count() + replacement.chars().count() < MIN_LEMMA_LEN
+            || suffix == &"s" && stem.ends_with('s')
+        {
+            // "ss" of "class" isn't a plural
+            continue;
+        }
+
+        // "running" -> "run", "stopped" -> "stop"
+        if replacement.is_empty() && !suffix.ends_with('s') {
+            let mut chars = stem.chars().rev();
+            if let (Some(last), Some(second)) = (chars.next(), chars.next()) {
+                if last == second && is_doubling_consonant(last) {
+                    lemmas.push(stem[..stem.len() - last.len_utf8()].to_string());
+                }
+            }
+        }
+
+        lemmas.push(format!("{stem}{replacement}"));
+    }
+
+    let mut unique: Vec<Word> = Vec::with_capacity(lemmas.len());
+    for lemma in lemmas {
+        let lemma = Word::from(lemma);
+        if lemma.as_str() != word && !unique.contains(&lemma) {
+            unique.push(lemma);
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lemmas_str(word: &str) -> Vec<String> {
+        lemmas(&word.into(), None)
+            .into_iter()
+            .map(Word::inner)
+            .collect()
+    }
+
+    #[test]
+    fn test_irregular_forms() {
+        assert_eq!(lemmas_str("went")[0], "go");
+        assert_eq!(lemmas_str("mice")[0], "mouse");
+        assert_eq!(lemmas_str("children")[0], "child");
+
+        // Some lemmas
+        assert_eq!(lemmas_str("better")[..2], ["good", "well"]);
+    }
+
+    #[test]
+    fn test_suffix_rules() {
+        assert!(lemmas_str("cats").contains(&"cat".into()));
+        assert!(lemmas_str("boxes").contains(&"box".into()));
+        assert!(lemmas_str("studies").contains(&"study".into()));
+        assert!(lemmas_str("wolves").contains(&"wolf".into()));
+        assert!(lemmas_str("knives").contains(&"knife".into()));
+
+        assert!(lemmas_str("walked").contains(&"walk".into()));
+        assert!(lemmas_str("liked").contains(&"like".into()));
+        assert!(lemmas_str("carried").contains(&"carry".into()));
+        assert!(lemmas_str("stopped").contains(&"stop".into()));
+
+        assert!(lemmas_str("walking").contains(&"walk".into()));
+        assert!(lemmas_str("making").contains(&"make".into()));
+        assert!(lemmas_str("running").contains(&"run".into()));
+        assert!(lemmas_str("lying").contains(&"lie".into()));
+
+        assert!(lemmas_str("happier").contains(&"happy".into()));
+        assert!(lemmas_str("biggest").contains(&"big".into()));
+    }
+
+    #[test]
+    fn test_no_lemmas() {
+        // "ss" isn't a plural
+        assert!(!lemmas_str("class").contains(&"clas".into()));
+        // Too short
+        assert!(lemmas_str("is").iter().all(|lemma| lemma == "be"));
+        // The word itself isn't a lemma
+        assert!(!lemmas_str("go").contains(&"go".into()));
+        // Phrases
+        assert!(lemmas_str("gives up").is_empty());
+    }
+
+    #[test]
+    fn test_language() {
+        assert_eq!(lemmas(&"went".into(), Some("en"))[0].as_str(), "go");
+        assert!(!lemmas(&"wanted".into(), None).is_empty());
+        assert!(lemmas(&"wanted".into(), Some("de")).is_empty());
+        assert!(lemmas(&"parles".into(), Some("fr")).is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+//! Lookup
+//! The pipeline looking up a word, shared by hovers and the custom `etymora/lookup` request.
+//! The word is looked up as it is, then in its lemmas, and the close headwords are suggested if nothing is found.
+
+use etymora_traits::Word;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dict_handler::{DictChain, Entry, Scope},
+    error::Result,
+    lemmatizer,
+};
+
+/// The max number of suggestions for an unknown word
+pub(crate) const SUGGESTION_LIMIT: usize = 5;
+
+/// `etymora/lookup`: look up an arbitrary word, not in a text document
+#[derive(Debug)]
+pub(crate) enum Lookup {}
+
+impl lsp_types::request::Request for Lookup {
+    type Params = LookupParams;
+    type Result = LookupResult;
+    const METHOD: &'static str = "etymora/lookup";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LookupParams {
+    pub(crate) word: String,
+    /// The name of the dictionary to use. All dictionaries if it's not set
+    #[serde(default)]
+    pub(crate) dictionary: Option<String>,
+    /// ISO 639-1 like "en" and "ja", to choose the dictionaries for the language
+    #[serde(default)]
+    pub(crate) language: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LookupResult {
+    /// The case folded word
+    pub(crate) word: String,
+    /// The lemma of the word whose entries are found, if the word itself has no entry
+    pub(crate) lemma: Option<String>,
+    pub(crate) entries: Vec<Entry>,
+    /// The headwords close to the word, if no entry is found
+    pub(crate) suggestions: Vec<String>,
+    /// The result rendered as hovers. `None` if nothing is found
+    pub(crate) markdown: Option<String>,
+}
+
+/// Look up the word, then its lemmas, and suggest the close headwords if nothing is found
+pub(crate) async fn lookup(dicts: &DictChain, word: &Word, scope: &Scope) -> Result<LookupResult> {
+    let mut result = LookupResult {
+        word: word.to_string(),
+        ..Default::default()
+    };
+
+    result.entries = dicts.lookup_entries(word, scope).await?;
+    if !result.entries.is_empty() {
+        result.markdown = dicts.render(&result.entries);
+        return Ok(result);
+    }
+
+    // 見出し語の形で引き直す
+    for lemma in lemmatizer::lemmas(word, scope.language.as_deref()) {
+        let entries = dicts.lookup_entries(&lemma, scope).await?;
+        if let Some(desc) = dicts.render(&entries) {
+            tracing::debug!("Found the lemma: {word} → {lemma}");
+            result.markdown = Some(format!("{word} → {lemma}\n\n{desc}"));
+            result.lemma = Some(lemma.inner());
+            result.entries = entries;
+            return Ok(result);
+        }
+    }
+
+    // 近い見出し語を提案する
+    result.suggestions = dicts.suggest(word, scope, SUGGESTION_LIMIT);
+    if !result.suggestions.is_empty() {
+        tracing::debug!("Suggesting {:?} for {word}", result.suggestions);
+        let list: Vec<String> = result
+            .suggestions
+            .iter()
+            .map(|s| format!("- {s}"))
+            .collect();
+        result.markdown = Some(format!(
+            "No entry for **{word}**. Did you mean:\n\n{}",
+            list.join("\n")
+        ));
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dict_handler::{DictChainConfig, HeadwordCache, LookupMode};
+
+    async fn chain(dir: &std::path::Path) -> DictChain {
+        let path = dir.join("JMdict_e");
+        std::fs::write(
+            &path,
+            "<JMdict><entry><r_ele><reb>run</reb></r_ele><sense><gloss>to move fast</gloss></sense></entry></JMdict>",
+        )
+        .unwrap();
+
+        let config: DictChainConfig = serde_json::from_value(serde_json::json!([
+            { "dict": { "jmdict": { "path": path } }, "name": "JMdict" },
+        ]))
+        .unwrap();
+
+        DictChain::init(&config, LookupMode::First, &HeadwordCache::default())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let dicts = chain(dir.path()).await;
+
+        let result = lookup(&dicts, &"run".into(), &Scope::default())
+            .await
+            .unwrap();
+        assert_eq!(result.entries[0].dictionary, "JMdict");
+        assert_eq!(result.lemma, None);
+        assert!(result.markdown.unwrap().starts_with("# run\n"));
+
+        let scope = Scope {
+            language: None,
+            dictionaries: vec!["JMdict".to_string()],
+        };
+        let result = lookup(&dicts, &"ran".into(), &scope).await.unwrap();
+        assert_eq!(result.lemma.as_deref(), Some("run"));
+        assert!(result.markdown.unwrap().starts_with("ran → run\n\n# run\n"));
+
+        // The English rules aren't applied to the other languages
+        let result = lookup(&dicts, &"ran".into(), &Scope::language(Some("de")))
+            .await
+            .unwrap();
+        assert_eq!(result.lemma, None);
+
+        dicts.indexed().await;
+        let result = lookup(&dicts, &"rnu".into(), &Scope::default())
+            .await
+            .unwrap();
+        assert!(result.entries.is_empty());
+        assert_eq!(result.suggestions, vec!["run"]);
+        assert_eq!(
+            result.markdown.unwrap(),
+            "No entry for **rnu**. Did you mean:\n\n- run"
+        );
+
+        let result = lookup(&dicts, &"zzz".into(), &Scope::default())
+            .await
+            .unwrap();
+        assert_eq!(result.markdown, None);
+
+        let scope = Scope {
+            language: None,
+            dictionaries: vec!["WordNet".to_string()],
+        };
+        let result = lookup(&dicts, &"run".into(), &scope).await.unwrap();
+        assert!(result.entries.is_empty());
+    }
+}
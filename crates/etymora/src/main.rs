@@ -1,6 +1,13 @@
+mod config;
+mod detect;
+mod diagnostics;
 mod dict_handler;
 mod error;
+mod lemmatizer;
+mod lookup;
+mod routing;
 mod server;
+mod suggest;
 mod text_document;
 
 use clap::Parser;
@@ -0,0 +1,130 @@
+//! Routing
+//! Route the documents to the dictionaries by their `languageId`s and paths.
+//! The language written in the document, like `lang: de` in the front matter, takes precedence over the one of the route.
+
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Route {
+    /// `languageId`s of the documents like "markdown" and "latex". Any if it's empty
+    pub(crate) language_ids: Vec<String>,
+    /// Globs of the paths like "docs/de/**", relative to the workspace root. Any if it's empty
+    pub(crate) globs: Vec<String>,
+    /// The language(ISO 639-1) of the documents, to choose the dictionaries by their `languages`
+    pub(crate) language: Option<String>,
+    /// The names of the dictionaries used for the documents. All if it's empty
+    pub(crate) dictionaries: Vec<String>,
+}
+
+/// The routes in priority order
+#[derive(Debug, Default)]
+pub(crate) struct Router {
+    routes: Vec<(Route, GlobSet)>,
+}
+
+impl Router {
+    pub(crate) fn new(routes: &[Route]) -> Result<Self, globset::Error> {
+        let routes = routes
+            .iter()
+            .map(|route| {
+                let mut globs = GlobSetBuilder::new();
+                for glob in &route.globs {
+                    globs.add(Glob::new(glob)?);
+                }
+                Ok((route.clone(), globs.build()?))
+            })
+            .collect::<Result<_, globset::Error>>()?;
+
+        Ok(Router { routes })
+    }
+
+    pub(crate) fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter().map(|(route, _)| route)
+    }
+
+    /// The first route which matches the document.
+    /// The globs match the path relative to the root, or the absolute path
+    pub(crate) fn route(
+        &self,
+        language_id: Option<&str>,
+        path: Option<&Path>,
+        root: Option<&Path>,
+    ) -> Option<&Route> {
+        let relative = path
+            .zip(root)
+            .and_then(|(path, root)| path.strip_prefix(root).ok());
+
+        self.routes
+            .iter()
+            .find(|(route, globs)| {
+                let language_id_matches = route.language_ids.is_empty()
+                    || language_id.is_some_and(|id| route.language_ids.iter().any(|l| l == id));
+                let path_matches = route.globs.is_empty()
+                    || relative.is_some_and(|path| globs.is_match(path))
+                    || path.is_some_and(|path| globs.is_match(path));
+
+                language_id_matches && path_matches
+            })
+            .map(|(route, _)| route)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        let routes: Vec<Route> = serde_json::from_value(serde_json::json!([
+            { "globs": ["docs/de/**"], "language": "de", "dictionaries": ["German"] },
+            { "language_ids": ["markdown", "plaintext"], "dictionaries": ["WordNet"] },
+            { "globs": ["/etc/**"], "language": "en" },
+        ]))
+        .unwrap();
+        let router = Router::new(&routes).unwrap();
+        let root = Path::new("/project");
+
+        let route = router
+            .route(
+                Some("markdown"),
+                Some(Path::new("/project/docs/de/intro.md")),
+                Some(root),
+            )
+            .unwrap();
+        assert_eq!(route.language.as_deref(), Some("de"));
+
+        let route = router
+            .route(
+                Some("markdown"),
+                Some(Path::new("/project/README.md")),
+                Some(root),
+            )
+            .unwrap();
+        assert_eq!(route.dictionaries, vec!["WordNet"]);
+
+        // Not relative to the root
+        let route = router
+            .route(Some("conf"), Some(Path::new("/etc/hosts")), Some(root))
+            .unwrap();
+        assert_eq!(route.language.as_deref(), Some("en"));
+
+        assert!(router
+            .route(
+                Some("rust"),
+                Some(Path::new("/project/src/main.rs")),
+                Some(root)
+            )
+            .is_none());
+        assert!(router.route(None, None, None).is_none());
+
+        assert!(Router::new(&[Route {
+            globs: vec!["a/**[".into()],
+            ..Default::default()
+        }])
+        .is_err());
+    }
+}
@@ -1,53 +1,242 @@
 //! Server
 
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
 use either::Either;
+use etymora_traits::fold_case;
 
-use etymora_traits::Dictionary;
 use lsp_server::{
     Connection, ExtractError, IoThreads, Message, Notification, RequestId, Response, ResponseError,
 };
 use lsp_types::{
-    notification::Notification as _,
-    request::{HoverRequest, Request as _},
-    Hover, HoverParams, HoverProviderCapability, InitializeParams, MarkupContent, NumberOrString,
-    ServerCapabilities, WorkDoneProgressReport,
+    notification::{
+        DidChangeConfiguration, DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as _, PublishDiagnostics, ShowMessage,
+    },
+    request::{
+        CodeActionRequest, Completion, HoverRequest, RegisterCapability, Request as _,
+        ResolveCompletionItem, WorkspaceConfiguration,
+    },
+    CodeAction, CodeActionKind, CodeActionOptions, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, CompletionItem, CompletionItemKind,
+    CompletionList, CompletionOptions, CompletionParams, CompletionResponse, CompletionTextEdit,
+    ConfigurationItem, ConfigurationParams, DidChangeConfigurationParams,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    Documentation, Hover, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
+    MarkupContent, MessageType, NumberOrString, Position, Registration, RegistrationParams,
+    ServerCapabilities, ShowMessageParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextEdit, Uri, WorkDoneProgressReport, WorkspaceEdit,
 };
+use rustc_hash::FxHashMap;
+use serde_json::Value;
+use tokio::task::JoinHandle;
 
 use crate::{
-    dict_handler,
+    config::{self, Config, Layers},
+    detect::{Detection, Detector},
+    diagnostics::SpellChecker,
+    dict_handler::{self, Scope},
     error::{EtymoraError, Result},
-    text_document::FileSystem,
+    lookup::{self, Lookup, LookupParams, LookupResult, SUGGESTION_LIMIT},
+    routing::Router,
+    suggest,
+    text_document::{self, Candidates, FileSystem, PositionEncoding, Prefix, SegmentConfig},
 };
 
 use tracing::{debug, info, warn};
 
+/// The max number of completion items
+const COMPLETION_LIMIT: usize = 50;
+/// The max number of characters of the glosses in completion items
+const GLOSS_LENGTH: usize = 60;
+
+/// The section of the settings pulled by `workspace/configuration`
+const CONFIG_SECTION: &str = "etymora";
+
 /// Server State
 pub(crate) struct Etymora {
     connection: Connection,
     io_threads: IoThreads,
+    /// The root of the workspace. The project config file for requests without documents is found from it
+    root: Option<PathBuf>,
+    /// Whether the client supports `workspace/configuration`
+    pull_configuration: bool,
+    layers: RwLock<Layers>,
+    /// The opened documents, shared by the states
+    fs: FileSystem,
+    /// The states for the project config files. `None` is for the documents without them
+    states: RwLock<FxHashMap<Option<PathBuf>, Arc<State>>>,
+    /// The debounced spell checks of the documents
+    checks: std::sync::Mutex<FxHashMap<Uri, JoinHandle<()>>>,
+    next_request_id: AtomicI32,
+    /// The requests sent to the client, waiting for the responses
+    pending: std::sync::Mutex<FxHashMap<RequestId, Pending>>,
+    /// The headwords for suggestions, shared by the states with the same dictionary config
+    headwords: dict_handler::HeadwordCache,
+}
+
+/// The state built from `Config`.
+/// It's replaced as a whole on reconfiguration, and requests keep using the snapshot they started with
+struct State {
     #[allow(dead_code)]
     config: Config,
-    dict: Option<dict_handler::Dicts>,
+    dicts: dict_handler::DictChain,
     fs: FileSystem,
+    spell_checker: SpellChecker,
+    router: Router,
+    detector: Detector,
+}
+
+impl State {
+    async fn new(
+        config: Config,
+        fs: FileSystem,
+        headwords: &dict_handler::HeadwordCache,
+    ) -> Result<State> {
+        let dicts =
+            dict_handler::DictChain::init(&config.dict_config, config.lookup_mode, headwords)
+                .await?;
+        let spell_checker = SpellChecker::new(config.spell_check.clone());
+        let router = Router::new(&config.routes).map_err(EtymoraError::Glob)?;
+        let detector = Detector::new(config.detect_language.clone())?;
+
+        if let Some(name) = router
+            .routes()
+            .flat_map(|route| &route.dictionaries)
+            .find(|name| !dicts.contains(name))
+        {
+            return Err(EtymoraError::UnknownDictionary(name.clone()));
+        }
+
+        Ok(State {
+            config,
+            dicts,
+            fs,
+            spell_checker,
+            router,
+            detector,
+        })
+    }
+
+    /// The dictionaries for the document by the route matching it.
+    /// The language marker in the document takes precedence over the language of the route
+    async fn scope_for(&self, uri: &Uri, root: Option<&Path>) -> Scope {
+        self.scope_at(uri, root, None).await.0
+    }
+
+    /// The scope for the word at the position, whose language is detected in the sentence around it.
+    /// The detection is skipped if the document has the language marker, and the language of the route is used if it isn't confident
+    async fn scope_at(
+        &self,
+        uri: &Uri,
+        root: Option<&Path>,
+        position: Option<&Position>,
+    ) -> (Scope, Option<Detection>) {
+        let language_id = self.fs.language_id(uri).await;
+        let path = text_document::try_from_uri(uri).ok();
+        let route = self
+            .router
+            .route(language_id.as_deref(), path.as_deref(), root);
+
+        let mut scope = Scope {
+            language: route.and_then(|route| route.language.clone()),
+            dictionaries: route
+                .map(|route| route.dictionaries.clone())
+                .unwrap_or_default(),
+        };
+
+        if let Some(language) = self.fs.marker_language(uri).await {
+            scope.language = Some(language);
+            return (scope, None);
+        }
+
+        let detection = match position {
+            Some(position) if self.detector.enabled() => self
+                .fs
+                .read_sentence_uri(uri, position)
+                .await
+                .ok()
+                .and_then(|sentence| self.detector.detect(&sentence)),
+            _ => None,
+        };
+        if let Some(detection) = &detection {
+            debug!(
+                "Detected {} with confidence {:.2}",
+                detection.name, detection.confidence
+            );
+            scope.language = Some(detection.code.to_string());
+        }
+
+        (scope, detection)
+    }
 }
 
-#[derive(Debug, serde::Deserialize, Default)]
-struct Config {
-    dict_config: dict_handler::DictConfigs,
+/// The requests sent to the client
+#[derive(Debug)]
+enum Pending {
+    Configuration,
+    RegisterCapability,
+}
+
+/// `data` of completion items, to look up the word on `completionItem/resolve`
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CompletionData {
+    word: String,
+    language: Option<String>,
+    /// The document, to look up the word with its config
+    uri: Uri,
+}
+
+/// The first line of the gloss, shortened
+fn short_gloss(gloss: &str) -> String {
+    let line = gloss.lines().next().unwrap_or_default().trim();
+    if line.chars().count() > GLOSS_LENGTH {
+        let short: String = line.chars().take(GLOSS_LENGTH - 1).collect();
+        format!("{}…", short.trim_end())
+    } else {
+        line.to_string()
+    }
 }
 
 impl Etymora {
     /// Generate(static) Server Capabilities
     /// `..Default::default()` cannot be used in a const context.
     /// * `HoverProvider` with `WorkDoneProgress`
+    /// * `CompletionProvider` with `completionItem/resolve`
+    /// * `CodeActionProvider` with quick fixes of unknown words
+    /// * `TextDocumentSync` with open/close and incremental changes
+    /// * `PositionEncoding` negotiated with the client
     #[inline]
-    fn gen_server_capabilities() -> ServerCapabilities {
+    fn gen_server_capabilities(position_encoding: PositionEncoding) -> ServerCapabilities {
         ServerCapabilities {
+            position_encoding: Some(position_encoding.into()),
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::INCREMENTAL),
+                    ..Default::default()
+                },
+            )),
             hover_provider: Some(HoverProviderCapability::Options(lsp_types::HoverOptions {
                 work_done_progress_options: lsp_types::WorkDoneProgressOptions {
                     work_done_progress: Some(false),
                 },
             })),
+            completion_provider: Some(CompletionOptions {
+                resolve_provider: Some(true),
+                ..Default::default()
+            }),
+            code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                ..Default::default()
+            })),
             ..Default::default()
         }
     }
@@ -55,12 +244,10 @@ impl Etymora {
     pub(crate) async fn init() -> Result<Etymora> {
         info!("Starting LSP server");
 
-        let server_capabilities = serde_json::to_value(Self::gen_server_capabilities()).unwrap();
-
         let (connection, io_threads) = Connection::stdio();
 
-        let params: InitializeParams = match connection.initialize(server_capabilities) {
-            Ok(it) => serde_json::from_value(it).map_err(EtymoraError::Desirialize)?,
+        let (id, params) = match connection.initialize_start() {
+            Ok(it) => it,
             Err(e) => {
                 if e.channel_is_disconnected() {
                     io_threads.join().map_err(EtymoraError::StdIO)?;
@@ -68,23 +255,193 @@ impl Etymora {
                 return Err(EtymoraError::Protocol(e));
             }
         };
+        let params: InitializeParams =
+            serde_json::from_value(params).map_err(EtymoraError::Desirialize)?;
 
-        let config: Config = if let Some(options) = params.initialization_options {
-            serde_json::from_value(options).map_err(EtymoraError::Desirialize)?
-        } else {
-            warn!("InitializeOptions is empty. Using the default Config");
-            Config::default()
-        };
+        let position_encoding = PositionEncoding::negotiate(
+            params
+                .capabilities
+                .general
+                .as_ref()
+                .and_then(|general| general.position_encodings.as_deref()),
+        );
+        info!("Using {position_encoding:?} as the position encoding");
 
-        let dict = Some(dict_handler::Dicts::init(&config.dict_config).await?);
+        let initialize_result = serde_json::to_value(InitializeResult {
+            capabilities: Self::gen_server_capabilities(position_encoding),
+            server_info: None,
+        })
+        .unwrap();
+
+        if let Err(e) = connection.initialize_finish(id, initialize_result) {
+            if e.channel_is_disconnected() {
+                io_threads.join().map_err(EtymoraError::StdIO)?;
+            }
+            return Err(EtymoraError::Protocol(e));
+        }
+
+        let workspace = params.capabilities.workspace.as_ref();
+        let pull_configuration = workspace.and_then(|w| w.configuration) == Some(true);
+        let register_configuration = workspace
+            .and_then(|w| w.did_change_configuration)
+            .and_then(|d| d.dynamic_registration)
+            == Some(true);
+
+        #[allow(deprecated)]
+        let root = params
+            .workspace_folders
+            .as_ref()
+            .and_then(|folders| folders.first())
+            .map(|folder| &folder.uri)
+            .or(params.root_uri.as_ref())
+            .and_then(|uri| text_document::try_from_uri(uri).ok());
+
+        let init_options = params.initialization_options.unwrap_or_default();
+        if init_options.is_null() {
+            warn!("InitializeOptions is empty");
+        }
+        let (user, user_error) = match read_user_config() {
+            Ok(user) => (user, None),
+            Err(e) => (Value::Null, Some(e)),
+        };
 
-        Ok(Etymora {
+        let etymora = Etymora {
             connection,
             io_threads,
-            config,
-            dict,
-            fs: FileSystem::default(),
-        })
+            root,
+            pull_configuration,
+            layers: RwLock::new(Layers {
+                user,
+                init_options,
+                settings: Value::Null,
+            }),
+            fs: FileSystem::new(position_encoding, SegmentConfig::default())
+                .map_err(EtymoraError::Segment)?,
+            states: Default::default(),
+            checks: Default::default(),
+            next_request_id: AtomicI32::new(0),
+            pending: Default::default(),
+            headwords: Default::default(),
+        };
+
+        if let Some(e) = user_error {
+            etymora.show_message(
+                MessageType::ERROR,
+                format!("etymora: Failed to apply the user config: {e}"),
+            )?;
+        }
+        // The dictionaries for the root are loaded at first
+        etymora.state_in(etymora.root.as_deref()).await?;
+
+        if register_configuration {
+            // Some clients only notify the changes of the registered
+            etymora.send_request::<RegisterCapability>(
+                RegistrationParams {
+                    registrations: vec![Registration {
+                        id: DidChangeConfiguration::METHOD.into(),
+                        method: DidChangeConfiguration::METHOD.into(),
+                        register_options: None,
+                    }],
+                },
+                Pending::RegisterCapability,
+            )?;
+        }
+        if pull_configuration {
+            etymora.request_configuration()?;
+        }
+
+        Ok(etymora)
+    }
+
+    /// The state for the documents in the dir, built on the first use.
+    /// A broken project config file is reported, and the state without it is used
+    async fn state_in(&self, dir: Option<&Path>) -> Result<Arc<State>> {
+        let project = dir.and_then(config::find_project_config);
+        if let Some(state) = self.states.read().unwrap().get(&project) {
+            return Ok(Arc::clone(state));
+        }
+
+        let layers = self.layers.read().unwrap().clone();
+        let state = match (
+            self.build_state(&layers, project.as_deref()).await,
+            &project,
+        ) {
+            (Ok(state), _) => state,
+            (Err(e), Some(path)) => {
+                warn!("Failed to apply {}: {e}", path.display());
+                self.show_message(
+                    MessageType::ERROR,
+                    format!("etymora: Failed to apply {}: {e}", path.display()),
+                )?;
+                self.build_state(&layers, None).await?
+            }
+            (Err(e), None) => return Err(e),
+        };
+
+        if let Some(path) = &project {
+            info!("Using {}", path.display());
+        }
+
+        let state = Arc::new(state);
+        self.states
+            .write()
+            .unwrap()
+            .insert(project, Arc::clone(&state));
+
+        Ok(state)
+    }
+
+    /// The state for the document. Documents which aren't files use the state for the root
+    async fn state_for(&self, uri: &Uri) -> Result<Arc<State>> {
+        let dir = text_document::try_from_uri(uri)
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf));
+
+        self.state_in(dir.as_deref().or(self.root.as_deref())).await
+    }
+
+    /// The state for the document in the notification handlers, which cannot respond errors.
+    /// The error is shown to the user and `None` is returned, so the server keeps running
+    async fn notified_state_for(&self, uri: &Uri) -> Result<Option<Arc<State>>> {
+        match self.state_for(uri).await {
+            Ok(state) => Ok(Some(state)),
+            Err(e) => {
+                warn!("Failed to load the config for {}: {e}", uri.as_str());
+                self.show_message(
+                    MessageType::ERROR,
+                    format!(
+                        "etymora: Failed to load the config for {}: {e}",
+                        uri.as_str()
+                    ),
+                )?;
+                Ok(None)
+            }
+        }
+    }
+
+    async fn build_state(&self, layers: &Layers, project: Option<&Path>) -> Result<State> {
+        let project = match project {
+            Some(path) => {
+                config::read_project_config(path, layers.trusts(path.parent().unwrap_or(path)))?
+            }
+            None => Value::Null,
+        };
+        let config = Config::parse(layers.merge(&project))?;
+
+        let fs = self
+            .fs
+            .reconfigure(config.segment.clone())
+            .map_err(EtymoraError::Segment)?;
+
+        let state = State::new(config, fs, &self.headwords).await?;
+        for (name, e) in state.dicts.failures() {
+            self.show_message(
+                MessageType::WARNING,
+                format!("etymora: Skipped the dictionary {name}: {e}"),
+            )?;
+        }
+
+        Ok(state)
     }
 
     pub(crate) fn shutdown(self) -> Result<()> {
@@ -134,34 +491,377 @@ impl Etymora {
                     Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
                     Err(ExtractError::MethodMismatch(req)) => panic!("{req:?}"),
                 },
+                Completion::METHOD => match cast::<Completion>(req) {
+                    Ok((id, params)) => {
+                        let res = match self.handle_completion(params).await {
+                            Ok(res) => Either::Right(res),
+                            Err(e) => Either::Left(e.into()),
+                        };
+
+                        self.dispacth(res, id)
+                    }
+                    Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
+                    Err(ExtractError::MethodMismatch(req)) => panic!("{req:?}"),
+                },
+                ResolveCompletionItem::METHOD => match cast::<ResolveCompletionItem>(req) {
+                    Ok((id, params)) => {
+                        let res = match self.handle_completion_resolve(params).await {
+                            Ok(res) => Either::Right(Some(res)),
+                            Err(e) => Either::Left(e.into()),
+                        };
+
+                        self.dispacth(res, id)
+                    }
+                    Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
+                    Err(ExtractError::MethodMismatch(req)) => panic!("{req:?}"),
+                },
+                Lookup::METHOD => match cast::<Lookup>(req) {
+                    Ok((id, params)) => {
+                        let res = match self.handle_lookup(params).await {
+                            Ok(res) => Either::Right(Some(res)),
+                            Err(e) => Either::Left(e.into()),
+                        };
+
+                        self.dispacth(res, id)
+                    }
+                    Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
+                    Err(ExtractError::MethodMismatch(req)) => panic!("{req:?}"),
+                },
+                CodeActionRequest::METHOD => match cast::<CodeActionRequest>(req) {
+                    Ok((id, params)) => {
+                        let res = match self.handle_code_action(params).await {
+                            Ok(res) => Either::Right(res),
+                            Err(e) => Either::Left(e.into()),
+                        };
+
+                        self.dispacth(res, id)
+                    }
+                    Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
+                    Err(ExtractError::MethodMismatch(req)) => panic!("{req:?}"),
+                },
+                _ => Ok(()),
+            },
+            Message::Response(resp) => self.handle_response(resp).await,
+            Message::Notification(noti) => match noti.method.as_str() {
+                DidOpenTextDocument::METHOD => {
+                    match cast_notification::<DidOpenTextDocument>(noti) {
+                        Ok(params) => self.handle_did_open(params).await,
+                        Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
+                        Err(ExtractError::MethodMismatch(noti)) => panic!("{noti:?}"),
+                    }
+                }
+                DidChangeTextDocument::METHOD => {
+                    match cast_notification::<DidChangeTextDocument>(noti) {
+                        Ok(params) => self.handle_did_change(params).await,
+                        Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
+                        Err(ExtractError::MethodMismatch(noti)) => panic!("{noti:?}"),
+                    }
+                }
+                DidChangeConfiguration::METHOD => {
+                    match cast_notification::<DidChangeConfiguration>(noti) {
+                        Ok(params) => self.handle_did_change_configuration(params).await,
+                        Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
+                        Err(ExtractError::MethodMismatch(noti)) => panic!("{noti:?}"),
+                    }
+                }
+                DidCloseTextDocument::METHOD => {
+                    match cast_notification::<DidCloseTextDocument>(noti) {
+                        Ok(params) => self.handle_did_close(params).await,
+                        Err(err @ ExtractError::JsonError { .. }) => panic!("{err:?}"),
+                        Err(ExtractError::MethodMismatch(noti)) => panic!("{noti:?}"),
+                    }
+                }
                 _ => Ok(()),
             },
-            Message::Response(_) => Ok(()),
-            Message::Notification(_) => Ok(()),
         }
     }
 
+    pub(crate) async fn handle_response(&self, resp: Response) -> Result<()> {
+        let Some(pending) = self.pending.lock().unwrap().remove(&resp.id) else {
+            return Ok(());
+        };
+
+        if let Some(error) = resp.error {
+            warn!("{pending:?} failed: {}", error.message);
+            return Ok(());
+        }
+
+        match pending {
+            Pending::Configuration => {
+                // The result has the only item for `CONFIG_SECTION`
+                let settings = resp
+                    .result
+                    .and_then(|result| serde_json::from_value::<Vec<Value>>(result).ok())
+                    .and_then(|mut items| items.pop())
+                    .unwrap_or_default();
+
+                self.apply_settings(settings).await
+            }
+            Pending::RegisterCapability => Ok(()),
+        }
+    }
+
+    pub(crate) async fn handle_did_change_configuration(
+        &self,
+        params: DidChangeConfigurationParams,
+    ) -> Result<()> {
+        info!("Configuration changed");
+
+        if self.pull_configuration {
+            // Some clients send only null, so the settings are pulled
+            return self.request_configuration();
+        }
+
+        let settings = match params.settings {
+            Value::Object(mut settings) if settings.contains_key(CONFIG_SECTION) => {
+                settings.remove(CONFIG_SECTION).unwrap()
+            }
+            settings => settings,
+        };
+
+        self.apply_settings(settings).await
+    }
+
+    fn request_configuration(&self) -> Result<()> {
+        self.send_request::<WorkspaceConfiguration>(
+            ConfigurationParams {
+                items: vec![ConfigurationItem {
+                    scope_uri: None,
+                    section: Some(CONFIG_SECTION.into()),
+                }],
+            },
+            Pending::Configuration,
+        )
+    }
+
+    /// Rebuild the states with the settings. The config files are read again.
+    /// The errors are shown to the user, and the current states are kept
+    async fn apply_settings(&self, settings: Value) -> Result<()> {
+        match self.reconfigure(settings).await {
+            Ok(()) => {
+                info!("Applied the configuration");
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Failed to apply the configuration: {e}");
+                self.show_message(
+                    MessageType::ERROR,
+                    format!("etymora: Failed to apply the configuration: {e}"),
+                )
+            }
+        }
+    }
+
+    async fn reconfigure(&self, settings: Value) -> Result<()> {
+        let mut layers = self.layers.read().unwrap().clone();
+        layers.user = read_user_config()?;
+        layers.settings = settings;
+
+        // The state for the root is built first to validate the config
+        let project = self.root.as_deref().and_then(config::find_project_config);
+        let state = Arc::new(self.build_state(&layers, project.as_deref()).await?);
+
+        *self.layers.write().unwrap() = layers;
+        {
+            let mut states = self.states.write().unwrap();
+            states.clear();
+            states.insert(project, state);
+        }
+
+        // Check the opened documents again with the new dictionaries
+        for (_, check) in self.checks.lock().unwrap().drain() {
+            check.abort();
+        }
+        // The diagnostics of the documents whose config fails are cleared, not to be stale
+        for uri in self.fs.opened().await {
+            match self.notified_state_for(&uri).await? {
+                Some(state) if state.spell_checker.config().enabled => {
+                    self.schedule_spell_check(uri).await?;
+                }
+                _ => self.publish_diagnostics(uri, vec![], None)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn handle_did_open(&self, params: DidOpenTextDocumentParams) -> Result<()> {
+        debug!("Opened {}", params.text_document.uri.as_str());
+
+        self.fs
+            .open(
+                params.text_document.uri.clone(),
+                &params.text_document.text,
+                params.text_document.version,
+                &params.text_document.language_id,
+            )
+            .await;
+
+        self.schedule_spell_check(params.text_document.uri).await
+    }
+
+    pub(crate) async fn handle_did_change(
+        &self,
+        params: DidChangeTextDocumentParams,
+    ) -> Result<()> {
+        if let Err(e) = self
+            .fs
+            .change(
+                &params.text_document.uri,
+                params.text_document.version,
+                params.content_changes,
+            )
+            .await
+        {
+            // Notifications cannot respond errors
+            warn!(
+                "Failed to apply changes to {}: {e}",
+                params.text_document.uri.as_str()
+            );
+        }
+
+        self.schedule_spell_check(params.text_document.uri).await
+    }
+
+    pub(crate) async fn handle_did_close(&self, params: DidCloseTextDocumentParams) -> Result<()> {
+        debug!("Closed {}", params.text_document.uri.as_str());
+
+        self.fs.close(&params.text_document.uri).await;
+
+        if let Some(check) = self
+            .checks
+            .lock()
+            .unwrap()
+            .remove(&params.text_document.uri)
+        {
+            check.abort();
+        }
+        // Clear the diagnostics of the closed document, which may be published with any config
+        self.publish_diagnostics(params.text_document.uri, vec![], None)?;
+
+        Ok(())
+    }
+
+    /// Check the document after the debounce, cancelling the previous check of it.
+    /// The diagnostics are only published if the document isn't changed while checking
+    async fn schedule_spell_check(&self, uri: Uri) -> Result<()> {
+        let Some(state) = self.notified_state_for(&uri).await? else {
+            return Ok(());
+        };
+        if !state.spell_checker.config().enabled {
+            return Ok(());
+        }
+
+        let sender = self.connection.sender.clone();
+        let debounce = Duration::from_millis(state.spell_checker.config().debounce);
+        let scope = state.scope_for(&uri, self.root.as_deref()).await;
+
+        let task_uri = uri.clone();
+        let check = tokio::spawn(async move {
+            let uri = task_uri;
+            tokio::time::sleep(debounce).await;
+
+            let Some((version, words)) = state.fs.checked_words_uri(&uri).await else {
+                return;
+            };
+
+            let diagnostics = match state.spell_checker.check(words, &state.dicts, &scope).await {
+                Ok(diagnostics) => diagnostics,
+                Err(e) => {
+                    warn!("Failed to check {}: {e}", uri.as_str());
+                    return;
+                }
+            };
+
+            if state.fs.version(&uri).await != Some(version) {
+                debug!("{} is changed while checking", uri.as_str());
+                return;
+            }
+
+            debug!(
+                "Found {} unknown words in {}",
+                diagnostics.len(),
+                uri.as_str()
+            );
+
+            if let Err(e) = sender.send(Message::Notification(diagnostics_notification(
+                uri,
+                diagnostics,
+                Some(version),
+            ))) {
+                warn!("Failed to publish diagnostics: {e}");
+            }
+        });
+
+        if let Some(previous) = self.checks.lock().unwrap().insert(uri, check) {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    fn publish_diagnostics(
+        &self,
+        uri: Uri,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+        version: Option<i32>,
+    ) -> Result<()> {
+        self.connection
+            .sender
+            .send(Message::Notification(diagnostics_notification(
+                uri,
+                diagnostics,
+                version,
+            )))
+            .map_err(|e| EtymoraError::SendMessage(e.0))
+    }
+
     pub(crate) async fn handle_hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         info!("Handling hover");
 
-        let word = self
+        let uri = &params.text_document_position_params.text_document.uri;
+        let state = self.state_for(uri).await?;
+
+        let Candidates {
+            spans: mut candidates,
+            language,
+        } = state
             .fs
-            .read_word_uri(
-                &params.text_document_position_params.text_document.uri,
-                &params.text_document_position_params.position,
-            )
+            .read_candidates_uri(uri, &params.text_document_position_params.position)
             .await
             .map_err(EtymoraError::Fs)?;
 
-        if word.is_none() | self.dict.is_none() {
+        if candidates.is_empty() | state.dicts.is_empty() {
             // ワードがない場合はなにもなく返す
             info!("No word or dictionary found");
             return Ok(None);
         }
 
-        let word = word.unwrap();
+        // 文字種で言語が分からない場合は文から推定する
+        let (scope, detection) = state
+            .scope_at(
+                uri,
+                self.root.as_deref(),
+                language
+                    .is_none()
+                    .then_some(&params.text_document_position_params.position),
+            )
+            .await;
+        let scope = scope.with_language(language);
 
-        let desc = self.dict.as_ref().unwrap().lookup_ditail(&word).await?;
+        // 最後の候補(単語)は存在を確認せずに引く
+        let mut span = candidates.pop().unwrap();
+        for phrase in candidates {
+            if state.dicts.exits(&phrase.word, &scope).await? {
+                debug!("Found the phrase: {}", phrase.word);
+                span = phrase;
+                break;
+            }
+        }
+
+        let desc = lookup::lookup(&state.dicts, &span.word, &scope)
+            .await?
+            .markdown;
 
         if desc.is_none() {
             // 説明がない場合はなにもなく返す
@@ -169,19 +869,221 @@ impl Etymora {
             return Ok(None);
         }
 
+        let desc = match detection {
+            Some(detection) => format!("*{} (detected)*\n\n{}", detection.name, desc.unwrap()),
+            None => desc.unwrap(),
+        };
+
         let desc = lsp_types::HoverContents::Markup(MarkupContent {
             kind: lsp_types::MarkupKind::Markdown,
-            value: desc.unwrap(),
+            value: desc,
         });
 
         let resp = Hover {
             contents: desc,
-            range: None, // TODO: `read_word_uri` の構造を変えないと対応できない
+            range: Some(span.range),
         };
 
         Ok(Some(resp))
     }
 
+    pub(crate) async fn handle_completion(
+        &self,
+        params: CompletionParams,
+    ) -> Result<Option<CompletionResponse>> {
+        info!("Handling completion");
+
+        let uri = params.text_document_position.text_document.uri;
+        let state = self.state_for(&uri).await?;
+
+        let Some(Prefix { span, language }) = state
+            .fs
+            .read_prefix_uri(&uri, &params.text_document_position.position)
+            .await
+            .map_err(EtymoraError::Fs)?
+        else {
+            return Ok(None);
+        };
+
+        let scope = state
+            .scope_for(&uri, self.root.as_deref())
+            .await
+            .with_language(language);
+
+        let completions = state
+            .dicts
+            .prefix_search(&span.word, &scope, COMPLETION_LIMIT)
+            .await?;
+
+        debug!("Found {} words for {}", completions.len(), span.word);
+
+        let items = completions
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.word.clone(),
+                kind: Some(CompletionItemKind::TEXT),
+                detail: completion.gloss.as_deref().map(short_gloss),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                    span.range,
+                    completion.word.clone(),
+                ))),
+                data: Some(
+                    serde_json::to_value(CompletionData {
+                        word: completion.word,
+                        language: scope.language.clone(),
+                        uri: uri.clone(),
+                    })
+                    .unwrap(),
+                ),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Some(CompletionResponse::List(CompletionList {
+            // More words are found as the prefix gets longer
+            is_incomplete: items.len() >= COMPLETION_LIMIT,
+            items,
+        })))
+    }
+
+    /// Look up the word of the completion item for its documentation
+    pub(crate) async fn handle_completion_resolve(
+        &self,
+        mut item: CompletionItem,
+    ) -> Result<CompletionItem> {
+        info!("Handling completionItem/resolve");
+
+        let Some(data) = item.data.clone() else {
+            return Ok(item);
+        };
+        let data: CompletionData =
+            serde_json::from_value(data).map_err(EtymoraError::Desirialize)?;
+        let state = self.state_for(&data.uri).await?;
+        let scope = state
+            .scope_for(&data.uri, self.root.as_deref())
+            .await
+            .with_language(data.language.as_deref());
+
+        if let Some(desc) = state
+            .dicts
+            .lookup_ditail(&data.word.as_str().into(), &scope)
+            .await?
+        {
+            item.documentation = Some(Documentation::MarkupContent(MarkupContent {
+                kind: lsp_types::MarkupKind::Markdown,
+                value: desc,
+            }));
+        }
+
+        Ok(item)
+    }
+
+    /// Look up the word given by the client, not in a text document
+    pub(crate) async fn handle_lookup(&self, params: LookupParams) -> Result<LookupResult> {
+        info!("Handling {}", Lookup::METHOD);
+
+        let state = self.state_in(self.root.as_deref()).await?;
+
+        if let Some(name) = params
+            .dictionary
+            .as_ref()
+            .filter(|name| !state.dicts.contains(name))
+        {
+            return Err(EtymoraError::UnknownDictionary(name.clone()));
+        }
+
+        let word = fold_case(&params.word).into();
+        let mut scope = Scope::language(params.language.as_deref());
+        scope.dictionaries.extend(params.dictionary);
+
+        lookup::lookup(&state.dicts, &word, &scope).await
+    }
+
+    /// Quick fixes replacing the unknown words of the spell-check diagnostics with the suggestions
+    pub(crate) async fn handle_code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> Result<Option<CodeActionResponse>> {
+        info!("Handling code action");
+
+        let uri = params.text_document.uri;
+        let state = self.state_for(&uri).await?;
+        let scope = state.scope_for(&uri, self.root.as_deref()).await;
+
+        let mut actions = vec![];
+
+        for diagnostic in params
+            .context
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.source.as_deref() == Some("etymora"))
+        {
+            // Diagnostics may be stale after the edits which aren't checked yet
+            let Ok(original) = state.fs.read_range_uri(&uri, &diagnostic.range).await else {
+                debug!("Skipped the stale diagnostic at {:?}", diagnostic.range);
+                continue;
+            };
+            let word = fold_case(&original);
+
+            for (i, suggestion) in state
+                .dicts
+                .suggest(&word.into(), &scope, SUGGESTION_LIMIT)
+                .into_iter()
+                .enumerate()
+            {
+                let replacement = suggest::match_case(&original, &suggestion);
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Replace with \"{replacement}\""),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(
+                            [(
+                                uri.clone(),
+                                vec![TextEdit::new(diagnostic.range, replacement)],
+                            )]
+                            .into_iter()
+                            .collect(),
+                        ),
+                        ..Default::default()
+                    }),
+                    is_preferred: Some(i == 0),
+                    ..Default::default()
+                }));
+            }
+        }
+
+        Ok(Some(actions))
+    }
+
+    /// Send the request to the client. The response is handled by `handle_response`
+    fn send_request<R>(&self, params: R::Params, pending: Pending) -> Result<()>
+    where
+        R: lsp_types::request::Request,
+    {
+        let id = RequestId::from(self.next_request_id.fetch_add(1, Ordering::Relaxed));
+        self.pending.lock().unwrap().insert(id.clone(), pending);
+
+        self.connection
+            .sender
+            .send(Message::Request(lsp_server::Request::new(
+                id,
+                R::METHOD.into(),
+                params,
+            )))
+            .map_err(|e| EtymoraError::SendMessage(e.0))
+    }
+
+    fn show_message(&self, typ: MessageType, message: String) -> Result<()> {
+        self.connection
+            .sender
+            .send(Message::Notification(Notification {
+                method: ShowMessage::METHOD.into(),
+                params: serde_json::to_value(ShowMessageParams { typ, message }).unwrap(),
+            }))
+            .map_err(|e| EtymoraError::SendMessage(e.0))
+    }
+
     fn dispacth<R>(&self, res: Either<ResponseError, Option<R>>, id: RequestId) -> Result<()>
     where
         R: serde::Serialize,
@@ -274,6 +1176,30 @@ impl Etymora {
     }
 }
 
+fn diagnostics_notification(
+    uri: Uri,
+    diagnostics: Vec<lsp_types::Diagnostic>,
+    version: Option<i32>,
+) -> Notification {
+    Notification {
+        method: PublishDiagnostics::METHOD.into(),
+        params: serde_json::to_value(lsp_types::PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version,
+        })
+        .unwrap(),
+    }
+}
+
+/// Read the user config file. It's optional
+fn read_user_config() -> Result<Value> {
+    match config::user_config_path() {
+        Some(path) => config::read_config_file(&path),
+        None => Ok(Value::Null),
+    }
+}
+
 fn cast<R>(
     req: lsp_server::Request,
 ) -> std::result::Result<(lsp_server::RequestId, R::Params), ExtractError<lsp_server::Request>>
@@ -283,3 +1209,29 @@ where
 {
     req.extract(R::METHOD)
 }
+
+fn cast_notification<N>(
+    noti: lsp_server::Notification,
+) -> std::result::Result<N::Params, ExtractError<lsp_server::Notification>>
+where
+    N: lsp_types::notification::Notification,
+    N::Params: serde::de::DeserializeOwned,
+{
+    noti.extract(N::METHOD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_gloss() {
+        assert_eq!(short_gloss("  A fruit.\nIt grows on trees."), "A fruit.");
+        assert_eq!(short_gloss(""), "");
+
+        let long = "word ".repeat(20);
+        let short = short_gloss(&long);
+        assert_eq!(short.chars().count(), GLOSS_LENGTH);
+        assert!(short.ends_with("word…"));
+    }
+}
@@ -0,0 +1,210 @@
+//! Suggestions
+//! Find the headwords close to a misspelled word in the edit distance, with a BK-tree of them.
+//! The distance is the optimal string alignment distance, which counts a transposition as one edit.
+
+/// The max edit distance of the suggestions for the word
+pub(crate) fn max_distance(word: &str) -> usize {
+    if word.chars().count() <= 4 {
+        1
+    } else {
+        2
+    }
+}
+
+/// The optimal string alignment distance of the chars
+fn distance(a: &[char], b: &[char]) -> usize {
+    // The rows for the previous two chars of `a`
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[derive(Debug)]
+struct Node {
+    word: String,
+    /// The distances to the children and their indices
+    children: Vec<(usize, usize)>,
+}
+
+/// BK-tree of words
+#[derive(Debug, Default)]
+pub(crate) struct BkTree {
+    nodes: Vec<Node>,
+}
+
+impl BkTree {
+    pub(crate) fn insert(&mut self, word: String) {
+        let chars: Vec<char> = word.chars().collect();
+
+        if self.nodes.is_empty() {
+            self.nodes.push(Node {
+                word,
+                children: vec![],
+            });
+            return;
+        }
+
+        let mut index = 0;
+        loop {
+            let node_chars: Vec<char> = self.nodes[index].word.chars().collect();
+            let d = distance(&chars, &node_chars);
+            if d == 0 {
+                return;
+            }
+
+            match self.nodes[index]
+                .children
+                .iter()
+                .find(|(child_distance, _)| *child_distance == d)
+            {
+                Some((_, child)) => index = *child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node {
+                        word,
+                        children: vec![],
+                    });
+                    self.nodes[index].children.push((d, child));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// The words within `max_distance` from the word, with their distances. Not sorted
+    pub(crate) fn find(&self, word: &str, max_distance: usize) -> Vec<(usize, &str)> {
+        let chars: Vec<char> = word.chars().collect();
+
+        let mut found = vec![];
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let node_chars: Vec<char> = node.word.chars().collect();
+            let d = distance(&chars, &node_chars);
+            if d <= max_distance {
+                found.push((d, node.word.as_str()));
+            }
+
+            // The triangle inequality
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|(child_distance, _)| child_distance.abs_diff(d) <= max_distance)
+                    .map(|(_, child)| *child),
+            );
+        }
+
+        found
+    }
+}
+
+impl FromIterator<String> for BkTree {
+    fn from_iter<T: IntoIterator<Item = String>>(iter: T) -> Self {
+        let mut tree = BkTree::default();
+        for word in iter {
+            tree.insert(word);
+        }
+        tree
+    }
+}
+
+/// Restore the case of the word in the text to the suggestion, which is case folded
+pub(crate) fn match_case(original: &str, suggestion: &str) -> String {
+    let mut chars = original.chars();
+    let is_upper = |c: char| c.is_uppercase();
+
+    if original.chars().count() > 1 && original.chars().filter(|c| c.is_alphabetic()).all(is_upper)
+    {
+        suggestion.to_uppercase()
+    } else if chars.next().is_some_and(is_upper) {
+        let mut suggestion_chars = suggestion.chars();
+        match suggestion_chars.next() {
+            Some(first) => first.to_uppercase().chain(suggestion_chars).collect(),
+            None => String::new(),
+        }
+    } else {
+        suggestion.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(a: &str, b: &str) -> usize {
+        distance(
+            &a.chars().collect::<Vec<_>>(),
+            &b.chars().collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(d("word", "word"), 0);
+        assert_eq!(d("wrod", "word"), 1);
+        assert_eq!(d("wor", "word"), 1);
+        assert_eq!(d("kitten", "sitting"), 3);
+        assert_eq!(d("", "abc"), 3);
+        assert_eq!(d("ことば", "こどば"), 1);
+    }
+
+    #[test]
+    fn test_bk_tree() {
+        let tree: BkTree = [
+            "book", "books", "cake", "boo", "cape", "cart", "boon", "cook",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+        let mut found = tree.find("bo", 1);
+        found.sort();
+        assert_eq!(found, vec![(1, "boo")]);
+
+        let mut found = tree.find("boko", 1);
+        found.sort();
+        assert_eq!(found, vec![(1, "boo"), (1, "book")]);
+
+        let mut found = tree.find("caqe", 2);
+        found.sort();
+        assert_eq!(found, vec![(1, "cake"), (1, "cape"), (2, "cart")]);
+
+        assert!(BkTree::default().find("word", 2).is_empty());
+    }
+
+    #[test]
+    fn test_match_case() {
+        assert_eq!(match_case("wrod", "word"), "word");
+        assert_eq!(match_case("Wrod", "word"), "Word");
+        assert_eq!(match_case("WROD", "word"), "WORD");
+        assert_eq!(match_case("I", "a"), "A");
+    }
+
+    #[test]
+    fn test_max_distance() {
+        assert_eq!(max_distance("wrod"), 1);
+        assert_eq!(max_distance("recieve"), 2);
+    }
+}
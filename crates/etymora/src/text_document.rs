@@ -1,18 +1,33 @@
 //! Text Document Module
 //! For extracting words from text documents.
 //! Exstract a word from a line.
-//! TODO: multiple word for some English idioms
+//! Documents opened in the editor are kept in memory and read from there,
+//! others are read from the disk.
+//! The `character` of positions is counted in the negotiated `PositionEncoding`.
+//! Phrases around the cursor like "give up" are proposed as candidates with the word.
+//! CJK text is segmented by the analyzer of its language, and Japanese words are looked up in their base forms.
 
-use etymora_traits::Word;
-use lsp_types::Position;
+mod check;
+mod marker;
+mod segment;
+mod syntax;
+
+pub(crate) use segment::{SegmentConfig, SegmentError};
+
+use segment::{CjkLanguage, CjkSegmenter, Segmenters, Token};
+
+use etymora_traits::{fold_case, WordSpan};
+use lsp_types::{Position, PositionEncodingKind, TextDocumentContentChangeEvent, Uri};
+use ropey::Rope;
 use rustc_hash::FxHashMap;
 
-use std::{path::PathBuf, sync::Arc};
+use std::{ops::Range, path::PathBuf, sync::Arc};
 use tokio::{
     fs,
     io::{AsyncBufReadExt, AsyncSeekExt, BufReader, SeekFrom},
     sync::RwLock,
 };
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum FsError {
@@ -22,14 +37,205 @@ pub(crate) enum FsError {
     IoError(#[source] tokio::io::Error),
     #[error("Wrong position")]
     WrongPosition,
+    #[error("The document is not opened")]
+    NotOpened,
+}
+
+/// The encoding of `Position::character`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum PositionEncoding {
+    Utf8,
+    /// The default of LSP
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Choose the encoding from the client's `general.positionEncodings`.
+    /// The client's order of preference is respected, and UTF-16 is used if the client doesn't support others.
+    pub(crate) fn negotiate(client_encodings: Option<&[PositionEncodingKind]>) -> Self {
+        client_encodings
+            .unwrap_or_default()
+            .iter()
+            .find_map(|kind| Self::try_from(kind).ok())
+            .unwrap_or_default()
+    }
+
+    /// The length of the char in this encoding
+    fn len_char(self, c: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => c.len_utf8(),
+            PositionEncoding::Utf16 => c.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+
+    /// Convert the `character` of a position to the char index in the line.
+    /// A position pointing to the middle of a char is treated as the char,
+    /// and a position beyond the end of the line is treated as the end of the line.
+    pub(crate) fn char_index(self, line: &str, character: u32) -> usize {
+        let character = character as usize;
+        let mut offset = 0;
+        for (i, c) in line.chars().enumerate() {
+            offset += self.len_char(c);
+            if offset > character {
+                return i;
+            }
+        }
+        line.chars().count()
+    }
+
+    /// Convert the char index in the line to the `character` of a position
+    pub(crate) fn character(self, line: &str, char_index: usize) -> u32 {
+        line.chars()
+            .take(char_index)
+            .map(|c| self.len_char(c))
+            .sum::<usize>() as u32
+    }
+}
+
+impl TryFrom<&PositionEncodingKind> for PositionEncoding {
+    type Error = ();
+
+    fn try_from(value: &PositionEncodingKind) -> Result<Self, Self::Error> {
+        if *value == PositionEncodingKind::UTF8 {
+            Ok(PositionEncoding::Utf8)
+        } else if *value == PositionEncodingKind::UTF16 {
+            Ok(PositionEncoding::Utf16)
+        } else if *value == PositionEncodingKind::UTF32 {
+            Ok(PositionEncoding::Utf32)
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl From<PositionEncoding> for PositionEncodingKind {
+    fn from(value: PositionEncoding) -> Self {
+        match value {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+            PositionEncoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+}
+
+/// A document opened in the editor
+#[derive(Debug)]
+struct Document {
+    text: Rope,
+    version: i32,
+    /// `languageId` given by the client, like "markdown" and "latex"
+    language_id: String,
+    /// Locked to parse on reads
+    syntax: std::sync::Mutex<syntax::SyntaxTree>,
+    /// Whether the text has kana, which makes the CJK text Japanese
+    has_kana: bool,
+}
+
+impl Document {
+    fn new(text: &str, version: i32, language_id: &str) -> Self {
+        Self {
+            text: Rope::from_str(text),
+            version,
+            language_id: language_id.to_string(),
+            syntax: Default::default(),
+            has_kana: text.chars().any(segment::is_kana),
+        }
+    }
+
+    /// Apply a `didChange` content change(incremental or full)
+    fn apply_change(
+        &mut self,
+        change: TextDocumentContentChangeEvent,
+        encoding: PositionEncoding,
+    ) -> Result<(), FsError> {
+        match change.range {
+            Some(range) => {
+                let start = self.char_index(&range.start, encoding)?;
+                let end = self.char_index(&range.end, encoding)?;
+                if start > end {
+                    return Err(FsError::WrongPosition);
+                }
+                self.syntax
+                    .get_mut()
+                    .unwrap()
+                    .edit(&self.text, start, end, &change.text);
+                // The whole text is scanned again only when kana is removed
+                let removes_kana =
+                    self.has_kana && self.text.slice(start..end).chars().any(segment::is_kana);
+                self.text.remove(start..end);
+                self.text.insert(start, &change.text);
+
+                if change.text.chars().any(segment::is_kana) {
+                    self.has_kana = true;
+                } else if removes_kana {
+                    self.has_kana = self.text.chars().any(segment::is_kana);
+                }
+            }
+            None => {
+                self.syntax.get_mut().unwrap().reset();
+                self.text = Rope::from_str(&change.text);
+                self.has_kana = change.text.chars().any(segment::is_kana);
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert the position to a char index of the rope
+    fn char_index(
+        &self,
+        position: &Position,
+        encoding: PositionEncoding,
+    ) -> Result<usize, FsError> {
+        let line = position.line as usize;
+        if line > self.text.len_lines() {
+            return Err(FsError::WrongPosition);
+        }
+        if line == self.text.len_lines() {
+            // The position just after the last line
+            return Ok(self.text.len_chars());
+        }
+
+        let line_start = self.text.line_to_char(line);
+        let content = line_content(self.text.line(line));
+        Ok(line_start + encoding.char_index(&content, position.character))
+    }
+
+    /// Convert the position to a byte offset of the text
+    fn byte_offset(
+        &self,
+        position: &Position,
+        encoding: PositionEncoding,
+    ) -> Result<usize, FsError> {
+        Ok(self.text.char_to_byte(self.char_index(position, encoding)?))
+    }
+
+    fn line(&self, line: u32) -> Option<String> {
+        let line = line as usize;
+        if line >= self.text.len_lines() {
+            return None;
+        }
+        Some(line_content(self.text.line(line)))
+    }
+}
+
+/// Strip the line break from a line of rope
+fn line_content(line: ropey::RopeSlice<'_>) -> String {
+    line.to_string().trim_end_matches(['\n', '\r']).to_string()
 }
 
 #[derive(Debug, Default)]
 pub(crate) struct FileSystem {
     map: Arc<RwLock<FxHashMap<PathBuf, fs::File>>>,
+    documents: Arc<RwLock<FxHashMap<Uri, Document>>>,
+    encoding: PositionEncoding,
+    segment: SegmentConfig,
+    segmenters: Segmenters,
 }
 
-fn try_from_uri(value: &lsp_types::Uri) -> Result<PathBuf, FsError> {
+pub(crate) fn try_from_uri(value: &lsp_types::Uri) -> Result<PathBuf, FsError> {
     if value
         .scheme()
         .map(|s| s.to_lowercase().as_str().to_string())
@@ -42,6 +248,81 @@ fn try_from_uri(value: &lsp_types::Uri) -> Result<PathBuf, FsError> {
 }
 
 impl FileSystem {
+    pub(crate) fn new(
+        encoding: PositionEncoding,
+        segment: SegmentConfig,
+    ) -> Result<Self, SegmentError> {
+        Ok(Self {
+            encoding,
+            segmenters: Segmenters::new(&segment)?,
+            segment,
+            ..Default::default()
+        })
+    }
+
+    /// A `FileSystem` with the new segment config, sharing the documents with this
+    pub(crate) fn reconfigure(&self, segment: SegmentConfig) -> Result<Self, SegmentError> {
+        Ok(Self {
+            map: Arc::clone(&self.map),
+            documents: Arc::clone(&self.documents),
+            encoding: self.encoding,
+            segmenters: Segmenters::new(&segment)?,
+            segment,
+        })
+    }
+
+    /// The URIs of the opened documents
+    pub(crate) async fn opened(&self) -> Vec<Uri> {
+        self.documents.read().await.keys().cloned().collect()
+    }
+
+    /// Handle `textDocument/didOpen`
+    pub(crate) async fn open(&self, uri: Uri, text: &str, version: i32, language_id: &str) {
+        self.documents
+            .write()
+            .await
+            .insert(uri, Document::new(text, version, language_id));
+    }
+
+    /// Handle `textDocument/didChange`
+    pub(crate) async fn change(
+        &self,
+        uri: &Uri,
+        version: i32,
+        changes: Vec<TextDocumentContentChangeEvent>,
+    ) -> Result<(), FsError> {
+        let mut documents = self.documents.write().await;
+        let document = documents.get_mut(uri).ok_or(FsError::NotOpened)?;
+
+        if version <= document.version {
+            tracing::warn!(
+                "Got an outdated change(version {version}, current {})",
+                document.version
+            );
+        }
+
+        for change in changes {
+            document.apply_change(change, self.encoding)?;
+        }
+        document.version = version;
+
+        Ok(())
+    }
+
+    /// Handle `textDocument/didClose`
+    pub(crate) async fn close(&self, uri: &Uri) {
+        self.documents.write().await.remove(uri);
+    }
+
+    /// Read the line from the opened document, or from the disk if it isn't opened
+    async fn read_line_uri(&self, uri: &Uri, position: &Position) -> Result<String, FsError> {
+        if let Some(document) = self.documents.read().await.get(uri) {
+            return document.line(position.line).ok_or(FsError::WrongPosition);
+        }
+
+        self.read_line(&try_from_uri(uri)?, position).await
+    }
+
     async fn read_line(&self, path: &PathBuf, position: &Position) -> Result<String, FsError> {
         let mut map = self.map.write().await;
         if !map.contains_key(path) {
@@ -73,51 +354,339 @@ impl FileSystem {
         Err(FsError::WrongPosition)
     }
 
-    /// A wrapped function for `read_word`
-    pub(crate) async fn read_word_uri(
+    /// The byte ranges of the prose in the document, like comments and strings of source code.
+    /// `None` if all of it is prose
+    fn prose_ranges(&self, document: &Document) -> Option<Vec<Range<usize>>> {
+        if !self.segment.syntax {
+            return None;
+        }
+
+        document
+            .syntax
+            .lock()
+            .unwrap()
+            .prose_ranges(&document.language_id, &document.text)
+    }
+
+    /// Whether the position is in the prose of the opened document. Documents not opened are prose
+    async fn is_prose(&self, uri: &Uri, position: &Position) -> Result<bool, FsError> {
+        let documents = self.documents.read().await;
+        let Some(document) = documents.get(uri) else {
+            return Ok(true);
+        };
+        let Some(ranges) = self.prose_ranges(document) else {
+            return Ok(true);
+        };
+
+        Ok(syntax::in_prose(
+            &ranges,
+            document.byte_offset(position, self.encoding)?,
+        ))
+    }
+
+    /// Read the candidates at the position from the opened document or the disk.
+    /// No candidate in the code of source code
+    pub(crate) async fn read_candidates_uri(
         &self,
         uri: &lsp_types::Uri,
         position: &Position,
-    ) -> Result<Option<Word>, FsError> {
-        self.read_word(&try_from_uri(uri)?, position).await
+    ) -> Result<Candidates, FsError> {
+        if !self.is_prose(uri, position).await? {
+            return Ok(Candidates {
+                spans: vec![],
+                language: None,
+            });
+        }
+
+        let line = self.read_line_uri(uri, position).await?;
+        let japanese = self.is_japanese(uri, &line).await;
+
+        let cjk = match self.segment.cjk {
+            CjkLanguage::Off => None,
+            _ => japanese.and_then(|japanese| self.segmenters.get(japanese)),
+        };
+
+        let spans = extract_candidates_from_line(line, position, self.encoding, &self.segment, cjk);
+        let language = spans
+            .last()
+            .and_then(|span| script_language(span.word.as_str(), japanese == Some(true)));
+
+        Ok(Candidates { spans, language })
     }
 
-    /// read word
-    pub(crate) async fn read_word(
+    /// Read the text in the range, which is in a line
+    pub(crate) async fn read_range_uri(
         &self,
-        path: &PathBuf,
+        uri: &Uri,
+        range: &lsp_types::Range,
+    ) -> Result<String, FsError> {
+        if range.start.line != range.end.line || range.start.character > range.end.character {
+            return Err(FsError::WrongPosition);
+        }
+
+        let line = self.read_line_uri(uri, &range.start).await?;
+        let start = self.encoding.char_index(&line, range.start.character);
+        let end = self.encoding.char_index(&line, range.end.character);
+
+        Ok(line.chars().skip(start).take(end - start).collect())
+    }
+
+    /// Read the sentence at the position in its line, to detect the language of it
+    pub(crate) async fn read_sentence_uri(
+        &self,
+        uri: &Uri,
         position: &Position,
-    ) -> Result<Option<Word>, FsError> {
-        Ok(extract_word_from_line(
-            self.read_line(path, position).await?,
-            position,
-        ))
+    ) -> Result<String, FsError> {
+        let line = self.read_line_uri(uri, position).await?;
+        let cursor = self.encoding.char_index(&line, position.character);
+
+        Ok(sentence_at(&line, cursor).to_string())
     }
+
+    /// `languageId` of the opened document
+    pub(crate) async fn language_id(&self, uri: &Uri) -> Option<String> {
+        self.documents
+            .read()
+            .await
+            .get(uri)
+            .map(|document| document.language_id.clone())
+    }
+
+    /// The language(ISO 639-1) written in the opened document, like `lang: de` in the front matter
+    pub(crate) async fn marker_language(&self, uri: &Uri) -> Option<String> {
+        let documents = self.documents.read().await;
+        let document = documents.get(uri)?;
+
+        marker::marker_language(document.text.lines().map(line_content))
+    }
+
+    /// The version of the opened document
+    pub(crate) async fn version(&self, uri: &Uri) -> Option<i32> {
+        self.documents
+            .read()
+            .await
+            .get(uri)
+            .map(|document| document.version)
+    }
+
+    /// The words to be spell-checked in the opened document, with the version of it
+    pub(crate) async fn checked_words_uri(&self, uri: &Uri) -> Option<(i32, Vec<WordSpan>)> {
+        let documents = self.documents.read().await;
+        let document = documents.get(uri)?;
+
+        let lines = (0..document.text.len_lines()).map(|i| line_content(document.text.line(i)));
+        let mut words = check::checked_words(lines, self.encoding, &self.segment);
+
+        if let Some(ranges) = self.prose_ranges(document) {
+            words.retain(|span| {
+                document
+                    .byte_offset(&span.range.start, self.encoding)
+                    .is_ok_and(|offset| syntax::in_prose(&ranges, offset))
+            });
+        }
+
+        Some((document.version, words))
+    }
+
+    /// Read the partial word before the cursor to complete. `None` if the cursor isn't after a word
+    pub(crate) async fn read_prefix_uri(
+        &self,
+        uri: &lsp_types::Uri,
+        position: &Position,
+    ) -> Result<Option<Prefix>, FsError> {
+        if !self.is_prose(uri, position).await? {
+            return Ok(None);
+        }
+
+        let line = self.read_line_uri(uri, position).await?;
+        let japanese = self.is_japanese(uri, &line).await;
+
+        Ok(
+            extract_prefix_from_line(&line, position, self.encoding, &self.segment).map(|span| {
+                let language = script_language(span.word.as_str(), japanese == Some(true));
+                Prefix { span, language }
+            }),
+        )
+    }
+
+    /// Whether the CJK text in the line is Japanese, or Chinese. `None` if the line has no CJK.
+    /// Without the language in the config, the document(or the line if it isn't opened) which has kana is Japanese
+    async fn is_japanese(&self, uri: &Uri, line: &str) -> Option<bool> {
+        if !line.chars().any(segment::is_cjk) {
+            return None;
+        }
+
+        Some(match self.segment.cjk {
+            CjkLanguage::Japanese => true,
+            CjkLanguage::Chinese => false,
+            CjkLanguage::Auto | CjkLanguage::Off => match self.documents.read().await.get(uri) {
+                Some(document) => document.has_kana,
+                None => line.chars().any(segment::is_kana),
+            },
+        })
+    }
+}
+
+/// The candidates at a position
+#[derive(Debug)]
+pub(crate) struct Candidates {
+    /// Phrases come first(longest first), and the single word is the last
+    pub(crate) spans: Vec<WordSpan>,
+    /// The language(ISO 639-1) of the word. `None` if it's unknown
+    pub(crate) language: Option<&'static str>,
+}
+
+/// The partial word before the cursor
+#[derive(Debug)]
+pub(crate) struct Prefix {
+    /// The word as it is typed, and the range from its start to the cursor
+    pub(crate) span: WordSpan,
+    /// The language(ISO 639-1) of the word. `None` if it's unknown
+    pub(crate) language: Option<&'static str>,
+}
+
+/// Guess the language of the word from its script. Ideographs are Chinese unless it's in Japanese text
+fn script_language(word: &str, japanese: bool) -> Option<&'static str> {
+    if word.chars().any(segment::is_kana) || japanese && word.chars().any(segment::is_cjk) {
+        Some("ja")
+    } else if word.chars().any(segment::is_cjk) {
+        Some("zh")
+    } else if word.chars().any(segment::is_hangul) {
+        Some("ko")
+    } else {
+        None
+    }
+}
+
+/// The sentence in the line at the cursor(the char index)
+fn sentence_at(line: &str, cursor: usize) -> &str {
+    let cursor = line
+        .char_indices()
+        .nth(cursor)
+        .map_or(line.len(), |(i, _)| i);
+
+    line.split_sentence_bound_indices()
+        .find(|(start, sentence)| cursor < start + sentence.len())
+        .or_else(|| line.split_sentence_bound_indices().last())
+        .map_or("", |(_, sentence)| sentence.trim())
 }
 
-/// Extract the word(lowercase, and ascii alphabet only) at the cursor position
-fn extract_word_from_line(s: String, position: &Position) -> Option<Word> {
-    let mut return_string: Option<String> = None;
-    let mut is_return_word = false;
-    for (i, ci) in s.chars().enumerate() {
-        // dbg!(i, ci, is_return_word, &return_string, &s);
-        if i as u32 == position.character {
-            // カーソルの位置の単語を返すべきとしてマーク
-            is_return_word = true;
+/// The index of the word at the cursor.
+/// The word just before the cursor is used if the cursor isn't on a word.
+fn word_index_at(words: &[Token], cursor: usize) -> Option<usize> {
+    words
+        .iter()
+        .position(|word| word.range.contains(&cursor))
+        .or_else(|| words.iter().position(|word| word.range.end == cursor))
+}
+
+/// Join the words(case folded), and make the span from the first to the last.
+/// Words are joined with a space, or without it if they are adjacent like CJK.
+/// A word alone is its base form if it has.
+fn words_to_span(s: &str, words: &[Token], line: u32, encoding: PositionEncoding) -> WordSpan {
+    let chars: Vec<char> = s.chars().collect();
+
+    let mut word = String::new();
+    for (i, token) in words.iter().enumerate() {
+        if i > 0 && words[i - 1].range.end < token.range.start {
+            word.push(' ');
         }
-        if !ci.is_ascii_alphabetic() {
-            if is_return_word {
-                break;
-            } else {
-                return_string = None;
-            }
-        } else if return_string.is_some() {
-            return_string.as_mut().unwrap().push(ci);
-        } else {
-            return_string = Some(format!("{ci}"));
+        match &token.base {
+            Some(base) if words.len() == 1 => word.push_str(base),
+            _ => word.extend(&chars[token.range.clone()]),
         }
     }
-    return_string.map(|s| s.to_lowercase().into())
+
+    let start = words.first().map_or(0, |word| word.range.start);
+    let end = words.last().map_or(0, |word| word.range.end);
+
+    WordSpan::new(
+        fold_case(&word),
+        lsp_types::Range::new(
+            Position::new(line, encoding.character(s, start)),
+            Position::new(line, encoding.character(s, end)),
+        ),
+    )
+}
+
+/// Extract the part of the word before the cursor. CJK runs are not segmented
+fn extract_prefix_from_line(
+    s: &str,
+    position: &Position,
+    encoding: PositionEncoding,
+    config: &SegmentConfig,
+) -> Option<WordSpan> {
+    let cursor = encoding.char_index(s, position.character);
+    let word = segment::words(s, config, None)
+        .into_iter()
+        .find(|word| word.range.start < cursor && cursor <= word.range.end)?;
+
+    let prefix: String = s
+        .chars()
+        .skip(word.range.start)
+        .take(cursor - word.range.start)
+        .collect();
+
+    Some(WordSpan::new(
+        prefix,
+        lsp_types::Range::new(
+            Position::new(position.line, encoding.character(s, word.range.start)),
+            Position::new(position.line, encoding.character(s, cursor)),
+        ),
+    ))
+}
+
+/// Extract the phrases containing the word at the cursor(longest first), and the word itself as the last.
+/// Phrases consist of words separated only by whitespaces, or adjacent words like CJK.
+fn extract_candidates_from_line(
+    s: String,
+    position: &Position,
+    encoding: PositionEncoding,
+    config: &SegmentConfig,
+    cjk: Option<&dyn CjkSegmenter>,
+) -> Vec<WordSpan> {
+    let cursor = encoding.char_index(&s, position.character);
+    let words = segment::words(&s, config, cjk);
+    let Some(index) = word_index_at(&words, cursor) else {
+        return vec![];
+    };
+
+    let chars: Vec<char> = s.chars().collect();
+    let joinable = |left: &Token, right: &Token| {
+        chars[left.range.end..right.range.start]
+            .iter()
+            .all(|c| c.is_whitespace())
+    };
+
+    let max_words = config.phrase_words.max(1);
+
+    // The range of words which can be a phrase with the word at the cursor
+    let mut first = index;
+    while first > 0 && index - first + 1 < max_words && joinable(&words[first - 1], &words[first]) {
+        first -= 1;
+    }
+    let mut last = index;
+    while last + 1 < words.len()
+        && last - index + 1 < max_words
+        && joinable(&words[last], &words[last + 1])
+    {
+        last += 1;
+    }
+
+    let mut candidates = vec![];
+    for len in (1..=max_words.min(last - first + 1)).rev() {
+        let starts = first.max((index + 1).saturating_sub(len))..=index.min(last + 1 - len);
+        for start in starts {
+            candidates.push(words_to_span(
+                &s,
+                &words[start..start + len],
+                position.line,
+                encoding,
+            ));
+        }
+    }
+
+    candidates
 }
 
 #[cfg(test)]
@@ -125,12 +694,28 @@ mod tests {
     use std::str::FromStr;
 
     use super::*;
+    use etymora_traits::Word;
     use lsp_types::Uri;
 
     use tempfile::TempDir;
     use tokio::fs;
     use tokio::io::AsyncWriteExt;
 
+    /// Extract only the word at the cursor
+    fn extract_word_from_line(
+        s: String,
+        position: &Position,
+        encoding: PositionEncoding,
+        config: &SegmentConfig,
+    ) -> Option<WordSpan> {
+        let config = SegmentConfig {
+            phrase_words: 1,
+            ..config.clone()
+        };
+
+        extract_candidates_from_line(s, position, encoding, &config, None).pop()
+    }
+
     async fn create_tempfile(
         path_suffix: &str,
     ) -> Result<(fs::File, PathBuf, TempDir), std::io::Error> {
@@ -197,6 +782,649 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_document_store() -> Result<(), Box<dyn std::error::Error>> {
+        let uri = Uri::from_str("file:///not-exist/doc.md")?;
+        let fs = FileSystem::default();
+
+        // Not opened and not on the disk
+        assert!(fs.read_line_uri(&uri, &Position::new(0, 0)).await.is_err());
+
+        fs.open(uri.clone(), "lorem ipsum\ndolor sit amet\n", 0, "markdown")
+            .await;
+
+        assert_eq!(
+            fs.read_line_uri(&uri, &Position::new(1, 0)).await?,
+            "dolor sit amet"
+        );
+
+        // Incremental change
+        fs.change(
+            &uri,
+            1,
+            vec![TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(
+                    Position::new(1, 6),
+                    Position::new(1, 9),
+                )),
+                range_length: None,
+                text: "consectetur".into(),
+            }],
+        )
+        .await?;
+
+        assert_eq!(
+            fs.read_line_uri(&uri, &Position::new(1, 0)).await?,
+            "dolor consectetur amet"
+        );
+        assert_eq!(
+            fs.read_range_uri(
+                &uri,
+                &lsp_types::Range::new(Position::new(1, 6), Position::new(1, 17))
+            )
+            .await?,
+            "consectetur"
+        );
+
+        // Insert a line break across the lines
+        fs.change(
+            &uri,
+            2,
+            vec![TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(
+                    Position::new(0, 5),
+                    Position::new(1, 5),
+                )),
+                range_length: None,
+                text: "\nadipiscing".into(),
+            }],
+        )
+        .await?;
+
+        assert_eq!(fs.read_line_uri(&uri, &Position::new(0, 0)).await?, "lorem");
+        assert_eq!(
+            fs.read_line_uri(&uri, &Position::new(1, 0)).await?,
+            "adipiscing consectetur amet"
+        );
+
+        // Full change
+        fs.change(
+            &uri,
+            3,
+            vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "elit".into(),
+            }],
+        )
+        .await?;
+
+        assert_eq!(
+            fs.read_candidates_uri(&uri, &Position::new(0, 2))
+                .await?
+                .spans
+                .pop(),
+            Some(WordSpan::new(
+                "elit",
+                lsp_types::Range::new(Position::new(0, 0), Position::new(0, 4))
+            ))
+        );
+
+        fs.close(&uri).await;
+
+        assert!(fs.read_line_uri(&uri, &Position::new(0, 0)).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure_keeps_documents() -> Result<(), Box<dyn std::error::Error>> {
+        let uri = Uri::from_str("file:///not-exist/test.md")?;
+        let fs = FileSystem::default();
+        fs.open(uri.clone(), "lorem ipsum", 0, "markdown").await;
+
+        let reconfigured = fs.reconfigure(SegmentConfig::default())?;
+        assert_eq!(reconfigured.opened().await, vec![uri.clone()]);
+        assert_eq!(
+            reconfigured.language_id(&uri).await.as_deref(),
+            Some("markdown")
+        );
+
+        // The changes are shared
+        fs.change(
+            &uri,
+            1,
+            vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "dolor".into(),
+            }],
+        )
+        .await?;
+        assert_eq!(
+            reconfigured
+                .read_line_uri(&uri, &Position::new(0, 0))
+                .await?,
+            "dolor"
+        );
+
+        fs.close(&uri).await;
+        assert!(reconfigured.opened().await.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_line_uri_fallback() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut file, path, _tempdir) = create_tempfile("test2").await?;
+
+        file.write_all(b"on the disk\n").await?;
+
+        let uri = Uri::from_str(&format!("file://{}", path.display()))?;
+        let fs = FileSystem::default();
+
+        assert_eq!(
+            fs.read_line_uri(&uri, &Position::new(0, 0)).await?,
+            "on the disk"
+        );
+
+        // The opened document takes precedence over the disk
+        fs.open(uri.clone(), "in the editor", 0, "markdown").await;
+
+        assert_eq!(
+            fs.read_line_uri(&uri, &Position::new(0, 0)).await?,
+            "in the editor"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_negotiate_position_encoding() {
+        assert_eq!(PositionEncoding::negotiate(None), PositionEncoding::Utf16);
+        assert_eq!(
+            PositionEncoding::negotiate(Some(&[])),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(
+            PositionEncoding::negotiate(Some(&[
+                PositionEncodingKind::new("utf-7"),
+                PositionEncodingKind::UTF32,
+                PositionEncodingKind::UTF8,
+            ])),
+            PositionEncoding::Utf32
+        );
+        assert_eq!(
+            PositionEncoding::negotiate(Some(&[PositionEncodingKind::UTF8])),
+            PositionEncoding::Utf8
+        );
+    }
+
+    #[test]
+    fn test_position_encoding_conversion() {
+        // U+1F600 is 4 bytes in UTF-8 and a surrogate pair in UTF-16
+        let line = "a😀b";
+
+        assert_eq!(PositionEncoding::Utf8.char_index(line, 5), 2);
+        assert_eq!(PositionEncoding::Utf16.char_index(line, 3), 2);
+        assert_eq!(PositionEncoding::Utf32.char_index(line, 2), 2);
+
+        // The middle of the emoji
+        assert_eq!(PositionEncoding::Utf8.char_index(line, 3), 1);
+        assert_eq!(PositionEncoding::Utf16.char_index(line, 2), 1);
+
+        // Beyond the end of the line
+        assert_eq!(PositionEncoding::Utf16.char_index(line, 100), 3);
+
+        assert_eq!(PositionEncoding::Utf8.character(line, 2), 5);
+        assert_eq!(PositionEncoding::Utf16.character(line, 2), 3);
+        assert_eq!(PositionEncoding::Utf32.character(line, 2), 2);
+    }
+
+    #[test]
+    fn test_extract_word_after_astral_plane() {
+        let line = "😀𝒳 lorem ipsum";
+
+        assert_eq!(
+            extract_word_from_line(
+                line.into(),
+                &Position::new(0, 5),
+                PositionEncoding::Utf16,
+                &SegmentConfig::default()
+            ),
+            Some(WordSpan::new(
+                "lorem",
+                lsp_types::Range::new(Position::new(0, 5), Position::new(0, 10))
+            ))
+        );
+        assert_eq!(
+            extract_word_from_line(
+                line.into(),
+                &Position::new(0, 3),
+                PositionEncoding::Utf32,
+                &SegmentConfig::default()
+            ),
+            Some(WordSpan::new(
+                "lorem",
+                lsp_types::Range::new(Position::new(0, 3), Position::new(0, 8))
+            ))
+        );
+        assert_eq!(
+            extract_word_from_line(
+                line.into(),
+                &Position::new(0, 9),
+                PositionEncoding::Utf8,
+                &SegmentConfig::default()
+            ),
+            Some(WordSpan::new(
+                "lorem",
+                lsp_types::Range::new(Position::new(0, 9), Position::new(0, 14))
+            ))
+        );
+        assert_eq!(
+            extract_word_from_line(
+                line.into(),
+                &Position::new(0, 11),
+                PositionEncoding::Utf16,
+                &SegmentConfig::default()
+            ),
+            Some(WordSpan::new(
+                "ipsum",
+                lsp_types::Range::new(Position::new(0, 11), Position::new(0, 16))
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_document_change_with_astral_plane() -> Result<(), Box<dyn std::error::Error>> {
+        let uri = Uri::from_str("file:///not-exist/emoji.md")?;
+        let fs = FileSystem::new(PositionEncoding::Utf16, SegmentConfig::default())?;
+
+        fs.open(uri.clone(), "😀 lorem", 0, "markdown").await;
+
+        // Replace "lorem"(UTF-16 offset 3..8)
+        fs.change(
+            &uri,
+            1,
+            vec![TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(
+                    Position::new(0, 3),
+                    Position::new(0, 8),
+                )),
+                range_length: None,
+                text: "ipsum".into(),
+            }],
+        )
+        .await?;
+
+        assert_eq!(
+            fs.read_line_uri(&uri, &Position::new(0, 0)).await?,
+            "😀 ipsum"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_word_range() {
+        assert_eq!(
+            extract_word_from_line(
+                "lorem ipsum\n".into(),
+                &Position::new(3, 8),
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            ),
+            Some(WordSpan::new(
+                "ipsum",
+                lsp_types::Range::new(Position::new(3, 6), Position::new(3, 11))
+            ))
+        );
+
+        // The end of the line
+        assert_eq!(
+            extract_word_from_line(
+                "lorem ipsum".into(),
+                &Position::new(0, 2),
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            ),
+            Some(WordSpan::new(
+                "lorem",
+                lsp_types::Range::new(Position::new(0, 0), Position::new(0, 5))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_extract_word_unicode() {
+        let config = SegmentConfig::default();
+
+        assert_eq!(
+            extract_word_from_line(
+                "Die Straße ist naïve".into(),
+                &Position::new(0, 6),
+                PositionEncoding::Utf16,
+                &config
+            ),
+            Some(WordSpan::new(
+                "strasse",
+                lsp_types::Range::new(Position::new(0, 4), Position::new(0, 10))
+            ))
+        );
+
+        assert_eq!(
+            extract_word_from_line(
+                "Ελληνικά и Русский".into(),
+                &Position::new(0, 12),
+                PositionEncoding::Utf16,
+                &config
+            )
+            .map(|span| span.word),
+            Some(Word::from("русский"))
+        );
+
+        assert_eq!(
+            extract_word_from_line(
+                "I don't know".into(),
+                &Position::new(0, 3),
+                PositionEncoding::Utf16,
+                &config
+            )
+            .map(|span| span.word),
+            Some(Word::from("don't"))
+        );
+    }
+
+    #[test]
+    fn test_extract_candidates() {
+        let config = SegmentConfig::default();
+        let words = |line: &str, character: u32| -> Vec<String> {
+            extract_candidates_from_line(
+                line.into(),
+                &Position::new(0, character),
+                PositionEncoding::Utf16,
+                &config,
+                None,
+            )
+            .into_iter()
+            .map(|span| span.word.inner())
+            .collect()
+        };
+
+        assert_eq!(words("Give up", 0), vec!["give up", "give"]);
+        assert_eq!(words("Give up", 5), vec!["give up", "up"]);
+
+        // Phrases don't cross punctuations
+        assert_eq!(words("I give, up", 3), vec!["i give", "give"]);
+
+        assert_eq!(
+            words("in spite of it", 4),
+            vec![
+                "in spite of it",
+                "in spite of",
+                "spite of it",
+                "in spite",
+                "spite of",
+                "spite"
+            ]
+        );
+
+        // Up to `phrase_words` words
+        assert_eq!(words("a b c d e f g", 6)[0], "a b c d");
+        assert_eq!(words("a b c d e f g", 6).len(), 4 + 3 + 2 + 1);
+
+        assert!(words("", 0).is_empty());
+    }
+
+    #[test]
+    fn test_extract_candidates_range() {
+        let candidates = extract_candidates_from_line(
+            "It was ad hoc.".into(),
+            &Position::new(2, 7),
+            PositionEncoding::Utf16,
+            &SegmentConfig {
+                phrase_words: 2,
+                ..Default::default()
+            },
+            None,
+        );
+
+        assert_eq!(
+            candidates,
+            vec![
+                WordSpan::new(
+                    "was ad",
+                    lsp_types::Range::new(Position::new(2, 3), Position::new(2, 9))
+                ),
+                WordSpan::new(
+                    "ad hoc",
+                    lsp_types::Range::new(Position::new(2, 7), Position::new(2, 13))
+                ),
+                WordSpan::new(
+                    "ad",
+                    lsp_types::Range::new(Position::new(2, 7), Position::new(2, 9))
+                ),
+            ]
+        );
+
+        // Disabled phrases
+        assert_eq!(
+            extract_candidates_from_line(
+                "ad hoc".into(),
+                &Position::new(0, 0),
+                PositionEncoding::Utf16,
+                &SegmentConfig {
+                    phrase_words: 1,
+                    ..Default::default()
+                },
+                None,
+            )
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_extract_candidates_cjk() {
+        let segmenters = Segmenters::default();
+        let candidates: Vec<String> = extract_candidates_from_line(
+            "我们学习语言学".into(),
+            &Position::new(0, 2),
+            PositionEncoding::Utf16,
+            &SegmentConfig {
+                phrase_words: 3,
+                ..Default::default()
+            },
+            segmenters.get(false),
+        )
+        .into_iter()
+        .map(|span| span.word.inner())
+        .collect();
+
+        // Adjacent words are joined without spaces
+        assert_eq!(
+            candidates,
+            vec!["我们学习语言学", "我们学习", "学习语言学", "学习"]
+        );
+    }
+
+    #[test]
+    fn test_sentence_at() {
+        let line = "Das ist gut. This is fine.  Ça va?";
+        assert_eq!(sentence_at(line, 4), "Das ist gut.");
+        assert_eq!(sentence_at(line, 15), "This is fine.");
+        assert_eq!(sentence_at(line, 30), "Ça va?");
+        // At the end of the line
+        assert_eq!(sentence_at(line, 34), "Ça va?");
+        assert_eq!(sentence_at("", 0), "");
+    }
+
+    #[test]
+    fn test_extract_prefix() {
+        let prefix = |line: &str, character: u32| {
+            extract_prefix_from_line(
+                line,
+                &Position::new(3, character),
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            )
+        };
+
+        assert_eq!(
+            prefix("I like Etymol", 13),
+            Some(WordSpan::new(
+                "Etymol",
+                lsp_types::Range::new(Position::new(3, 7), Position::new(3, 13))
+            ))
+        );
+        // Only before the cursor
+        assert_eq!(prefix("🦀 etymology", 6).unwrap().word.inner(), "ety");
+        // Not after a word
+        assert_eq!(prefix("I like ", 7), None);
+        assert_eq!(prefix("I like", 2), None);
+    }
+
+    #[test]
+    fn test_script_language() {
+        assert_eq!(script_language("ひらがな", false), Some("ja"));
+        assert_eq!(script_language("漢字", true), Some("ja"));
+        assert_eq!(script_language("漢字", false), Some("zh"));
+        assert_eq!(script_language("한국어", false), Some("ko"));
+        assert_eq!(script_language("word", true), None);
+    }
+
+    #[tokio::test]
+    async fn test_cjk_language_of_document() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = FileSystem::new(PositionEncoding::Utf16, SegmentConfig::default())?;
+        let (mut file, path, _dir) = create_tempfile("chinese.txt").await?;
+        file.write_all("学习".as_bytes()).await?;
+        file.flush().await?;
+
+        // Chinese
+        let uri = Uri::from_str(&format!("file://{}", path.display()))?;
+        let candidates = fs.read_candidates_uri(&uri, &Position::new(0, 0)).await?;
+        assert_eq!(candidates.spans.last().unwrap().word, Word::from("学习"));
+        assert_eq!(candidates.language, Some("zh"));
+
+        // Japanese since the document has kana, but no dictionary is given
+        let uri = Uri::from_str("file:///not-exist/japanese.md")?;
+        fs.open(uri.clone(), "学习\nひらがな", 0, "markdown").await;
+        let candidates = fs.read_candidates_uri(&uri, &Position::new(0, 0)).await?;
+        assert_eq!(candidates.spans.last().unwrap().word, Word::from("学"));
+        assert_eq!(candidates.language, Some("ja"));
+
+        // Chinese again without the kana, and Japanese with the inserted kana
+        fs.change(
+            &uri,
+            1,
+            vec![TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(
+                    Position::new(0, 2),
+                    Position::new(1, 4),
+                )),
+                range_length: None,
+                text: String::new(),
+            }],
+        )
+        .await?;
+        let candidates = fs.read_candidates_uri(&uri, &Position::new(0, 0)).await?;
+        assert_eq!(candidates.language, Some("zh"));
+
+        fs.change(
+            &uri,
+            2,
+            vec![TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(
+                    Position::new(0, 2),
+                    Position::new(0, 2),
+                )),
+                range_length: None,
+                text: "です".to_string(),
+            }],
+        )
+        .await?;
+        let candidates = fs.read_candidates_uri(&uri, &Position::new(0, 0)).await?;
+        assert_eq!(candidates.language, Some("ja"));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[tokio::test]
+    async fn test_source_code() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = FileSystem::new(
+            PositionEncoding::default(),
+            SegmentConfig {
+                syntax: true,
+                ..Default::default()
+            },
+        )?;
+        let uri = Uri::from_str("file:///not-exist/main.rs")?;
+        fs.open(
+            uri.clone(),
+            "/// Print the greeting\nfn greeting() {\n    println!(\"hello world\"); // TODO\n}\n",
+            0,
+            "rust",
+        )
+        .await;
+
+        let word = |line, character| {
+            let fs = &fs;
+            let uri = &uri;
+            async move {
+                fs.read_candidates_uri(uri, &Position::new(line, character))
+                    .await
+                    .unwrap()
+                    .spans
+                    .pop()
+                    .map(|span| span.word.to_string())
+            }
+        };
+        assert_eq!(word(0, 16).await.as_deref(), Some("greeting"));
+        assert_eq!(word(1, 5).await, None);
+        assert_eq!(word(2, 4).await, None);
+        assert_eq!(word(2, 18).await.as_deref(), Some("hello"));
+        assert_eq!(word(2, 35).await.as_deref(), Some("todo"));
+
+        assert!(fs
+            .read_prefix_uri(&uri, &Position::new(1, 6))
+            .await?
+            .is_none());
+
+        let (_, words) = fs.checked_words_uri(&uri).await.unwrap();
+        let words: Vec<_> = words.iter().map(|span| span.word.to_string()).collect();
+        assert_eq!(
+            words,
+            vec!["print", "the", "greeting", "hello", "world", "todo"]
+        );
+
+        // The tree follows the changes
+        fs.change(
+            &uri,
+            1,
+            vec![TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(
+                    Position::new(1, 0),
+                    Position::new(1, 0),
+                )),
+                range_length: None,
+                text: "// Say hello\n".to_string(),
+            }],
+        )
+        .await?;
+        assert_eq!(word(1, 4).await.as_deref(), Some("say"));
+        assert_eq!(word(2, 5).await, None);
+
+        // Prose without the grammar, or with the stage disabled(default)
+        let reconfigured = fs.reconfigure(SegmentConfig::default())?;
+        assert!(reconfigured
+            .read_prefix_uri(&uri, &Position::new(1, 6))
+            .await?
+            .is_some());
+
+        Ok(())
+    }
+
     #[test]
     fn test_extract_word_from_line() -> Result<(), Box<dyn std::error::Error>> {
         assert_eq!(
@@ -205,8 +1433,11 @@ mod tests {
                 &Position {
                     line: 0,
                     character: 0
-                }
-            ),
+                },
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            )
+            .map(|span| span.word),
             Some(Word::from("testword".to_string()))
         );
 
@@ -216,8 +1447,11 @@ mod tests {
                 &Position {
                     line: 0,
                     character: 0
-                }
-            ),
+                },
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            )
+            .map(|span| span.word),
             Some(Word::from("testword".to_string()))
         );
 
@@ -227,8 +1461,11 @@ mod tests {
                 &Position {
                     line: 0,
                     character: 0
-                }
-            ),
+                },
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            )
+            .map(|span| span.word),
             Some(Word::from("testword".to_string()))
         );
 
@@ -238,8 +1475,11 @@ mod tests {
                 &Position {
                     line: 0,
                     character: 0
-                }
-            ),
+                },
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            )
+            .map(|span| span.word),
             Some(Word::from("testword".to_string()))
         );
 
@@ -249,8 +1489,11 @@ mod tests {
                 &Position {
                     line: 0,
                     character: 0
-                }
-            ),
+                },
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            )
+            .map(|span| span.word),
             Some(Word::from("lorem".to_string()))
         );
 
@@ -260,8 +1503,11 @@ mod tests {
                 &Position {
                     line: 0,
                     character: 7
-                }
-            ),
+                },
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            )
+            .map(|span| span.word),
             Some(Word::from("ipsum".to_string()))
         );
 
@@ -271,8 +1517,11 @@ mod tests {
                 &Position {
                     line: 0,
                     character: 0
-                }
-            ),
+                },
+                PositionEncoding::Utf16,
+                &SegmentConfig::default(),
+            )
+            .map(|span| span.word),
             None
         );
 
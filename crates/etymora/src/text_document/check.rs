@@ -0,0 +1,152 @@
+//! Words to be spell-checked
+//! Fenced code blocks, inline code, URLs, paths and identifiers like `snake_case` and `camelCase` are skipped.
+//! CJK words are skipped too, since they are split by the dictionaries of the analyzers.
+
+use std::ops::Range;
+
+use etymora_traits::WordSpan;
+
+use super::{segment, words_to_span, PositionEncoding, SegmentConfig};
+
+const FENCES: [&str; 2] = ["```", "~~~"];
+
+/// Extract the words to be checked from the lines of a document
+pub(super) fn checked_words(
+    lines: impl Iterator<Item = String>,
+    encoding: PositionEncoding,
+    config: &SegmentConfig,
+) -> Vec<WordSpan> {
+    let mut in_fence = false;
+    let mut spans = vec![];
+
+    for (line_number, line) in lines.enumerate() {
+        if FENCES
+            .iter()
+            .any(|fence| line.trim_start().starts_with(fence))
+        {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let skipped = skipped_ranges(&chars);
+
+        for word in segment::words(&line, config, None) {
+            let is_skipped = word.range.len() < 2
+                || skipped
+                    .iter()
+                    .any(|range| range.start < word.range.end && word.range.start < range.end)
+                || chars[word.range.clone()]
+                    .iter()
+                    .any(|c| segment::is_cjk(*c) || segment::is_hangul(*c));
+
+            if !is_skipped {
+                spans.push(words_to_span(&line, &[word], line_number as u32, encoding));
+            }
+        }
+    }
+
+    spans
+}
+
+/// The ranges of inline code, and the runs of non-whitespace chars which are URLs or identifiers in char index
+fn skipped_ranges(chars: &[char]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+
+    let mut code_start = None;
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '`' {
+            match code_start.take() {
+                Some(start) => ranges.push(start..i + 1),
+                None => code_start = Some(i),
+            }
+        }
+    }
+
+    let mut run_start = 0;
+    for i in 0..=chars.len() {
+        if chars.get(i).is_none_or(|c| c.is_whitespace()) {
+            if run_start < i && is_code_like(&chars[run_start..i]) {
+                ranges.push(run_start..i);
+            }
+            run_start = i + 1;
+        }
+    }
+
+    ranges
+}
+
+/// Whether the run of chars is a URL, an email address, a path or an identifier
+fn is_code_like(run: &[char]) -> bool {
+    let run: String = run.iter().collect();
+    let run = run.trim_matches(|c: char| !c.is_alphanumeric() && c != '_');
+
+    let has_letter = run.chars().any(char::is_alphabetic);
+    let is_camel_case = run
+        .chars()
+        .zip(run.chars().skip(1))
+        .any(|(a, b)| a.is_lowercase() && b.is_uppercase());
+
+    run.contains("://")
+        || run.starts_with("www.")
+        || run.contains(['@', '/', '\\', '_'])
+        || run.contains("::")
+        || run.contains('.') && has_letter
+        || run.chars().any(|c| c.is_ascii_digit()) && has_letter
+        || is_camel_case
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        checked_words(
+            text.lines().map(str::to_string),
+            PositionEncoding::Utf16,
+            &SegmentConfig::default(),
+        )
+        .into_iter()
+        .map(|span| span.word.inner())
+        .collect()
+    }
+
+    #[test]
+    fn test_checked_words() {
+        assert_eq!(
+            words("See `some_code` at https://example.com/docs, or mail@example.com."),
+            vec!["see", "at", "or"]
+        );
+        assert_eq!(
+            words("Call parseLine(foo_bar) with utf8 in src/main.rs or Foo::new"),
+            vec!["call", "with", "in", "or"]
+        );
+        assert_eq!(
+            words("Text\n```rust\nlet unchecked = 1;\n```\nMore 日本語 I"),
+            vec!["text", "more"]
+        );
+    }
+
+    #[test]
+    fn test_checked_words_range() {
+        let spans = checked_words(
+            ["", "🦀 Word"].into_iter().map(str::to_string),
+            PositionEncoding::Utf16,
+            &SegmentConfig::default(),
+        );
+
+        assert_eq!(
+            spans,
+            vec![WordSpan::new(
+                "word",
+                lsp_types::Range::new(
+                    lsp_types::Position::new(1, 3),
+                    lsp_types::Position::new(1, 7)
+                )
+            )]
+        );
+    }
+}
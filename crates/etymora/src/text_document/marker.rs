@@ -0,0 +1,150 @@
+//! Language markers
+//! The language of a document written in it, like `lang: de` in the front matter of Markdown,
+//! `<html lang="de">` of HTML and `\selectlanguage{german}` of LaTeX.
+
+/// The number of lines searched for the markers from the start
+const MARKER_LINES: usize = 100;
+
+/// The language names of babel and polyglossia, and their ISO 639-1 codes
+const LATEX_LANGUAGES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("american", "en"),
+    ("british", "en"),
+    ("UKenglish", "en"),
+    ("USenglish", "en"),
+    ("german", "de"),
+    ("ngerman", "de"),
+    ("austrian", "de"),
+    ("naustrian", "de"),
+    ("french", "fr"),
+    ("francais", "fr"),
+    ("spanish", "es"),
+    ("italian", "it"),
+    ("portuguese", "pt"),
+    ("brazilian", "pt"),
+    ("dutch", "nl"),
+    ("russian", "ru"),
+    ("polish", "pl"),
+    ("swedish", "sv"),
+    ("danish", "da"),
+    ("norsk", "nb"),
+    ("finnish", "fi"),
+    ("greek", "el"),
+    ("japanese", "ja"),
+    ("chinese", "zh"),
+    ("korean", "ko"),
+];
+
+/// The language(ISO 639-1) of the document by the marker in it
+pub(super) fn marker_language(mut lines: impl Iterator<Item = String>) -> Option<String> {
+    let first = lines.next()?;
+
+    // The front matter is only at the start
+    let front_matter = match first.trim_end() {
+        "---" => Some(("---", ':')),
+        "+++" => Some(("+++", '=')),
+        _ => None,
+    };
+    if let Some((fence, separator)) = front_matter {
+        for line in lines.by_ref().take(MARKER_LINES) {
+            if line.trim_end() == fence {
+                break;
+            }
+            if let Some(language) = front_matter_language(&line, separator) {
+                return Some(language);
+            }
+        }
+        return lines
+            .take(MARKER_LINES)
+            .find_map(|line| inline_language(&line));
+    }
+
+    std::iter::once(first)
+        .chain(lines)
+        .take(MARKER_LINES)
+        .find_map(|line| inline_language(&line))
+}
+
+/// `lang: de` in YAML, or `lang = "de"` in TOML
+fn front_matter_language(line: &str, separator: char) -> Option<String> {
+    let (key, value) = line.split_once(separator)?;
+    if !matches!(key.trim(), "lang" | "language") {
+        return None;
+    }
+
+    language_code(value.trim().trim_matches(['"', '\'']))
+}
+
+/// The `lang` attribute of `<html>`, or `\selectlanguage`
+fn inline_language(line: &str) -> Option<String> {
+    if let Some(start) = line.find("<html") {
+        let tag = &line[start..];
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        // `lang="de"` or `xml:lang='de'`
+        let value = tag.split("lang=").nth(1)?;
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = value[1..].split(quote).next()?;
+        return language_code(value);
+    }
+
+    if let Some(start) = line.find("\\selectlanguage{") {
+        let name = line[start + "\\selectlanguage{".len()..]
+            .split('}')
+            .next()?;
+        return LATEX_LANGUAGES
+            .iter()
+            .find(|(latex, _)| *latex == name.trim())
+            .map(|(_, code)| code.to_string());
+    }
+
+    None
+}
+
+/// The primary language subtag of a language tag like "en-US" and "pt_BR"
+fn language_code(tag: &str) -> Option<String> {
+    let code = tag.split(['-', '_']).next()?.to_ascii_lowercase();
+    (2..=3)
+        .contains(&code.len())
+        .then_some(code)
+        .filter(|code| code.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn language(text: &str) -> Option<String> {
+        marker_language(text.lines().map(str::to_string))
+    }
+
+    #[test]
+    fn test_front_matter() {
+        assert_eq!(
+            language("---\ntitle: Hallo\nlang: de-AT\n---\n# Hallo").as_deref(),
+            Some("de")
+        );
+        assert_eq!(
+            language("+++\ntitle = \"Bonjour\"\nlanguage = \"fr\"\n+++\n").as_deref(),
+            Some("fr")
+        );
+        // Not in the front matter
+        assert_eq!(language("# Title\n\nlang: de\n"), None);
+        assert_eq!(language("---\nlang: 日本語\n---\n"), None);
+    }
+
+    #[test]
+    fn test_inline_marker() {
+        assert_eq!(
+            language("<!DOCTYPE html>\n<html class=\"x\" lang=\"pt-BR\">\n").as_deref(),
+            Some("pt")
+        );
+        assert_eq!(
+            language("\\documentclass{article}\n\\usepackage[ngerman]{babel}\n\\begin{document}\n\\selectlanguage{ngerman}\n")
+                .as_deref(),
+            Some("de")
+        );
+        assert_eq!(language("\\selectlanguage{klingon}\n"), None);
+        assert_eq!(language("Just text"), None);
+        assert_eq!(language(""), None);
+    }
+}
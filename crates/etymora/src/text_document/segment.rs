@@ -0,0 +1,422 @@
+//! Word segmentation
+//! Word boundaries follow Unicode UAX #29, and apostrophes and hyphens between letters are handled by `SegmentConfig`.
+//! CJK text has no spaces between words, so runs of it are split by a `CjkSegmenter` for the language.
+
+mod chinese;
+mod japanese;
+
+use std::{ops::Range, path::PathBuf};
+
+use serde::Deserialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+const APOSTROPHES: [&str; 2] = ["'", "\u{2019}"];
+const HYPHENS: [char; 2] = ['-', '\u{2010}'];
+
+/// Whether a punctuation between letters joins them into a word
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum JoinPolicy {
+    Join,
+    Split,
+}
+
+/// The language of CJK text, which decides the segmenter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CjkLanguage {
+    /// Japanese if the document has kana, Chinese otherwise
+    #[default]
+    Auto,
+    Japanese,
+    Chinese,
+    /// Split only by UAX #29, a word for each ideograph
+    Off,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct SegmentConfig {
+    /// "don't" is a word with `Join`(default), "don" and "t" with `Split`
+    pub(crate) apostrophe: JoinPolicy,
+    /// "well-known" is a word with `Join`, "well" and "known" with `Split`(default)
+    pub(crate) hyphen: JoinPolicy,
+    /// The maximum number of words in a phrase like "in spite of". `1` disables phrases
+    pub(crate) phrase_words: usize,
+    pub(crate) cjk: CjkLanguage,
+    /// The directory of a MeCab dictionary(IPADIC format, UTF-8) for Japanese.
+    /// Japanese text is split only by UAX #29 without it
+    pub(crate) japanese_dictionary: Option<PathBuf>,
+    /// Look up only the words in comments and string literals of source code(`languageId` "rust" and "python").
+    /// Off by default. Needs the `tree-sitter` feature
+    pub(crate) syntax: bool,
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        Self {
+            apostrophe: JoinPolicy::Join,
+            hyphen: JoinPolicy::Split,
+            phrase_words: 4,
+            cjk: CjkLanguage::Auto,
+            japanese_dictionary: None,
+            syntax: false,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum SegmentError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid dictionary of the Japanese analyzer: {0}")]
+    InvalidDictionary(String),
+}
+
+/// A word in a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Token {
+    /// The range in char index
+    pub(crate) range: Range<usize>,
+    /// The form looked up instead of the surface, like the base form of a Japanese verb
+    pub(crate) base: Option<String>,
+}
+
+impl From<Range<usize>> for Token {
+    fn from(range: Range<usize>) -> Self {
+        Token { range, base: None }
+    }
+}
+
+/// A segmenter of a run of CJK chars. The ranges of the tokens are in the run
+pub(crate) trait CjkSegmenter {
+    fn segment(&self, text: &str) -> Vec<Token>;
+}
+
+/// The segmenters of the languages
+#[derive(Debug, Default)]
+pub(crate) struct Segmenters {
+    japanese: Option<japanese::Analyzer>,
+    chinese: chinese::Segmenter,
+}
+
+impl Segmenters {
+    pub(crate) fn new(config: &SegmentConfig) -> Result<Self, SegmentError> {
+        let japanese = match (&config.japanese_dictionary, config.cjk) {
+            (Some(path), CjkLanguage::Auto | CjkLanguage::Japanese) => {
+                Some(japanese::Analyzer::load(path)?)
+            }
+            (None, CjkLanguage::Japanese) => {
+                tracing::warn!("japanese_dictionary isn't set. Japanese is split only by UAX #29");
+                None
+            }
+            _ => None,
+        };
+
+        Ok(Segmenters {
+            japanese,
+            chinese: chinese::Segmenter::default(),
+        })
+    }
+
+    pub(crate) fn get(&self, japanese: bool) -> Option<&dyn CjkSegmenter> {
+        if japanese {
+            self.japanese.as_ref().map(|a| a as &dyn CjkSegmenter)
+        } else {
+            Some(&self.chinese)
+        }
+    }
+}
+
+/// Whether the char is an ideograph or kana
+pub(crate) fn is_cjk(c: char) -> bool {
+    is_kana(c)
+        || matches!(
+            c,
+            '\u{3005}'
+                | '\u{3007}'
+                | '\u{3400}'..='\u{4DBF}'
+                | '\u{4E00}'..='\u{9FFF}'
+                | '\u{F900}'..='\u{FAFF}'
+                | '\u{20000}'..='\u{3FFFF}'
+        )
+}
+
+pub(crate) fn is_kana(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3041}'..='\u{309F}'
+            | '\u{30A1}'..='\u{30FA}'
+            | '\u{30FC}'..='\u{30FF}'
+            | '\u{31F0}'..='\u{31FF}'
+            | '\u{FF66}'..='\u{FF9F}'
+    )
+}
+
+pub(crate) fn is_hangul(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}'
+    )
+}
+
+/// Split the run of CJK chars, and push the tokens in the line
+fn push_cjk(
+    words: &mut Vec<Token>,
+    chars: &[char],
+    run: Range<usize>,
+    segmenter: &dyn CjkSegmenter,
+) {
+    let text: String = chars[run.clone()].iter().collect();
+    words.extend(segmenter.segment(&text).into_iter().map(|token| Token {
+        range: run.start + token.range.start..run.start + token.range.end,
+        base: token.base,
+    }));
+}
+
+/// Whether the grapheme cluster is a letter(with its combining marks)
+fn is_letter(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(char::is_alphabetic)
+}
+
+/// Split the line into words, and return them with their ranges in char index.
+/// Digits and symbols are not a part of words. Runs of CJK chars are split by `cjk` if it's given.
+pub(crate) fn words(
+    line: &str,
+    config: &SegmentConfig,
+    cjk: Option<&dyn CjkSegmenter>,
+) -> Vec<Token> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut words: Vec<Token> = vec![];
+    let mut char_index = 0;
+    // The start of the run of CJK chars, which can be over some segments
+    let mut cjk_start: Option<usize> = None;
+
+    for segment in line.split_word_bounds() {
+        let graphemes: Vec<&str> = segment.graphemes(true).collect();
+        let mut current: Option<Range<usize>> = None;
+
+        for (i, grapheme) in graphemes.iter().enumerate() {
+            let len = grapheme.chars().count();
+
+            if cjk.is_some() && grapheme.chars().next().is_some_and(is_cjk) {
+                if let Some(range) = current.take() {
+                    words.push(range.into());
+                }
+                cjk_start.get_or_insert(char_index);
+                char_index += len;
+                continue;
+            }
+            if let (Some(segmenter), Some(start)) = (cjk, cjk_start.take()) {
+                push_cjk(&mut words, &chars, start..char_index, segmenter);
+            }
+
+            // UAX #29 keeps apostrophes between letters in a segment
+            let joined_apostrophe = config.apostrophe == JoinPolicy::Join
+                && APOSTROPHES.contains(grapheme)
+                && current.is_some()
+                && graphemes.get(i + 1).is_some_and(|next| is_letter(next));
+
+            if is_letter(grapheme) || joined_apostrophe {
+                match current.as_mut() {
+                    Some(range) => range.end = char_index + len,
+                    None => current = Some(char_index..char_index + len),
+                }
+            } else if let Some(range) = current.take() {
+                words.push(range.into());
+            }
+
+            char_index += len;
+        }
+
+        if let Some(range) = current {
+            words.push(range.into());
+        }
+    }
+
+    if let (Some(segmenter), Some(start)) = (cjk, cjk_start) {
+        push_cjk(&mut words, &chars, start..char_index, segmenter);
+    }
+
+    if config.hyphen == JoinPolicy::Join {
+        let mut joined: Vec<Token> = Vec::with_capacity(words.len());
+
+        for word in words {
+            match joined.last_mut() {
+                Some(last)
+                    if last.range.end + 1 == word.range.start
+                        && HYPHENS.contains(&chars[last.range.end]) =>
+                {
+                    last.range.end = word.range.end;
+                    last.base = None;
+                }
+                _ => joined.push(word),
+            }
+        }
+
+        words = joined;
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words_str(line: &str, config: &SegmentConfig) -> Vec<String> {
+        words_cjk(line, config, None)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect()
+    }
+
+    /// The words and their base forms
+    fn words_cjk(
+        line: &str,
+        config: &SegmentConfig,
+        cjk: Option<&dyn CjkSegmenter>,
+    ) -> Vec<(String, Option<String>)> {
+        words(line, config, cjk)
+            .into_iter()
+            .map(|token| {
+                (
+                    line.chars()
+                        .skip(token.range.start)
+                        .take(token.range.len())
+                        .collect(),
+                    token.base,
+                )
+            })
+            .collect()
+    }
+
+    fn ranges(line: &str, config: &SegmentConfig) -> Vec<Range<usize>> {
+        words(line, config, None)
+            .into_iter()
+            .map(|token| token.range)
+            .collect()
+    }
+
+    #[test]
+    fn test_words_unicode() {
+        let config = SegmentConfig::default();
+
+        assert_eq!(
+            words_str("A naïve café in der Straße", &config),
+            vec!["A", "naïve", "café", "in", "der", "Straße"]
+        );
+
+        // "e" with U+0301 COMBINING ACUTE ACCENT
+        assert_eq!(
+            words_str("cafe\u{301} au lait", &config),
+            vec!["cafe\u{301}", "au", "lait"]
+        );
+
+        assert_eq!(
+            words_str("Привет, мир! Καλημέρα κόσμε.", &config),
+            vec!["Привет", "мир", "Καλημέρα", "κόσμε"]
+        );
+
+        // Digits are not a part of words
+        assert_eq!(words_str("testworD6 42", &config), vec!["testworD"]);
+
+        assert!(words_str("", &config).is_empty());
+    }
+
+    #[test]
+    fn test_words_ranges() {
+        let config = SegmentConfig::default();
+
+        assert_eq!(ranges("naïve café", &config), vec![0..5, 6..10]);
+        assert_eq!(ranges("cafe\u{301}!", &config), vec![0..5]);
+    }
+
+    #[test]
+    fn test_words_apostrophe() {
+        let join = SegmentConfig::default();
+        let split = SegmentConfig {
+            apostrophe: JoinPolicy::Split,
+            ..Default::default()
+        };
+
+        assert_eq!(words_str("don't stop", &join), vec!["don't", "stop"]);
+        assert_eq!(words_str("don\u{2019}t", &join), vec!["don\u{2019}t"]);
+        assert_eq!(words_str("don't stop", &split), vec!["don", "t", "stop"]);
+
+        // Quotes around words are not a part of them
+        assert_eq!(words_str("'quoted'", &join), vec!["quoted"]);
+    }
+
+    #[test]
+    fn test_words_hyphen() {
+        let split = SegmentConfig::default();
+        let join = SegmentConfig {
+            hyphen: JoinPolicy::Join,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            words_str("a well-known word", &split),
+            vec!["a", "well", "known", "word"]
+        );
+        assert_eq!(
+            words_str("a well-known word", &join),
+            vec!["a", "well-known", "word"]
+        );
+
+        // A hyphen not between words
+        assert_eq!(words_str("well - known", &join), vec!["well", "known"]);
+    }
+
+    #[test]
+    fn test_words_cjk() {
+        let config = SegmentConfig::default();
+        let dir = tempfile::tempdir().unwrap();
+        japanese::tests::write_dictionary(dir.path());
+        let segmenters = Segmenters::new(&SegmentConfig {
+            japanese_dictionary: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            words_cjk("Tokyo(東京都)に行きます.", &config, segmenters.get(true)),
+            [
+                ("Tokyo".into(), None),
+                ("東京".into(), None),
+                ("都".into(), None),
+                ("に".into(), None),
+                ("行き".into(), Some("行く".into())),
+                ("ます".into(), None),
+            ]
+        );
+
+        assert_eq!(
+            words_str("我们学习语言学 in Chinese", &config)[..2],
+            ["我", "们"]
+        );
+        assert_eq!(
+            words_cjk("我们学习语言学 in Chinese", &config, segmenters.get(false))
+                .into_iter()
+                .map(|(word, _)| word)
+                .collect::<Vec<_>>(),
+            ["我们", "学习", "语言学", "in", "Chinese"]
+        );
+
+        // Without the dictionary
+        assert!(Segmenters::default().get(true).is_none());
+    }
+
+    #[test]
+    fn test_is_kana() {
+        assert!("ひらがなカタカナｶﾀｶﾅー".chars().all(is_kana));
+        assert!(!"漢字々".chars().any(is_kana));
+        assert!("漢字々ひらがな".chars().all(is_cjk));
+        assert!(!"Latin・、。".chars().any(is_cjk));
+    }
+}
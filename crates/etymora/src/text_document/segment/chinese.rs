@@ -0,0 +1,67 @@
+//! Chinese segmenter
+//! Jieba with its bundled dictionary, and HMM for the words not in it.
+
+use std::sync::OnceLock;
+
+use jieba_rs::Jieba;
+
+use super::{CjkSegmenter, Token};
+
+/// The dictionary is loaded at the first use, since it takes time
+#[derive(Default)]
+pub(crate) struct Segmenter {
+    jieba: OnceLock<Jieba>,
+}
+
+impl std::fmt::Debug for Segmenter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Segmenter")
+            .field("loaded", &self.jieba.get().is_some())
+            .finish()
+    }
+}
+
+impl CjkSegmenter for Segmenter {
+    fn segment(&self, text: &str) -> Vec<Token> {
+        let jieba = self.jieba.get_or_init(Jieba::new);
+
+        let mut start = 0;
+        jieba
+            .cut(text, true)
+            .into_iter()
+            .map(|word| {
+                let end = start + word.chars().count();
+                let token = Token {
+                    range: start..end,
+                    base: None,
+                };
+                start = end;
+                token
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segmenter() {
+        let segmenter = Segmenter::default();
+        let text = "我们学习语言学";
+
+        let words: Vec<String> = segmenter
+            .segment(text)
+            .into_iter()
+            .map(|token| {
+                text.chars()
+                    .skip(token.range.start)
+                    .take(token.range.len())
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(words, ["我们", "学习", "语言学"]);
+    }
+}
@@ -0,0 +1,388 @@
+//! Japanese morphological analyzer
+//! Finds the path of the least cost over the lattice of the words of a MeCab dictionary(IPADIC format, UTF-8).
+//! The dictionary is the directory of the sources, which has `*.csv` and `matrix.def`.
+
+use std::path::{Path, PathBuf};
+
+use rustc_hash::FxHashMap;
+
+use super::{CjkSegmenter, SegmentError, Token};
+
+/// The cost of a char or a run of katakana which isn't in the dictionary
+const UNKNOWN_COST: i64 = 10000;
+/// The index of the base form in the features(after the cost) of IPADIC
+const BASE_FORM_FIELD: usize = 6;
+
+#[derive(Debug)]
+struct Morpheme {
+    left_id: usize,
+    right_id: usize,
+    cost: i64,
+    /// `None` if it's the same as the surface
+    base: Option<Box<str>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Analyzer {
+    /// Surfaces to the morphemes
+    morphemes: FxHashMap<String, Vec<Morpheme>>,
+    /// The max length of the surfaces in chars
+    max_len: usize,
+    /// The connection costs from the `right_id` of a morpheme to the `left_id` of the next
+    matrix: Vec<i64>,
+    right_size: usize,
+    left_size: usize,
+}
+
+/// A node of the lattice
+#[derive(Debug, Clone, Copy)]
+struct Node<'a> {
+    start: usize,
+    end: usize,
+    morpheme: Option<&'a Morpheme>,
+}
+
+impl Node<'_> {
+    fn left_id(&self) -> usize {
+        self.morpheme.map_or(0, |m| m.left_id)
+    }
+
+    fn right_id(&self) -> usize {
+        self.morpheme.map_or(0, |m| m.right_id)
+    }
+
+    fn cost(&self) -> i64 {
+        self.morpheme.map_or(UNKNOWN_COST, |m| m.cost)
+    }
+}
+
+fn read(path: &Path) -> Result<String, SegmentError> {
+    let bytes = std::fs::read(path).map_err(|source| SegmentError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    String::from_utf8(bytes)
+        .map_err(|_| SegmentError::InvalidDictionary(format!("{} isn't UTF-8", path.display())))
+}
+
+/// Split a line of CSV. Fields can be quoted like `","`
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30A1}'..='\u{30FA}' | '\u{30FC}' | '\u{FF66}'..='\u{FF9F}')
+}
+
+impl Analyzer {
+    pub(crate) fn load(dir: &Path) -> Result<Self, SegmentError> {
+        let invalid = |path: &Path, line: &str| {
+            SegmentError::InvalidDictionary(format!("Invalid line in {}: {line}", path.display()))
+        };
+
+        let mut csv_paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map_err(|source| SegmentError::Io {
+                path: dir.to_path_buf(),
+                source,
+            })?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "csv"))
+            .collect();
+        csv_paths.sort();
+
+        if csv_paths.is_empty() {
+            return Err(SegmentError::InvalidDictionary(format!(
+                "No *.csv in {}",
+                dir.display()
+            )));
+        }
+
+        let mut morphemes: FxHashMap<String, Vec<Morpheme>> = FxHashMap::default();
+        for path in csv_paths {
+            for line in read(&path)?.lines().filter(|line| !line.is_empty()) {
+                let fields = split_csv(line);
+                let [surface, left_id, right_id, cost, features @ ..] = &fields[..] else {
+                    return Err(invalid(&path, line));
+                };
+                let (Ok(left_id), Ok(right_id), Ok(cost)) =
+                    (left_id.parse(), right_id.parse(), cost.parse())
+                else {
+                    return Err(invalid(&path, line));
+                };
+
+                let base = features
+                    .get(BASE_FORM_FIELD)
+                    .filter(|base| *base != "*" && *base != surface)
+                    .map(|base| base.as_str().into());
+
+                morphemes
+                    .entry(surface.clone())
+                    .or_default()
+                    .push(Morpheme {
+                        left_id,
+                        right_id,
+                        cost,
+                        base,
+                    });
+            }
+        }
+
+        let matrix_path = dir.join("matrix.def");
+        let matrix_def = read(&matrix_path)?;
+        let mut lines = matrix_def.lines();
+
+        let header = lines.next().unwrap_or_default();
+        let Some((right_size, left_size)) = header
+            .split_once(' ')
+            .and_then(|(r, l)| Some((r.trim().parse().ok()?, l.trim().parse().ok()?)))
+        else {
+            return Err(invalid(&matrix_path, header));
+        };
+
+        let mut matrix = vec![0; right_size * left_size];
+        for line in lines.filter(|line| !line.is_empty()) {
+            let mut fields = line.split_ascii_whitespace();
+            let (Some(Ok(right_id)), Some(Ok(left_id)), Some(Ok(cost))) = (
+                fields.next().map(str::parse::<usize>),
+                fields.next().map(str::parse::<usize>),
+                fields.next().map(str::parse::<i64>),
+            ) else {
+                return Err(invalid(&matrix_path, line));
+            };
+            if right_id >= right_size || left_id >= left_size {
+                return Err(invalid(&matrix_path, line));
+            }
+            matrix[right_id * left_size + left_id] = cost;
+        }
+
+        let max_len = morphemes
+            .keys()
+            .map(|surface| surface.chars().count())
+            .max()
+            .unwrap_or(1);
+
+        Ok(Analyzer {
+            morphemes,
+            max_len,
+            matrix,
+            right_size,
+            left_size,
+        })
+    }
+
+    fn connection_cost(&self, right_id: usize, left_id: usize) -> i64 {
+        if right_id < self.right_size && left_id < self.left_size {
+            self.matrix[right_id * self.left_size + left_id]
+        } else {
+            0
+        }
+    }
+
+    /// The nodes starting at the char index
+    fn nodes_at<'a>(&'a self, chars: &[char], start: usize) -> Vec<Node<'a>> {
+        let mut nodes = vec![];
+        let mut surface = String::new();
+
+        for end in start + 1..=chars.len().min(start + self.max_len) {
+            surface.push(chars[end - 1]);
+            for morpheme in self.morphemes.get(&surface).into_iter().flatten() {
+                nodes.push(Node {
+                    start,
+                    end,
+                    morpheme: Some(morpheme),
+                });
+            }
+        }
+
+        // Unknown words: a char if no words start here, and a run of katakana like loanwords
+        if nodes.is_empty() {
+            nodes.push(Node {
+                start,
+                end: start + 1,
+                morpheme: None,
+            });
+        }
+        if is_katakana(chars[start]) {
+            let end = chars[start..]
+                .iter()
+                .position(|c| !is_katakana(*c))
+                .map_or(chars.len(), |len| start + len);
+            if end > start + 1 {
+                nodes.push(Node {
+                    start,
+                    end,
+                    morpheme: None,
+                });
+            }
+        }
+
+        nodes
+    }
+}
+
+impl CjkSegmenter for Analyzer {
+    fn segment(&self, text: &str) -> Vec<Token> {
+        let chars: Vec<char> = text.chars().collect();
+        if chars.is_empty() {
+            return vec![];
+        }
+
+        // The best cost to reach each char index, and the last node of the path
+        let mut best: Vec<Option<(i64, Node)>> = vec![None; chars.len() + 1];
+
+        for start in 0..chars.len() {
+            let (cost_before, right_id) = match start {
+                0 => (0, 0),
+                _ => match &best[start] {
+                    Some((cost, node)) => (*cost, node.right_id()),
+                    None => continue,
+                },
+            };
+
+            for node in self.nodes_at(&chars, start) {
+                let cost =
+                    cost_before + self.connection_cost(right_id, node.left_id()) + node.cost();
+                if best[node.end].is_none_or(|(best_cost, _)| cost < best_cost) {
+                    best[node.end] = Some((cost, node));
+                }
+            }
+        }
+
+        let mut tokens = vec![];
+        let mut end = chars.len();
+        while end > 0 {
+            let Some((_, node)) = best[end] else {
+                break;
+            };
+            tokens.push(Token {
+                range: node.start..node.end,
+                base: node
+                    .morpheme
+                    .and_then(|m| m.base.as_deref())
+                    .map(str::to_string),
+            });
+            end = node.start;
+        }
+        tokens.reverse();
+
+        tokens
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Write a tiny dictionary in the format of IPADIC
+    pub(crate) fn write_dictionary(dir: &Path) {
+        std::fs::write(
+            dir.join("Noun.csv"),
+            "東京,1,1,3000,名詞,固有名詞,地域,一般,*,*,東京,トウキョウ,トーキョー
+京都,1,1,3000,名詞,固有名詞,地域,一般,*,*,京都,キョウト,キョート
+都,1,1,5000,名詞,接尾,地域,*,*,*,都,ト,ト
+\",\",1,1,5000,記号,読点,*,*,*,*,\",\",\",\",\",\"
+",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("Others.csv"),
+            "に,2,2,1000,助詞,格助詞,一般,*,*,*,に,ニ,ニ
+行き,3,3,2000,動詞,自立,*,*,五段・カ行促音便,連用形,行く,イキ,イキ
+ます,4,4,1000,助動詞,*,*,*,特殊・マス,基本形,ます,マス,マス
+",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("matrix.def"),
+            "5 5\n0 1 -100\n1 2 -100\n2 3 -100\n3 4 -100\n4 0 -100\n",
+        )
+        .unwrap();
+    }
+
+    fn segment_str(analyzer: &Analyzer, text: &str) -> Vec<(String, Option<String>)> {
+        analyzer
+            .segment(text)
+            .into_iter()
+            .map(|token| {
+                (
+                    text.chars()
+                        .skip(token.range.start)
+                        .take(token.range.len())
+                        .collect(),
+                    token.base,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_analyzer() {
+        let dir = tempfile::tempdir().unwrap();
+        write_dictionary(dir.path());
+        let analyzer = Analyzer::load(dir.path()).unwrap();
+
+        assert_eq!(
+            segment_str(&analyzer, "東京都に行きます"),
+            [
+                ("東京".into(), None),
+                ("都".into(), None),
+                ("に".into(), None),
+                ("行き".into(), Some("行く".into())),
+                ("ます".into(), None),
+            ]
+        );
+
+        // Unknown words
+        assert_eq!(
+            segment_str(&analyzer, "京都にコーヒー"),
+            [
+                ("京都".into(), None),
+                ("に".into(), None),
+                ("コーヒー".into(), None),
+            ]
+        );
+        assert_eq!(segment_str(&analyzer, "猫"), [("猫".into(), None)]);
+        assert!(analyzer.segment("").is_empty());
+    }
+
+    #[test]
+    fn test_split_csv() {
+        assert_eq!(split_csv("a,b,,c"), ["a", "b", "", "c"]);
+        assert_eq!(split_csv("\",\",1,\"a\"\"b\""), [",", "1", "a\"b"]);
+    }
+
+    #[test]
+    fn test_invalid_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            Analyzer::load(dir.path()),
+            Err(SegmentError::InvalidDictionary(_))
+        ));
+
+        write_dictionary(dir.path());
+        std::fs::write(dir.path().join("Broken.csv"), "行く,x,3,2000\n").unwrap();
+        assert!(matches!(
+            Analyzer::load(dir.path()),
+            Err(SegmentError::InvalidDictionary(_))
+        ));
+    }
+}
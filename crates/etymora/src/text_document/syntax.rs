@@ -0,0 +1,229 @@
+//! Syntax
+//! In source code, only the words in comments(including doc comments) and string literals are prose,
+//! and the others are left to the language server of the code. The grammars of tree-sitter are chosen by `languageId`.
+
+use std::ops::Range;
+
+use ropey::Rope;
+
+/// The node kinds of the prose in the grammar
+#[cfg(feature = "tree-sitter")]
+fn grammar(language_id: &str) -> Option<(tree_sitter::Language, &'static [&'static str])> {
+    match language_id {
+        "rust" => Some((
+            tree_sitter_rust::LANGUAGE.into(),
+            &[
+                "line_comment",
+                "block_comment",
+                "string_literal",
+                "raw_string_literal",
+            ],
+        )),
+        // Interpolations of f-strings are code
+        "python" => Some((
+            tree_sitter_python::LANGUAGE.into(),
+            &["comment", "string_content"],
+        )),
+        _ => None,
+    }
+}
+
+/// The syntax tree of an opened document. It's parsed on the first use,
+/// and parsed again incrementally from the edited tree after changes
+#[cfg(feature = "tree-sitter")]
+#[derive(Debug, Default)]
+pub(super) struct SyntaxTree {
+    tree: Option<tree_sitter::Tree>,
+    /// Whether the text has been edited since the tree was parsed
+    edited: bool,
+}
+
+#[cfg(feature = "tree-sitter")]
+impl SyntaxTree {
+    /// Apply the edit of the chars `start..end` to the tree. Call it before the text is changed
+    pub(super) fn edit(&mut self, text: &Rope, start: usize, end: usize, new_text: &str) {
+        let Some(tree) = self.tree.as_mut() else {
+            return;
+        };
+
+        let point = |char_index: usize| {
+            let row = text.char_to_line(char_index);
+            tree_sitter::Point::new(row, text.char_to_byte(char_index) - text.line_to_byte(row))
+        };
+        let start_position = point(start);
+        let new_end_position = match new_text.rfind('\n') {
+            Some(i) => tree_sitter::Point::new(
+                start_position.row + new_text.matches('\n').count(),
+                new_text.len() - i - 1,
+            ),
+            None => {
+                tree_sitter::Point::new(start_position.row, start_position.column + new_text.len())
+            }
+        };
+
+        let start_byte = text.char_to_byte(start);
+        tree.edit(&tree_sitter::InputEdit {
+            start_byte,
+            old_end_byte: text.char_to_byte(end),
+            new_end_byte: start_byte + new_text.len(),
+            start_position,
+            old_end_position: point(end),
+            new_end_position,
+        });
+        self.edited = true;
+    }
+
+    /// Drop the tree when the whole text is replaced
+    pub(super) fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The byte ranges of the prose in the text. `None` if the language has no grammar, and all of it is prose
+    pub(super) fn prose_ranges(
+        &mut self,
+        language_id: &str,
+        text: &Rope,
+    ) -> Option<Vec<Range<usize>>> {
+        let (language, kinds) = grammar(language_id)?;
+
+        if self.tree.is_none() || self.edited {
+            let mut parser = tree_sitter::Parser::new();
+            if let Err(e) = parser.set_language(&language) {
+                tracing::warn!("Incompatible grammar of {language_id}: {e}");
+                return None;
+            }
+            // Read the chunks of the rope without copying the text
+            self.tree = parser.parse_with(
+                &mut |byte, _| {
+                    if byte >= text.len_bytes() {
+                        return &[][..];
+                    }
+                    let (chunk, chunk_byte, _, _) = text.chunk_at_byte(byte);
+                    &chunk.as_bytes()[byte - chunk_byte..]
+                },
+                self.tree.as_ref(),
+            );
+            self.edited = false;
+        }
+        let tree = self.tree.as_ref()?;
+
+        let mut ranges = vec![];
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            if kinds.contains(&node.kind()) {
+                // Line comments may have the line break, which is the start of the next line
+                let mut range = node.byte_range();
+                if range.end > 0 && text.byte(range.end - 1) == b'\n' {
+                    range.end -= 1;
+                }
+                ranges.push(range);
+            } else if cursor.goto_first_child() {
+                continue;
+            }
+
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return Some(ranges);
+                }
+            }
+        }
+    }
+}
+
+/// All of the text is prose without tree-sitter
+#[cfg(not(feature = "tree-sitter"))]
+#[derive(Debug, Default)]
+pub(super) struct SyntaxTree;
+
+#[cfg(not(feature = "tree-sitter"))]
+impl SyntaxTree {
+    pub(super) fn edit(&mut self, _text: &Rope, _start: usize, _end: usize, _new_text: &str) {}
+
+    pub(super) fn reset(&mut self) {}
+
+    pub(super) fn prose_ranges(
+        &mut self,
+        _language_id: &str,
+        _text: &Rope,
+    ) -> Option<Vec<Range<usize>>> {
+        None
+    }
+}
+
+/// Whether the byte offset is in the prose. The end of a range is included for the cursor after a word
+pub(super) fn in_prose(ranges: &[Range<usize>], offset: usize) -> bool {
+    ranges
+        .iter()
+        .any(|range| range.start <= offset && offset <= range.end)
+}
+
+#[cfg(all(test, feature = "tree-sitter"))]
+mod tests {
+    use super::*;
+
+    fn prose<'a>(language_id: &str, text: &'a str) -> Vec<&'a str> {
+        SyntaxTree::default()
+            .prose_ranges(language_id, &Rope::from_str(text))
+            .unwrap()
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_rust() {
+        let text = "/// Doc comment\nfn main() {\n    let word = \"string\"; // comment\n    /* block */\n}\n";
+        assert_eq!(
+            prose("rust", text),
+            vec!["/// Doc comment", "\"string\"", "// comment", "/* block */"]
+        );
+    }
+
+    #[test]
+    fn test_python() {
+        let text =
+            "def f(x):\n    \"\"\"Docstring\"\"\"\n    # comment\n    return f\"value {x}\"\n";
+        assert_eq!(
+            prose("python", text),
+            vec!["Docstring", "# comment", "value "]
+        );
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut text = Rope::from_str("fn main() {\n    let x = 1;\n}\n");
+        let mut tree = SyntaxTree::default();
+        assert_eq!(tree.prose_ranges("rust", &text), Some(vec![]));
+
+        // Reparsed from the edited tree
+        let start = text.line_to_char(1) + 4;
+        let inserted = "// note\n    ";
+        tree.edit(&text, start, start, inserted);
+        text.insert(start, inserted);
+        let ranges = tree.prose_ranges("rust", &text).unwrap();
+        assert_eq!(
+            ranges,
+            SyntaxTree::default().prose_ranges("rust", &text).unwrap()
+        );
+        assert_eq!(text.byte_slice(ranges[0].clone()), "// note");
+
+        let end = start + inserted.chars().count();
+        tree.edit(&text, start, end, "");
+        text.remove(start..end);
+        assert_eq!(tree.prose_ranges("rust", &text), Some(vec![]));
+    }
+
+    #[test]
+    fn test_in_prose() {
+        assert!(SyntaxTree::default()
+            .prose_ranges("markdown", &Rope::from_str("# Title"))
+            .is_none());
+
+        let ranges = [3..6, 10..12];
+        assert!(in_prose(&ranges, 3));
+        assert!(in_prose(&ranges, 6));
+        assert!(!in_prose(&ranges, 8));
+        assert!(!in_prose(&[], 0));
+    }
+}